@@ -4,6 +4,7 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use fred::interfaces::EventInterface;
+use fred::interfaces::TransactionInterface;
 #[cfg(test)]
 use fred::mocks::Mocks;
 use fred::prelude::ClientLike;
@@ -17,6 +18,7 @@ use fred::types::FromRedis;
 use fred::types::PerformanceConfig;
 use fred::types::ReconnectPolicy;
 use fred::types::RedisConfig;
+use fred::types::SetOptions;
 use fred::types::TlsConfig;
 use fred::types::TlsHostMapping;
 use futures::FutureExt;
@@ -350,7 +352,6 @@ impl RedisCacheStorage {
         }
     }
 
-    #[allow(dead_code)]
     pub(crate) fn set_ttl(&mut self, ttl: Option<Duration>) {
         self.ttl = ttl;
     }
@@ -527,6 +528,55 @@ impl RedisCacheStorage {
         tracing::trace!("insert result {:?}", r);
     }
 
+    /// Atomically increments the integer stored at a key, and returns the new value
+    ///
+    /// The TTL is only set when the key is created by this increment. Creating the key with its
+    /// expiration and incrementing it happen in a single transaction, so the key cannot be left
+    /// without a TTL.
+    pub(crate) async fn incr<K: KeyType>(
+        &self,
+        key: RedisKey<K>,
+        amount: i64,
+        ttl: Option<Duration>,
+    ) -> Result<i64, RedisError> {
+        let key = self.make_key(key);
+        tracing::trace!("incrementing in redis: {:?}, {}", key, amount);
+        let Some(ttl) = ttl.as_ref().or(self.ttl.as_ref()) else {
+            return self.inner.incr_by::<i64, _>(&key, amount).await;
+        };
+
+        let transaction = self.inner.multi();
+        transaction
+            .set::<(), _, _>(
+                &key,
+                0,
+                Some(Expiration::EX(ttl.as_secs() as i64)),
+                Some(SetOptions::NX),
+                false,
+            )
+            .await?;
+        transaction.incr_by::<(), _>(&key, amount).await?;
+        let (_, value): (fred::types::RedisValue, i64) = transaction.exec(true).await?;
+        Ok(value)
+    }
+
+    /// Deletes keys, and returns the number of keys that were present
+    pub(crate) async fn delete<K: KeyType>(&self, keys: Vec<RedisKey<K>>) -> Option<u32> {
+        let keys = keys
+            .into_iter()
+            .map(|k| self.make_key(k))
+            .collect::<Vec<_>>();
+        tracing::trace!("deleting from redis: {:?}", keys);
+        self.inner
+            .del(keys)
+            .await
+            .map_err(|e| {
+                tracing::error!("delete error: {}", e);
+                e
+            })
+            .ok()
+    }
+
     pub(crate) async fn insert_multiple<K: KeyType, V: ValueType>(
         &self,
         data: &[(RedisKey<K>, RedisValue<V>)],
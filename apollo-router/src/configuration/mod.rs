@@ -28,6 +28,8 @@ use itertools::Itertools;
 use once_cell::sync::Lazy;
 pub(crate) use persisted_queries::PersistedQueries;
 #[cfg(test)]
+pub(crate) use persisted_queries::PersistedQueriesReportUnknown;
+#[cfg(test)]
 pub(crate) use persisted_queries::PersistedQueriesSafelist;
 use regex::Regex;
 use rustls::Certificate;
@@ -1409,4 +1411,34 @@ pub(crate) struct Batching {
 
     /// Batching mode
     pub(crate) mode: BatchingMode,
+
+    /// Subgraph options for batching
+    #[serde(default)]
+    pub(crate) subgraph: SubgraphConfiguration<CommonBatchingConfig>,
+}
+
+/// Common options for configuring subgraph batching
+#[derive(Debug, Clone, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields, default)]
+pub(crate) struct CommonBatchingConfig {
+    /// Groups the fetches to the subgraph in batched requests (disabled by default)
+    pub(crate) enabled: bool,
+}
+
+impl Batching {
+    /// Whether the fetches of a client batch to this subgraph are grouped in batched requests
+    pub(crate) fn batch_include(&self, service_name: &str) -> bool {
+        self.enabled && self.subgraph.get(service_name).enabled
+    }
+
+    /// Whether the fetches to at least one subgraph are grouped in batched requests
+    pub(crate) fn batches_subgraphs(&self) -> bool {
+        self.enabled
+            && (self.subgraph.all.enabled
+                || self
+                    .subgraph
+                    .subgraphs
+                    .values()
+                    .any(|config| config.enabled))
+    }
 }
@@ -1,7 +1,13 @@
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
 use schemars::JsonSchema;
 use serde::Deserialize;
 use serde::Serialize;
 
+use crate::configuration::ListenAddr;
+
 /// Persisted Queries (PQ) configuration
 #[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 #[serde(deny_unknown_fields, default)]
@@ -14,6 +20,22 @@ pub struct PersistedQueries {
 
     /// Restricts execution of operations that are not found in the Persisted Query List
     pub safelist: PersistedQueriesSafelist,
+
+    /// Paths of persisted query manifest files to use instead of the Persisted Query List from
+    /// GraphOS. The files are watched for changes
+    pub local_manifests: Option<Vec<PathBuf>>,
+
+    /// URLs of persisted query manifests to use instead of the Persisted Query List from GraphOS.
+    /// The manifests are polled for changes
+    pub manifest_urls: Option<Vec<String>>,
+
+    /// Interval between two checks of the manifest URLs for changes (default: 30s)
+    #[serde(with = "humantime_serde")]
+    #[schemars(with = "String")]
+    pub manifest_poll_interval: Duration,
+
+    /// Reports the freeform GraphQL operations that are not in the persisted query list
+    pub report_unknown: PersistedQueriesReportUnknown,
 }
 
 #[cfg(test)]
@@ -24,11 +46,20 @@ impl PersistedQueries {
         enabled: Option<bool>,
         log_unknown: Option<bool>,
         safelist: Option<PersistedQueriesSafelist>,
+        local_manifests: Option<Vec<PathBuf>>,
+        manifest_urls: Option<Vec<String>>,
+        manifest_poll_interval: Option<Duration>,
+        report_unknown: Option<PersistedQueriesReportUnknown>,
     ) -> Self {
         Self {
             enabled: enabled.unwrap_or_else(default_pq),
             safelist: safelist.unwrap_or_default(),
             log_unknown: log_unknown.unwrap_or_else(default_log_unknown),
+            local_manifests,
+            manifest_urls,
+            manifest_poll_interval: manifest_poll_interval
+                .unwrap_or_else(default_manifest_poll_interval),
+            report_unknown: report_unknown.unwrap_or_default(),
         }
     }
 }
@@ -56,12 +87,72 @@ impl PersistedQueriesSafelist {
     }
 }
 
+/// Persisted Queries (PQ) unknown operations report configuration
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields, default)]
+pub struct PersistedQueriesReportUnknown {
+    /// Counts the operations that are not in the persisted query list, and exposes the most
+    /// frequent ones on an endpoint (disabled by default)
+    pub enabled: bool,
+
+    /// The listen address of the report endpoint (default: 127.0.0.1:8088)
+    pub listen: ListenAddr,
+
+    /// The path of the report endpoint (default: /persisted-queries/unknown)
+    pub path: String,
+
+    /// Maximum number of distinct operations kept in the report (default: 1000)
+    pub max_operations: usize,
+
+    /// Maximum number of distinct client names counted per operation, the other clients are
+    /// counted as `__other__` (default: 100)
+    pub max_client_names: usize,
+}
+
+#[cfg(test)]
+#[buildstructor::buildstructor]
+impl PersistedQueriesReportUnknown {
+    #[builder]
+    pub(crate) fn new(
+        enabled: Option<bool>,
+        listen: Option<ListenAddr>,
+        path: Option<String>,
+        max_operations: Option<usize>,
+        max_client_names: Option<usize>,
+    ) -> Self {
+        Self {
+            enabled: enabled.unwrap_or_default(),
+            listen: listen.unwrap_or_else(default_report_unknown_listen),
+            path: path.unwrap_or_else(default_report_unknown_path),
+            max_operations: max_operations.unwrap_or_else(default_report_unknown_max_operations),
+            max_client_names: max_client_names
+                .unwrap_or_else(default_report_unknown_max_client_names),
+        }
+    }
+}
+
 impl Default for PersistedQueries {
     fn default() -> Self {
         Self {
             enabled: default_pq(),
             safelist: PersistedQueriesSafelist::default(),
             log_unknown: default_log_unknown(),
+            local_manifests: None,
+            manifest_urls: None,
+            manifest_poll_interval: default_manifest_poll_interval(),
+            report_unknown: PersistedQueriesReportUnknown::default(),
+        }
+    }
+}
+
+impl Default for PersistedQueriesReportUnknown {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            listen: default_report_unknown_listen(),
+            path: default_report_unknown_path(),
+            max_operations: default_report_unknown_max_operations(),
+            max_client_names: default_report_unknown_max_client_names(),
         }
     }
 }
@@ -90,3 +181,23 @@ const fn default_require_id() -> bool {
 const fn default_log_unknown() -> bool {
     false
 }
+
+const fn default_manifest_poll_interval() -> Duration {
+    Duration::from_secs(30)
+}
+
+fn default_report_unknown_listen() -> ListenAddr {
+    SocketAddr::from(([127, 0, 0, 1], 8088)).into()
+}
+
+fn default_report_unknown_path() -> String {
+    "/persisted-queries/unknown".to_string()
+}
+
+const fn default_report_unknown_max_operations() -> usize {
+    1000
+}
+
+const fn default_report_unknown_max_client_names() -> usize {
+    100
+}
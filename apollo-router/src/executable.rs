@@ -31,6 +31,7 @@ use crate::configuration::generate_upgrade;
 use crate::configuration::Discussed;
 use crate::metrics::meter_provider;
 use crate::plugin::plugins;
+use crate::plugins::rhai::runner::run_fixtures;
 use crate::plugins::telemetry::reload::init_telemetry;
 use crate::router::ConfigurationSource;
 use crate::router::RouterHttpServer;
@@ -116,6 +117,8 @@ extern "C" fn drop_ad_hoc_profiler() {
 enum Commands {
     /// Configuration subcommands.
     Config(ConfigSubcommandArgs),
+    /// Rhai subcommands.
+    Rhai(RhaiSubcommandArgs),
 }
 
 #[derive(Args, Debug)]
@@ -146,6 +149,35 @@ enum ConfigSubcommand {
     Preview,
 }
 
+#[derive(Args, Debug)]
+struct RhaiSubcommandArgs {
+    /// Subcommands
+    #[clap(subcommand)]
+    command: RhaiSubcommand,
+}
+
+#[derive(Subcommand, Debug)]
+enum RhaiSubcommand {
+    /// Run a Rhai script against fixture files, without starting the router.
+    Test {
+        /// The directory where Rhai scripts can be found.
+        #[clap(long, default_value = "rhai")]
+        scripts: PathBuf,
+
+        /// The main entry point for Rhai script evaluation, in the scripts directory.
+        #[clap(long, default_value = "main.rhai")]
+        main: String,
+
+        /// Schema location, available to the script as `Router.APOLLO_SDL`.
+        #[clap(long, value_parser)]
+        supergraph: Option<PathBuf>,
+
+        /// The fixture files describing the test cases.
+        #[clap(value_parser, required = true)]
+        fixtures: Vec<PathBuf>,
+    },
+}
+
 /// Options for the router
 #[derive(Parser, Debug)]
 #[clap(name = "router", about = "Apollo federation router")]
@@ -484,6 +516,24 @@ impl Executable {
                 Discussed::new().print_preview();
                 Ok(())
             }
+            Some(Commands::Rhai(RhaiSubcommandArgs {
+                command:
+                    RhaiSubcommand::Test {
+                        scripts,
+                        main,
+                        supergraph,
+                        fixtures,
+                    },
+            })) => {
+                let summary = run_fixtures(scripts, main, supergraph.as_deref(), fixtures)
+                    .await
+                    .map_err(|e| anyhow!(e))?;
+                if summary.failed > 0 {
+                    Err(anyhow!("{} Rhai test(s) failed", summary.failed))
+                } else {
+                    Ok(())
+                }
+            }
             None => Self::inner_start(shutdown, schema, config, license, opt).await,
         };
 
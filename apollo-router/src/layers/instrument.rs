@@ -89,6 +89,49 @@ where
 
     fn call(&mut self, req: Request) -> Self::Future {
         let span = (self.span_fn)(&req);
-        self.inner.call(req).instrument(span)
+        // Enter the span while calling the inner service, so that the work done synchronously
+        // by the inner layers (checkpoints, request mapping) happens in the span too
+        let future = {
+            let _guard = span.enter();
+            self.inner.call(req)
+        };
+        future.instrument(span)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tower::BoxError;
+    use tower::Service;
+    use tower::ServiceBuilder;
+    use tower::ServiceExt;
+    use tracing::info_span;
+    use tracing_subscriber::Registry;
+
+    use crate::layers::ServiceBuilderExt;
+
+    fn current_span_name() -> Option<&'static str> {
+        tracing::Span::current()
+            .metadata()
+            .map(|metadata| metadata.name())
+    }
+
+    #[tokio::test]
+    async fn test_layer() -> Result<(), BoxError> {
+        let _guard = tracing::subscriber::set_default(Registry::default());
+
+        let mut service = ServiceBuilder::new()
+            .instrument(|_: &()| info_span!("instrumented"))
+            .service_fn(|_: ()| {
+                // the synchronous part of the call
+                let in_call = current_span_name();
+                async move { Ok::<_, BoxError>((in_call, current_span_name())) }
+            });
+
+        let (in_call, in_future) = service.ready().await?.call(()).await?;
+        assert_eq!(in_call, Some("instrumented"));
+        assert_eq!(in_future, Some("instrumented"));
+        assert_eq!(current_span_name(), None);
+        Ok(())
     }
 }
@@ -5,6 +5,7 @@ use std::mem;
 use std::ops::DerefMut;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::sync::Weak;
 
 use derive_more::From;
 use itertools::Itertools;
@@ -29,6 +30,7 @@ use opentelemetry_api::metrics::MetricsError;
 use opentelemetry_api::metrics::Observer;
 
 use crate::metrics::filter::FilterMeterProvider;
+use crate::metrics::meter_provider;
 
 // This meter provider enables us to combine multiple meter providers. The reasons we need this are:
 // 1. Prometheus meters are special. To dispose a meter is to dispose the entire registry. This means we need to make a best effort to keep them around.
@@ -86,6 +88,7 @@ pub(crate) enum InstrumentWrapper {
     U64Histogram(Arc<Histogram<u64>>),
     F64Histogram(Arc<Histogram<f64>>),
     U64Gauge(Arc<ObservableGauge<u64>>),
+    F64Gauge(Arc<ObservableGauge<f64>>),
 }
 
 #[derive(Eq, PartialEq, Hash)]
@@ -136,7 +139,6 @@ impl AggregateMeterProvider {
     }
 
     /// Create a registered instrument. This enables caching at callsites and invalidation at the meter provider via weak reference.
-    #[allow(dead_code)]
     pub(crate) fn create_registered_instrument<T>(
         &self,
         create_fn: impl Fn(&mut Inner) -> T,
@@ -160,6 +162,37 @@ impl AggregateMeterProvider {
     }
 }
 
+/// Instruments created at runtime, by name.
+///
+/// They are registered with the meter provider, which drops them when a meter provider changes:
+/// they are then created again on their next use, like the instruments of the `metric!` macros.
+pub(crate) struct RegisteredInstruments<T> {
+    instruments: Mutex<HashMap<String, Weak<T>>>,
+}
+
+impl<T> Default for RegisteredInstruments<T> {
+    fn default() -> Self {
+        Self {
+            instruments: Default::default(),
+        }
+    }
+}
+
+impl<T> RegisteredInstruments<T>
+where
+    Arc<T>: Into<InstrumentWrapper>,
+{
+    pub(crate) fn get_or_create(&self, name: &str, create: impl Fn(&mut Inner) -> T) -> Arc<T> {
+        let mut instruments = self.instruments.lock().expect("lock poisoned");
+        if let Some(instrument) = instruments.get(name).and_then(Weak::upgrade) {
+            return instrument;
+        }
+        let instrument = meter_provider().create_registered_instrument(create);
+        instruments.insert(name.to_string(), Arc::downgrade(&instrument));
+        instrument
+    }
+}
+
 impl Inner {
     pub(crate) fn meter(&mut self, name: impl Into<Cow<'static, str>>) -> Meter {
         self.versioned_meter(
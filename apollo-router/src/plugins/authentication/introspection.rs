@@ -0,0 +1,348 @@
+//! OAuth2 token introspection ([RFC 7662](https://datatracker.ietf.org/doc/html/rfc7662))
+
+use std::num::NonZeroUsize;
+use std::ops::ControlFlow;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use http::header::ACCEPT;
+use http::StatusCode;
+use lru::LruCache;
+use mime::APPLICATION_JSON;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::Map;
+use serde_json::Value;
+use sha2::Digest;
+use sha2::Sha256;
+use tokio::sync::Mutex;
+use tower::BoxError;
+use url::Url;
+
+use super::default_header_name;
+use super::default_header_value_prefix;
+use super::extract_jwt;
+use super::AuthenticationError;
+use super::Header;
+use super::Source;
+use super::APOLLO_AUTHENTICATION_JWT_CLAIMS;
+use super::CLIENT;
+use super::DEFAULT_AUTHENTICATION_NETWORK_TIMEOUT;
+use crate::graphql;
+use crate::services::router;
+use crate::Context;
+
+const DEFAULT_INTROSPECTION_CACHE_CAPACITY: NonZeroUsize = match NonZeroUsize::new(1000) {
+    Some(capacity) => capacity,
+    None => unreachable!(),
+};
+const DEFAULT_INTROSPECTION_CACHE_TTL: Duration = Duration::from_secs(60);
+const DEFAULT_INTROSPECTION_INACTIVE_CACHE_TTL: Duration = Duration::from_secs(10);
+
+/// OAuth2 token introspection configuration
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(super) struct IntrospectionConf {
+    /// URL of the token introspection endpoint
+    endpoint: String,
+    /// Client identifier used to authenticate with the introspection endpoint
+    client_id: String,
+    /// Client secret used to authenticate with the introspection endpoint
+    client_secret: String,
+    /// Value of the `token_type_hint` parameter sent to the introspection endpoint
+    #[serde(default = "default_token_type_hint")]
+    token_type_hint: String,
+    /// Sources to extract the token from. Defaults to the `authorization` header with the `Bearer` prefix
+    #[serde(default = "default_sources")]
+    pub(super) sources: Vec<Source>,
+    /// Timeout for introspection requests in human-readable format; defaults to 15s
+    #[serde(
+        deserialize_with = "humantime_serde::deserialize",
+        default = "default_timeout"
+    )]
+    #[schemars(with = "String", default = "default_timeout")]
+    timeout: Duration,
+    /// List of headers to add to the introspection request
+    #[serde(default)]
+    headers: Vec<Header>,
+    /// Introspection results cache
+    #[serde(default)]
+    cache: IntrospectionCacheConf,
+}
+
+/// Introspection results cache configuration
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields, default)]
+pub(super) struct IntrospectionCacheConf {
+    /// Number of entries in the Least Recently Used cache
+    capacity: NonZeroUsize,
+    /// Maximum time an active token is cached, in human-readable format; defaults to 60s.
+    /// An active token is never cached past its `exp` claim
+    #[serde(deserialize_with = "humantime_serde::deserialize")]
+    #[schemars(with = "String")]
+    ttl: Duration,
+    /// Time an inactive token is cached, in human-readable format; defaults to 10s
+    #[serde(deserialize_with = "humantime_serde::deserialize")]
+    #[schemars(with = "String")]
+    inactive_ttl: Duration,
+}
+
+impl Default for IntrospectionCacheConf {
+    fn default() -> Self {
+        Self {
+            capacity: DEFAULT_INTROSPECTION_CACHE_CAPACITY,
+            ttl: DEFAULT_INTROSPECTION_CACHE_TTL,
+            inactive_ttl: DEFAULT_INTROSPECTION_INACTIVE_CACHE_TTL,
+        }
+    }
+}
+
+fn default_token_type_hint() -> String {
+    "access_token".to_string()
+}
+
+fn default_sources() -> Vec<Source> {
+    vec![Source::Header {
+        name: default_header_name(),
+        value_prefix: default_header_value_prefix(),
+    }]
+}
+
+fn default_timeout() -> Duration {
+    DEFAULT_AUTHENTICATION_NETWORK_TIMEOUT
+}
+
+/// The result of an introspection request, as cached by the [`IntrospectionManager`]
+#[derive(Clone, Debug)]
+enum Introspection {
+    Active(Value),
+    Inactive,
+}
+
+#[derive(Clone)]
+struct CachedIntrospection {
+    introspection: Introspection,
+    expires_at: Instant,
+}
+
+#[derive(Clone)]
+pub(super) struct IntrospectionManager {
+    configuration: Arc<IntrospectionConf>,
+    endpoint: Url,
+    cache: Arc<Mutex<LruCache<String, CachedIntrospection>>>,
+}
+
+impl IntrospectionManager {
+    pub(super) fn new(configuration: IntrospectionConf) -> Result<Self, BoxError> {
+        let endpoint = Url::from_str(configuration.endpoint.as_str())?;
+        let cache = LruCache::new(configuration.cache.capacity);
+
+        Ok(Self {
+            configuration: Arc::new(configuration),
+            endpoint,
+            cache: Arc::new(Mutex::new(cache)),
+        })
+    }
+
+    /// Returns the claims associated with an active token, or `None` if the token is inactive
+    async fn introspect(&self, token: &str) -> Result<Option<Value>, AuthenticationError<'_>> {
+        // tokens are credentials, so we do not keep them around in memory
+        let key = hex::encode(Sha256::digest(token.as_bytes()));
+
+        if let Some(cached) = self.cache.lock().await.get(&key) {
+            if cached.expires_at > Instant::now() {
+                return Ok(match &cached.introspection {
+                    Introspection::Active(claims) => Some(claims.clone()),
+                    Introspection::Inactive => None,
+                });
+            }
+        }
+
+        let introspection = self.fetch(token).await?;
+        let ttl = match &introspection {
+            Introspection::Active(claims) => expires_in(claims)
+                .map_or(self.configuration.cache.ttl, |expires_in| {
+                    expires_in.min(self.configuration.cache.ttl)
+                }),
+            Introspection::Inactive => self.configuration.cache.inactive_ttl,
+        };
+
+        if !ttl.is_zero() {
+            self.cache.lock().await.put(
+                key,
+                CachedIntrospection {
+                    introspection: introspection.clone(),
+                    expires_at: Instant::now() + ttl,
+                },
+            );
+        }
+
+        Ok(match introspection {
+            Introspection::Active(claims) => Some(claims),
+            Introspection::Inactive => None,
+        })
+    }
+
+    async fn fetch(&self, token: &str) -> Result<Introspection, AuthenticationError<'_>> {
+        let client = CLIENT
+            .as_ref()
+            .map_err(|e| introspection_failed(e.to_string()))?
+            .clone();
+
+        let mut builder = client
+            .post(self.endpoint.clone())
+            .basic_auth(
+                &self.configuration.client_id,
+                Some(&self.configuration.client_secret),
+            )
+            .header(ACCEPT, APPLICATION_JSON.essence_str())
+            .form(&[
+                ("token", token),
+                (
+                    "token_type_hint",
+                    self.configuration.token_type_hint.as_str(),
+                ),
+            ]);
+
+        for header in self.configuration.headers.iter() {
+            builder = builder.header(header.name.clone(), header.value.clone());
+        }
+
+        let response = builder
+            .timeout(self.configuration.timeout)
+            .send()
+            .await
+            .map_err(|e| introspection_failed(e.to_string()))?;
+
+        if !response.status().is_success() {
+            return Err(introspection_failed(format!(
+                "introspection endpoint returned status {}",
+                response.status()
+            )));
+        }
+
+        let mut body: Map<String, Value> = response
+            .json()
+            .await
+            .map_err(|e| introspection_failed(e.to_string()))?;
+
+        match body.remove("active") {
+            Some(Value::Bool(true)) => Ok(Introspection::Active(Value::Object(body))),
+            Some(Value::Bool(false)) => Ok(Introspection::Inactive),
+            _ => Err(introspection_failed(
+                "the introspection response does not contain a boolean `active` field".to_string(),
+            )),
+        }
+    }
+}
+
+/// Logs the reason of an introspection failure, which may contain the endpoint URL, and returns
+/// an error that can be shown to the client
+fn introspection_failed(reason: String) -> AuthenticationError<'static> {
+    tracing::error!("token introspection failed: {reason}");
+    AuthenticationError::IntrospectionFailed
+}
+
+/// Time until the `exp` claim of an introspection response, if present
+fn expires_in(claims: &Value) -> Option<Duration> {
+    let exp = claims.get("exp")?.as_u64()?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("we should not run before EPOCH")
+        .as_secs();
+    Some(Duration::from_secs(exp.saturating_sub(now)))
+}
+
+pub(super) async fn authenticate(
+    manager: &IntrospectionManager,
+    request: router::Request,
+) -> ControlFlow<router::Response, router::Request> {
+    const AUTHENTICATION_KIND: &str = "INTROSPECTION";
+
+    fn failure_message(
+        context: Context,
+        error: AuthenticationError,
+        status: StatusCode,
+    ) -> ControlFlow<router::Response, router::Request> {
+        // This is a metric and will not appear in the logs
+        tracing::info!(
+            monotonic_counter.apollo_authentication_failure_count = 1u64,
+            kind = %AUTHENTICATION_KIND
+        );
+        tracing::info!(message = %error, "token introspection failure");
+        let response = router::Response::infallible_builder()
+            .error(
+                graphql::Error::builder()
+                    .message(error.to_string())
+                    .extension_code("AUTH_ERROR")
+                    .build(),
+            )
+            .status_code(status)
+            .context(context)
+            .build();
+        ControlFlow::Break(response)
+    }
+
+    // the request was already authenticated by another source
+    if request
+        .context
+        .contains_key(APOLLO_AUTHENTICATION_JWT_CLAIMS)
+    {
+        return ControlFlow::Continue(request);
+    }
+
+    let mut token = None;
+    for source in &manager.configuration.sources {
+        match extract_jwt(source, request.router_request.headers()) {
+            None => continue,
+            Some(Err(error)) => {
+                return failure_message(request.context, error, StatusCode::BAD_REQUEST)
+            }
+            Some(Ok(extracted_token)) => {
+                token = Some(extracted_token);
+                break;
+            }
+        }
+    }
+
+    let token = match token {
+        Some(token) => token,
+        None => return ControlFlow::Continue(request),
+    };
+
+    let claims = match manager.introspect(token).await {
+        Ok(Some(claims)) => claims,
+        Ok(None) => {
+            return failure_message(
+                request.context,
+                AuthenticationError::InactiveToken,
+                StatusCode::UNAUTHORIZED,
+            )
+        }
+        Err(error) => {
+            return failure_message(request.context, error, StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    };
+
+    if let Err(e) = request
+        .context
+        .insert(APOLLO_AUTHENTICATION_JWT_CLAIMS, claims)
+    {
+        return failure_message(
+            request.context,
+            AuthenticationError::CannotInsertClaimsIntoContext(e),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        );
+    }
+
+    // This is a metric and will not appear in the logs
+    tracing::info!(
+        monotonic_counter.apollo_authentication_success_count = 1u64,
+        kind = %AUTHENTICATION_KIND
+    );
+    ControlFlow::Continue(request)
+}
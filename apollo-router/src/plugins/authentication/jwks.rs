@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::mem;
+use std::pin::Pin;
 use std::sync::Arc;
 use std::sync::RwLock;
 use std::time::Duration;
@@ -11,6 +12,7 @@ use futures::future::Either;
 use futures::pin_mut;
 use futures::stream::repeat;
 use futures::stream::select_all;
+use futures::Stream;
 use http::header::ACCEPT;
 use http::header::CONTENT_TYPE;
 use jsonwebtoken::jwk::Jwk;
@@ -18,6 +20,12 @@ use jsonwebtoken::jwk::JwkSet;
 use jsonwebtoken::Algorithm;
 use mime::APPLICATION_JSON;
 use serde_json::Value;
+use spki::der::asn1::UintRef;
+use spki::der::Decode;
+use spki::der::Reader;
+use spki::der::SliceReader;
+use spki::ObjectIdentifier;
+use spki::SubjectPublicKeyInfoRef;
 use tokio::fs::read_to_string;
 use tokio::sync::oneshot;
 use tower::BoxError;
@@ -37,13 +45,32 @@ pub(super) struct JwksManager {
 
 #[derive(Clone)]
 pub(super) struct JwksConfig {
-    pub(super) url: Url,
+    pub(super) source: JwksSource,
     pub(super) issuer: Option<String>,
     pub(super) algorithms: Option<HashSet<Algorithm>>,
     pub(super) poll_interval: Duration,
     pub(super) headers: Vec<Header>,
 }
 
+#[derive(Clone)]
+pub(super) enum JwksSource {
+    /// Downloaded from a URL, and refreshed every `poll_interval`
+    Url(Url),
+    /// Read from the local file at that `file://` URL, and reloaded when the file changes
+    File(Url),
+    /// Defined in the configuration
+    Inline(JwkSet),
+}
+
+impl JwksSource {
+    fn url(&self) -> Option<&Url> {
+        match self {
+            JwksSource::Url(url) | JwksSource::File(url) => Some(url),
+            JwksSource::Inline(_) => None,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub(super) struct JwkSetInfo {
     pub(super) jwks: JwkSet,
@@ -57,13 +84,19 @@ impl JwksManager {
 
         let downloads = list
             .iter()
-            .cloned()
-            .map(|JwksConfig { url, headers, .. }| {
-                let span = tracing::info_span!("fetch jwks", url = %url);
-                get_jwks(url.clone(), headers.clone())
-                    .map(|opt_jwks| opt_jwks.map(|jwks| (url, jwks)))
-                    .instrument(span)
-            })
+            .filter_map(
+                |JwksConfig {
+                     source, headers, ..
+                 }| {
+                    let url = source.url()?.clone();
+                    let span = tracing::info_span!("fetch jwks", url = %url);
+                    Some(
+                        get_jwks(url.clone(), headers.clone())
+                            .map(|opt_jwks| opt_jwks.map(|jwks| (url, jwks)))
+                            .instrument(span),
+                    )
+                },
+            )
             .collect::<Vec<_>>();
 
         let jwks_map: HashMap<_, _> = join_all(downloads).await.into_iter().flatten().collect();
@@ -106,19 +139,38 @@ async fn poll(
 ) {
     use futures::stream::StreamExt;
 
-    let mut streams = select_all(list.into_iter().map(move |config| {
+    let mut streams = select_all(list.into_iter().filter_map(move |config| {
         let jwks_map = jwks_map.clone();
-        Box::pin(
-            repeat((config, jwks_map)).then(|(config, jwks_map)| async move {
-                tokio::time::sleep(config.poll_interval).await;
+        let stream: Pin<Box<dyn Stream<Item = ()> + Send>> = match config.source.clone() {
+            JwksSource::Url(url) => Box::pin(repeat((config, url, jwks_map)).then(
+                |(config, url, jwks_map)| async move {
+                    tokio::time::sleep(config.poll_interval).await;
 
-                if let Some(jwks) = get_jwks(config.url.clone(), config.headers.clone()).await {
-                    if let Ok(mut map) = jwks_map.write() {
-                        map.insert(config.url, jwks);
+                    if let Some(jwks) = get_jwks(url.clone(), config.headers.clone()).await {
+                        if let Ok(mut map) = jwks_map.write() {
+                            map.insert(url, jwks);
+                        }
                     }
-                }
-            }),
-        )
+                },
+            )),
+            JwksSource::File(url) => {
+                let path = url.to_file_path().ok()?;
+                Box::pin(crate::files::watch(&path).then(move |_| {
+                    let url = url.clone();
+                    let jwks_map = jwks_map.clone();
+                    async move {
+                        if let Some(jwks) = get_jwks(url.clone(), Vec::new()).await {
+                            tracing::info!(%url, "reloaded JWKS");
+                            if let Ok(mut map) = jwks_map.write() {
+                                map.insert(url, jwks);
+                            }
+                        }
+                    }
+                }))
+            }
+            JwksSource::Inline(_) => return None,
+        };
+        Some(stream)
     }));
 
     pin_mut!(drop_receiver);
@@ -130,7 +182,7 @@ async fn poll(
         match select(drop_receiver, next).await {
             // the _drop_signal was dropped, we must shut down the task
             Either::Left((_res, _)) => return,
-            // another JWKS download was performed
+            // another JWKS download or reload was performed
             Either::Right((Some(()), receiver)) => {
                 drop_receiver = receiver;
             }
@@ -250,8 +302,18 @@ impl<'a> Iterator for Iter<'a> {
             match self.list.pop() {
                 None => return None,
                 Some(config) => {
+                    let url = match &config.source {
+                        JwksSource::Inline(jwks) => {
+                            return Some(JwkSetInfo {
+                                jwks: jwks.clone(),
+                                issuer: config.issuer.clone(),
+                                algorithms: config.algorithms.clone(),
+                            })
+                        }
+                        JwksSource::Url(url) | JwksSource::File(url) => url,
+                    };
                     if let Ok(map) = self.manager.jwks_map.read() {
-                        if let Some(jwks) = map.get(&config.url) {
+                        if let Some(jwks) = map.get(url) {
                             return Some(JwkSetInfo {
                                 jwks: jwks.clone(),
                                 issuer: config.issuer.clone(),
@@ -266,3 +328,85 @@ impl<'a> Iterator for Iter<'a> {
         }
     }
 }
+
+const OID_RSA_ENCRYPTION: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.1");
+const OID_EC_PUBLIC_KEY: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.2.1");
+const OID_P256: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.3.1.7");
+const OID_P384: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.132.0.34");
+const OID_ED25519: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.101.112");
+
+/// Converts a PEM encoded public key to a JWK
+///
+/// Both SubjectPublicKeyInfo (`PUBLIC KEY`) and PKCS#1 (`RSA PUBLIC KEY`) encodings are
+/// supported, for RSA, P-256, P-384 and Ed25519 keys.
+pub(super) fn pem_to_jwk(
+    pem: &str,
+    algorithm: Algorithm,
+    key_id: Option<String>,
+) -> Result<Jwk, BoxError> {
+    use base64::prelude::BASE64_URL_SAFE_NO_PAD;
+    use base64::Engine as _;
+
+    let pem = pem::parse(pem.trim())?;
+
+    let encode = |bytes: &[u8]| BASE64_URL_SAFE_NO_PAD.encode(bytes);
+    let rsa_key = |pkcs1: &[u8]| -> Result<Value, BoxError> {
+        // RSAPublicKey ::= SEQUENCE { modulus INTEGER, publicExponent INTEGER }
+        let mut reader = SliceReader::new(pkcs1)?;
+        let (n, e) = reader.sequence(|key| Ok((UintRef::decode(key)?, UintRef::decode(key)?)))?;
+        reader.finish(())?;
+        Ok(serde_json::json!({
+            "kty": "RSA",
+            "n": encode(n.as_bytes()),
+            "e": encode(e.as_bytes()),
+        }))
+    };
+
+    let mut key = match pem.tag() {
+        "RSA PUBLIC KEY" => rsa_key(pem.contents())?,
+        "PUBLIC KEY" => {
+            let spki = SubjectPublicKeyInfoRef::try_from(pem.contents())?;
+            let public_key = spki
+                .subject_public_key
+                .as_bytes()
+                .ok_or("unsupported public key padding")?;
+
+            match spki.algorithm.oid {
+                OID_RSA_ENCRYPTION => rsa_key(public_key)?,
+                OID_EC_PUBLIC_KEY => {
+                    let curve = match spki.algorithm.parameters_oid()? {
+                        OID_P256 => "P-256",
+                        OID_P384 => "P-384",
+                        _ => return Err("unsupported elliptic curve".into()),
+                    };
+                    // only uncompressed points are supported
+                    let point = public_key
+                        .strip_prefix(&[0x04])
+                        .ok_or("unsupported compressed elliptic curve point")?;
+                    let (x, y) = point.split_at(point.len() / 2);
+                    serde_json::json!({
+                        "kty": "EC",
+                        "crv": curve,
+                        "x": encode(x),
+                        "y": encode(y),
+                    })
+                }
+                OID_ED25519 => serde_json::json!({
+                    "kty": "OKP",
+                    "crv": "Ed25519",
+                    "x": encode(public_key),
+                }),
+                _ => return Err("unsupported public key algorithm".into()),
+            }
+        }
+        label => return Err(format!("unsupported PEM label '{label}'").into()),
+    };
+
+    key["alg"] = serde_json::to_value(algorithm)?;
+    key["use"] = "sig".into();
+    if let Some(key_id) = key_id {
+        key["kid"] = key_id.into();
+    }
+
+    Ok(serde_json::from_value(key)?)
+}
@@ -2,12 +2,14 @@
 
 use std::collections::HashMap;
 use std::ops::ControlFlow;
+use std::path::PathBuf;
 use std::str::FromStr;
 use std::time::Duration;
 use std::time::SystemTime;
 use std::time::UNIX_EPOCH;
 
 use displaydoc::Display;
+use futures::FutureExt;
 use http::HeaderMap;
 use http::HeaderName;
 use http::HeaderValue;
@@ -18,6 +20,7 @@ use jsonwebtoken::errors::Error as JWTError;
 use jsonwebtoken::jwk::AlgorithmParameters;
 use jsonwebtoken::jwk::EllipticCurve;
 use jsonwebtoken::jwk::Jwk;
+use jsonwebtoken::jwk::JwkSet;
 use jsonwebtoken::jwk::KeyAlgorithm;
 use jsonwebtoken::jwk::KeyOperations;
 use jsonwebtoken::jwk::PublicKeyUse;
@@ -36,9 +39,11 @@ use tower::ServiceBuilder;
 use tower::ServiceExt;
 use url::Url;
 
+use self::introspection::IntrospectionConf;
+use self::introspection::IntrospectionManager;
 use self::jwks::JwksManager;
+use self::subgraph::AuthParams;
 use self::subgraph::SigningParams;
-use self::subgraph::SigningParamsConfig;
 use self::subgraph::SubgraphAuth;
 use crate::graphql;
 use crate::layers::ServiceBuilderExt;
@@ -48,12 +53,15 @@ use crate::plugin::Plugin;
 use crate::plugin::PluginInit;
 use crate::plugins::authentication::jwks::JwkSetInfo;
 use crate::plugins::authentication::jwks::JwksConfig;
+use crate::plugins::authentication::jwks::JwksSource;
 use crate::register_plugin;
 use crate::services::router;
 use crate::Context;
 
+mod introspection;
 mod jwks;
 pub(crate) mod subgraph;
+mod tokens;
 
 #[cfg(test)]
 mod tests;
@@ -99,6 +107,12 @@ pub(crate) enum AuthenticationError<'a> {
 
     /// Unsupported key algorithm: {0}
     UnsupportedKeyAlgorithm(KeyAlgorithm),
+
+    /// Token is not active
+    InactiveToken,
+
+    /// Token introspection failed
+    IntrospectionFailed,
 }
 
 const DEFAULT_AUTHENTICATION_NETWORK_TIMEOUT: Duration = Duration::from_secs(15);
@@ -110,15 +124,20 @@ static CLIENT: Lazy<Result<Client, BoxError>> = Lazy::new(|| Ok(Client::new()));
 pub(crate) enum Error {
     #[error("header_value_prefix must not contain whitespace")]
     BadHeaderValuePrefix,
+    #[error("a JWKS must define exactly one of url, path or keys")]
+    InvalidJwksSource,
 }
 
 struct Router {
     configuration: JWTConf,
     jwks_manager: JwksManager,
+    /// Let tokens which are not JWTs through, so that they can be introspected
+    allow_opaque_tokens: bool,
 }
 
 struct AuthenticationPlugin {
     router: Option<Router>,
+    introspection: Option<IntrospectionManager>,
     subgraph: Option<SubgraphAuth>,
 }
 
@@ -143,7 +162,11 @@ struct JWTConf {
 #[serde(deny_unknown_fields)]
 struct JwksConf {
     /// Retrieve the JWK Set
-    url: String,
+    url: Option<String>,
+    /// Path of a local JWK Set file, reloaded when the file changes
+    path: Option<PathBuf>,
+    /// Keys defined in the configuration, as JWKs or PEM encoded public keys
+    keys: Option<Vec<InlineKey>>,
     /// Polling interval for each JWKS endpoint in human-readable format; defaults to 60s
     #[serde(
         deserialize_with = "humantime_serde::deserialize",
@@ -162,6 +185,51 @@ struct JwksConf {
     headers: Vec<Header>,
 }
 
+/// A key defined in the configuration
+#[derive(Clone, Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum InlineKey {
+    /// PEM encoded public key
+    Pem {
+        /// The public key, in PEM format
+        pem: String,
+        /// Algorithm of the key. Possible values are `ES256`, `ES384`, `RS256`, `RS384`, `RS512`, `PS256`, `PS384`, `PS512`, `EdDSA`
+        #[schemars(with = "String")]
+        algorithm: Algorithm,
+        /// Key identifier, matched with the `kid` header of the JWT
+        kid: Option<String>,
+    },
+    /// JSON Web Key
+    Jwk(serde_json::Map<String, Value>),
+}
+
+impl InlineKey {
+    fn to_jwk(&self) -> Result<Jwk, BoxError> {
+        match self {
+            InlineKey::Pem {
+                pem,
+                algorithm,
+                kid,
+            } => jwks::pem_to_jwk(pem, *algorithm, kid.clone()),
+            InlineKey::Jwk(jwk) => Ok(serde_json::from_value(Value::Object(jwk.clone()))?),
+        }
+    }
+}
+
+// Keys may contain secrets for symmetric algorithms, so they must not appear in logs
+impl std::fmt::Debug for InlineKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InlineKey::Pem { algorithm, kid, .. } => f
+                .debug_struct("Pem")
+                .field("algorithm", algorithm)
+                .field("kid", kid)
+                .finish(),
+            InlineKey::Jwk(jwk) => f.debug_struct("Jwk").field("kid", &jwk.get("kid")).finish(),
+        }
+    }
+}
+
 #[derive(Clone, Debug, JsonSchema, Deserialize)]
 #[serde(rename_all = "snake_case", deny_unknown_fields)]
 /// Insert a header
@@ -204,14 +272,16 @@ struct Conf {
     subgraph: Option<subgraph::Config>,
 }
 
-// We may support additional authentication mechanisms in future, so all
-// configuration (which is currently JWT specific) is isolated to the
-// JWTConf structure.
+// Each authentication mechanism has its own configuration structure. When
+// several mechanisms are configured, JWT validation runs first and tokens
+// which are not JWTs are handed over to token introspection.
 #[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 struct RouterConf {
     /// The JWT configuration
-    jwt: JWTConf,
+    jwt: Option<JWTConf>,
+    /// The OAuth2 token introspection configuration
+    introspection: Option<IntrospectionConf>,
 }
 
 fn default_header_name() -> String {
@@ -410,16 +480,16 @@ impl Plugin for AuthenticationPlugin {
     async fn new(init: PluginInit<Self::Config>) -> Result<Self, BoxError> {
         let subgraph = if let Some(config) = init.config.subgraph {
             let all = if let Some(config) = &config.all {
-                Some(subgraph::make_signing_params(config, "all").await?)
+                Some(subgraph::make_auth_params(config, "all").await?)
             } else {
                 None
             };
 
-            let mut subgraphs: HashMap<String, SigningParamsConfig> = Default::default();
+            let mut subgraphs: HashMap<String, AuthParams> = Default::default();
             for (subgraph_name, config) in &config.subgraphs {
                 subgraphs.insert(
                     subgraph_name.clone(),
-                    subgraph::make_signing_params(config, subgraph_name.as_str()).await?,
+                    subgraph::make_auth_params(config, subgraph_name.as_str()).await?,
                 );
             }
 
@@ -430,9 +500,27 @@ impl Plugin for AuthenticationPlugin {
             None
         };
 
-        let router = if let Some(mut router_conf) = init.config.router {
-            if router_conf
-                .jwt
+        let (jwt_conf, introspection_conf) = match init.config.router {
+            Some(RouterConf { jwt, introspection }) => (jwt, introspection),
+            None => (None, None),
+        };
+
+        let introspection = if let Some(introspection_conf) = introspection_conf {
+            for source in &introspection_conf.sources {
+                if let Source::Header { value_prefix, .. } = source {
+                    if value_prefix.as_bytes().iter().any(u8::is_ascii_whitespace) {
+                        return Err(Error::BadHeaderValuePrefix.into());
+                    }
+                }
+            }
+
+            Some(IntrospectionManager::new(introspection_conf)?)
+        } else {
+            None
+        };
+
+        let router = if let Some(mut jwt_conf) = jwt_conf {
+            if jwt_conf
                 .header_value_prefix
                 .as_bytes()
                 .iter()
@@ -441,7 +529,7 @@ impl Plugin for AuthenticationPlugin {
                 return Err(Error::BadHeaderValuePrefix.into());
             }
 
-            for source in &router_conf.jwt.sources {
+            for source in &jwt_conf.sources {
                 if let Source::Header { value_prefix, .. } = source {
                     if value_prefix.as_bytes().iter().any(u8::is_ascii_whitespace) {
                         return Err(Error::BadHeaderValuePrefix.into());
@@ -449,19 +537,37 @@ impl Plugin for AuthenticationPlugin {
                 }
             }
 
-            router_conf.jwt.sources.insert(
+            jwt_conf.sources.insert(
                 0,
                 Source::Header {
-                    name: router_conf.jwt.header_name.clone(),
-                    value_prefix: router_conf.jwt.header_value_prefix.clone(),
+                    name: jwt_conf.header_name.clone(),
+                    value_prefix: jwt_conf.header_value_prefix.clone(),
                 },
             );
 
             let mut list = vec![];
-            for jwks_conf in &router_conf.jwt.jwks {
-                let url: Url = Url::from_str(jwks_conf.url.as_str())?;
+            for jwks_conf in &jwt_conf.jwks {
+                let source = match (&jwks_conf.url, &jwks_conf.path, &jwks_conf.keys) {
+                    (Some(url), None, None) => JwksSource::Url(Url::from_str(url.as_str())?),
+                    (None, Some(path), None) => {
+                        let path = std::fs::canonicalize(path).map_err(|e| {
+                            format!("could not find JWKS file {}: {e}", path.display())
+                        })?;
+                        JwksSource::File(
+                            Url::from_file_path(&path)
+                                .map_err(|_| format!("invalid JWKS path {}", path.display()))?,
+                        )
+                    }
+                    (None, None, Some(keys)) => JwksSource::Inline(JwkSet {
+                        keys: keys
+                            .iter()
+                            .map(InlineKey::to_jwk)
+                            .collect::<Result<_, _>>()?,
+                    }),
+                    _ => return Err(Error::InvalidJwksSource.into()),
+                };
                 list.push(JwksConfig {
-                    url,
+                    source,
                     issuer: jwks_conf.issuer.clone(),
                     algorithms: jwks_conf
                         .algorithms
@@ -472,46 +578,80 @@ impl Plugin for AuthenticationPlugin {
                 });
             }
 
-            tracing::info!(jwks=?router_conf.jwt.jwks, "JWT authentication using JWKSets from");
+            tracing::info!(jwks=?jwt_conf.jwks, "JWT authentication using JWKSets from");
 
             let jwks_manager = JwksManager::new(list).await?;
 
             Some(Router {
-                configuration: router_conf.jwt,
+                configuration: jwt_conf,
                 jwks_manager,
+                allow_opaque_tokens: introspection.is_some(),
             })
         } else {
             None
         };
 
-        Ok(Self { router, subgraph })
+        Ok(Self {
+            router,
+            introspection,
+            subgraph,
+        })
     }
 
     fn router_service(&self, service: router::BoxService) -> router::BoxService {
-        if let Some(config) = &self.router {
-            let jwks_manager = config.jwks_manager.clone();
-            let configuration = config.configuration.clone();
-
-            fn authentication_service_span() -> impl Fn(&router::Request) -> tracing::Span + Clone {
-                move |_request: &router::Request| {
-                    tracing::info_span!(
-                        AUTHENTICATION_SPAN_NAME,
-                        "authentication service" = stringify!(router::Request),
-                        "otel.kind" = "INTERNAL"
-                    )
-                }
+        if self.router.is_none() && self.introspection.is_none() {
+            return service;
+        }
+
+        fn authentication_service_span() -> impl Fn(&router::Request) -> tracing::Span + Clone {
+            move |_request: &router::Request| {
+                tracing::info_span!(
+                    AUTHENTICATION_SPAN_NAME,
+                    "authentication service" = stringify!(router::Request),
+                    "otel.kind" = "INTERNAL"
+                )
             }
+        }
 
+        // token introspection runs after JWT validation, for the tokens that
+        // were not recognized as JWTs
+        let service = if let Some(manager) = &self.introspection {
+            let manager = manager.clone();
             ServiceBuilder::new()
-                .instrument(authentication_service_span())
-                .checkpoint(move |request: router::Request| {
-                    Ok(authenticate(&configuration, &jwks_manager, request))
+                .oneshot_checkpoint_async(move |request: router::Request| {
+                    let manager = manager.clone();
+                    async move {
+                        Ok::<_, BoxError>(introspection::authenticate(&manager, request).await)
+                    }
+                    .boxed()
                 })
                 .service(service)
                 .boxed()
         } else {
             service
-        }
+        };
+
+        let jwt = self.router.as_ref().map(|config| {
+            (
+                config.configuration.clone(),
+                config.jwks_manager.clone(),
+                config.allow_opaque_tokens,
+            )
+        });
+
+        ServiceBuilder::new()
+            .instrument(authentication_service_span())
+            .checkpoint(move |request: router::Request| match &jwt {
+                Some((configuration, jwks_manager, allow_opaque_tokens)) => Ok(authenticate(
+                    configuration,
+                    jwks_manager,
+                    *allow_opaque_tokens,
+                    request,
+                )),
+                None => Ok(ControlFlow::Continue(request)),
+            })
+            .service(service)
+            .boxed()
     }
 
     fn subgraph_service(
@@ -530,6 +670,7 @@ impl Plugin for AuthenticationPlugin {
 fn authenticate(
     config: &JWTConf,
     jwks_manager: &JwksManager,
+    allow_opaque_tokens: bool,
     request: router::Request,
 ) -> ControlFlow<router::Response, router::Request> {
     const AUTHENTICATION_KIND: &str = "JWT";
@@ -591,6 +732,8 @@ fn authenticate(
     // Try to create a valid header to work with
     let jwt_header = match decode_header(jwt) {
         Ok(h) => h,
+        // This is not a JWT, it may be an opaque token validated by introspection
+        Err(_) if allow_opaque_tokens => return ControlFlow::Continue(request),
         Err(e) => {
             // Don't reflect the jwt on error, just reply with a fixed
             // error message.
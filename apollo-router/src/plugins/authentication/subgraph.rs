@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::ops::ControlFlow;
 use std::sync::Arc;
 use std::time::SystemTime;
 
@@ -11,7 +12,10 @@ use aws_sigv4::http_request::SignableRequest;
 use aws_sigv4::http_request::SigningSettings;
 use aws_smithy_runtime_api::client::identity::Identity;
 use aws_types::region::Region;
+use futures::FutureExt;
+use http::header::AUTHORIZATION;
 use http::HeaderMap;
+use http::HeaderValue;
 use http::Request;
 use hyper::Body;
 use schemars::JsonSchema;
@@ -20,6 +24,11 @@ use tower::BoxError;
 use tower::ServiceBuilder;
 use tower::ServiceExt;
 
+use super::tokens::ForwardJwtConfig;
+use super::tokens::OAuth2ClientCredentialsConfig;
+use super::tokens::TokenFileConfig;
+use super::tokens::TokenProvider;
+use crate::layers::ServiceBuilderExt;
 use crate::services::SubgraphRequest;
 
 /// Hardcoded Config using access_key and secret.
@@ -168,6 +177,12 @@ impl AWSSigV4Config {
 pub(crate) enum AuthConfig {
     #[serde(rename = "aws_sig_v4")]
     AWSSigV4(AWSSigV4Config),
+    #[serde(rename = "oauth2_client_credentials")]
+    OAuth2ClientCredentials(OAuth2ClientCredentialsConfig),
+    #[serde(rename = "token_file")]
+    TokenFile(TokenFileConfig),
+    #[serde(rename = "forward_jwt")]
+    ForwardJwt(ForwardJwtConfig),
 }
 
 /// Configure subgraph authentication
@@ -185,8 +200,16 @@ pub(crate) struct Config {
 #[allow(dead_code)]
 #[derive(Clone, Default)]
 pub(crate) struct SigningParams {
-    pub(crate) all: Option<SigningParamsConfig>,
-    pub(crate) subgraphs: HashMap<String, SigningParamsConfig>,
+    pub(crate) all: Option<AuthParams>,
+    pub(crate) subgraphs: HashMap<String, AuthParams>,
+}
+
+#[derive(Clone)]
+pub(crate) enum AuthParams {
+    /// Requests are signed by the HTTP client service
+    AWSSigV4(SigningParamsConfig),
+    /// A bearer token is added to the request's `authorization` header
+    Token(TokenProvider),
 }
 
 #[derive(Clone)]
@@ -318,34 +341,45 @@ fn increment_failure_counter(subgraph_name: &str) {
     );
 }
 
-pub(super) async fn make_signing_params(
+pub(super) async fn make_auth_params(
     config: &AuthConfig,
     subgraph_name: &str,
-) -> Result<SigningParamsConfig, BoxError> {
+) -> Result<AuthParams, BoxError> {
     match config {
-        AuthConfig::AWSSigV4(config) => {
-            let credentials_provider = config.get_credentials_provider().await;
-            if let Err(e) = credentials_provider.provide_credentials().await {
-                let error_subgraph_name = if subgraph_name == "all" {
-                    "all subgraphs".to_string()
-                } else {
-                    format!("{} subgraph", subgraph_name)
-                };
-                return Err(format!(
-                    "auth: {}: couldn't get credentials from provider: {}",
-                    error_subgraph_name, e,
-                )
-                .into());
-            }
-
-            Ok(SigningParamsConfig {
-                region: config.region(),
-                service_name: config.service_name(),
-                credentials_provider,
-                subgraph_name: subgraph_name.to_string(),
-            })
+        AuthConfig::AWSSigV4(config) => make_signing_params(config, subgraph_name)
+            .await
+            .map(AuthParams::AWSSigV4),
+        AuthConfig::OAuth2ClientCredentials(config) => {
+            TokenProvider::client_credentials(config).map(AuthParams::Token)
         }
+        AuthConfig::TokenFile(config) => TokenProvider::file(config).await.map(AuthParams::Token),
+        AuthConfig::ForwardJwt(config) => TokenProvider::forward_jwt(config).map(AuthParams::Token),
+    }
+    .map_err(|e| {
+        let error_subgraph_name = if subgraph_name == "all" {
+            "all subgraphs".to_string()
+        } else {
+            format!("{} subgraph", subgraph_name)
+        };
+        format!("auth: {}: {}", error_subgraph_name, e).into()
+    })
+}
+
+async fn make_signing_params(
+    config: &AWSSigV4Config,
+    subgraph_name: &str,
+) -> Result<SigningParamsConfig, BoxError> {
+    let credentials_provider = config.get_credentials_provider().await;
+    if let Err(e) = credentials_provider.provide_credentials().await {
+        return Err(format!("couldn't get credentials from provider: {}", e).into());
     }
+
+    Ok(SigningParamsConfig {
+        region: config.region(),
+        service_name: config.service_name(),
+        credentials_provider,
+        subgraph_name: subgraph_name.to_string(),
+    })
 }
 
 /// There are three possible cases
@@ -369,23 +403,52 @@ impl SubgraphAuth {
         name: &str,
         service: crate::services::subgraph::BoxService,
     ) -> crate::services::subgraph::BoxService {
-        if let Some(signing_params) = self.params_for_service(name) {
-            ServiceBuilder::new()
+        match self.params_for_service(name) {
+            Some(AuthParams::AWSSigV4(signing_params)) => ServiceBuilder::new()
                 .map_request(move |req: SubgraphRequest| {
                     let signing_params = signing_params.clone();
                     req.context.extensions().lock().insert(signing_params);
                     req
                 })
                 .service(service)
-                .boxed()
-        } else {
-            service
+                .boxed(),
+            Some(AuthParams::Token(token_provider)) => {
+                let subgraph_name = name.to_string();
+                ServiceBuilder::new()
+                    .oneshot_checkpoint_async(move |mut req: SubgraphRequest| {
+                        let token_provider = token_provider.clone();
+                        let subgraph_name = subgraph_name.clone();
+                        async move {
+                            let token = token_provider
+                                .token(req.supergraph_request.headers())
+                                .await
+                                .map_err(|err| {
+                                    let error = format!(
+                                        "could not get a token for subgraph {subgraph_name}: {err}"
+                                    );
+                                    tracing::error!("{}", error);
+                                    error
+                                })?;
+                            if let Some(token) = token {
+                                req.subgraph_request.headers_mut().insert(
+                                    AUTHORIZATION,
+                                    HeaderValue::from_str(&format!("Bearer {token}"))?,
+                                );
+                            }
+                            Ok::<_, BoxError>(ControlFlow::Continue(req))
+                        }
+                        .boxed()
+                    })
+                    .service(service)
+                    .boxed()
+            }
+            None => service,
         }
     }
 }
 
 impl SubgraphAuth {
-    fn params_for_service(&self, service_name: &str) -> Option<SigningParamsConfig> {
+    fn params_for_service(&self, service_name: &str) -> Option<AuthParams> {
         self.signing_params
             .subgraphs
             .get(service_name)
@@ -414,13 +477,13 @@ mod test {
 
     async fn test_signing_settings(service_name: &str) -> SigningSettings {
         let params: SigningParamsConfig = make_signing_params(
-            &AuthConfig::AWSSigV4(AWSSigV4Config::Hardcoded(AWSSigV4HardcodedConfig {
+            &AWSSigV4Config::Hardcoded(AWSSigV4HardcodedConfig {
                 access_key_id: "id".to_string(),
                 secret_access_key: "secret".to_string(),
                 region: "us-east-1".to_string(),
                 service_name: service_name.to_string(),
                 assume_role: None,
-            })),
+            }),
             "all",
         )
         .await
@@ -528,7 +591,7 @@ mod test {
 
         let mut service = SubgraphAuth {
             signing_params: SigningParams {
-                all: make_signing_params(
+                all: make_auth_params(
                     &AuthConfig::AWSSigV4(AWSSigV4Config::Hardcoded(AWSSigV4HardcodedConfig {
                         access_key_id: "id".to_string(),
                         secret_access_key: "secret".to_string(),
@@ -580,7 +643,7 @@ mod test {
 
         let mut service = SubgraphAuth {
             signing_params: SigningParams {
-                all: make_signing_params(
+                all: make_auth_params(
                     &AuthConfig::AWSSigV4(AWSSigV4Config::Hardcoded(AWSSigV4HardcodedConfig {
                         access_key_id: "id".to_string(),
                         secret_access_key: "secret".to_string(),
@@ -601,6 +664,216 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_oauth2_client_credentials_config() {
+        serde_yaml::from_str::<Config>(
+            r#"
+        all:
+          oauth2_client_credentials:
+            token_endpoint: "https://idp.example.com/oauth2/token"
+            client_id: "router"
+            client_secret: "secret"
+            scopes: ["read", "write"]
+            refresh_margin: 1m
+        subgraphs:
+          products:
+            token_file:
+              path: "/var/run/secrets/products-token"
+          reviews:
+            forward_jwt:
+              exchange:
+                token_endpoint: "https://idp.example.com/oauth2/token"
+                client_id: "router"
+                client_secret: "secret"
+                audience: "reviews"
+        "#,
+        )
+        .unwrap();
+    }
+
+    fn get_authorization(request: &SubgraphRequest) -> Option<String> {
+        request
+            .subgraph_request
+            .headers()
+            .get(http::header::AUTHORIZATION)
+            .map(|value| value.to_str().unwrap().to_string())
+    }
+
+    #[tokio::test]
+    async fn test_oauth2_client_credentials_token() -> Result<(), BoxError> {
+        use wiremock::matchers::body_string_contains;
+        use wiremock::matchers::method;
+        use wiremock::Mock;
+        use wiremock::MockServer;
+        use wiremock::ResponseTemplate;
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_string_contains("grant_type=client_credentials"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "access_token": "subgraph-token",
+                "token_type": "Bearer",
+                "expires_in": 3600,
+            })))
+            // the token is cached between requests
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config: AuthConfig = serde_json::from_value(serde_json::json!({
+            "oauth2_client_credentials": {
+                "token_endpoint": format!("{}/token", server.uri()),
+                "client_id": "router",
+                "client_secret": "secret",
+            }
+        }))?;
+
+        let mut mock = MockSubgraphService::new();
+        mock.expect_call()
+            .times(2)
+            .withf(|request| {
+                assert_eq!(
+                    get_authorization(request).as_deref(),
+                    Some("Bearer subgraph-token")
+                );
+                true
+            })
+            .returning(example_response);
+
+        let mut service = SubgraphAuth {
+            signing_params: SigningParams {
+                all: Some(make_auth_params(&config, "all").await?),
+                subgraphs: Default::default(),
+            },
+        }
+        .subgraph_service("test_subgraph", mock.boxed());
+
+        service.ready().await?.call(example_request()).await?;
+        service.ready().await?.call(example_request()).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_oauth2_client_credentials_token_without_expiration() -> Result<(), BoxError> {
+        use wiremock::matchers::method;
+        use wiremock::Mock;
+        use wiremock::MockServer;
+        use wiremock::ResponseTemplate;
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "access_token": "subgraph-token",
+                "token_type": "Bearer",
+            })))
+            // the token is refreshed once its default lifetime is over
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let config: AuthConfig = serde_json::from_value(serde_json::json!({
+            "oauth2_client_credentials": {
+                "token_endpoint": format!("{}/token", server.uri()),
+                "client_id": "router",
+                "client_secret": "secret",
+                "refresh_margin": "0s",
+                "default_token_lifetime": "100ms",
+            }
+        }))?;
+
+        let mut mock = MockSubgraphService::new();
+        mock.expect_call().times(3).returning(example_response);
+
+        let mut service = SubgraphAuth {
+            signing_params: SigningParams {
+                all: Some(make_auth_params(&config, "all").await?),
+                subgraphs: Default::default(),
+            },
+        }
+        .subgraph_service("test_subgraph", mock.boxed());
+
+        service.ready().await?.call(example_request()).await?;
+        service.ready().await?.call(example_request()).await?;
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        service.ready().await?.call(example_request()).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_token_file() -> Result<(), BoxError> {
+        let path = std::env::temp_dir().join(format!("subgraph-token-{}", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "file-token\n")?;
+
+        let config: AuthConfig = serde_json::from_value(serde_json::json!({
+            "token_file": { "path": path }
+        }))?;
+
+        let mut mock = MockSubgraphService::new();
+        mock.expect_call()
+            .times(1)
+            .withf(|request| {
+                assert_eq!(
+                    get_authorization(request).as_deref(),
+                    Some("Bearer file-token")
+                );
+                true
+            })
+            .returning(example_response);
+
+        let mut service = SubgraphAuth {
+            signing_params: SigningParams {
+                all: None,
+                subgraphs: [(
+                    "products".to_string(),
+                    make_auth_params(&config, "products").await?,
+                )]
+                .into_iter()
+                .collect(),
+            },
+        }
+        .subgraph_service("products", mock.boxed());
+
+        service.ready().await?.call(example_request()).await?;
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_forward_jwt() -> Result<(), BoxError> {
+        let config: AuthConfig = serde_json::from_value(serde_json::json!({ "forward_jwt": {} }))?;
+
+        let mut mock = MockSubgraphService::new();
+        mock.expect_call()
+            .times(1)
+            .withf(|request| {
+                assert_eq!(
+                    get_authorization(request).as_deref(),
+                    Some("Bearer client-jwt")
+                );
+                true
+            })
+            .returning(example_response);
+
+        let mut service = SubgraphAuth {
+            signing_params: SigningParams {
+                all: Some(make_auth_params(&config, "all").await?),
+                subgraphs: Default::default(),
+            },
+        }
+        .subgraph_service("test_subgraph", mock.boxed());
+
+        let mut request = example_request();
+        request.supergraph_request = Arc::new(
+            http::Request::builder()
+                .header(http::header::AUTHORIZATION, "Bearer client-jwt")
+                .body(Request::builder().query("query").build())
+                .expect("expecting valid request"),
+        );
+
+        service.ready().await?.call(request).await?;
+        Ok(())
+    }
+
     fn example_response(_: SubgraphRequest) -> Result<SubgraphResponse, BoxError> {
         Ok(SubgraphResponse::new_from_response(
             http::Response::default(),
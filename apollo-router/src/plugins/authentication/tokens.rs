@@ -0,0 +1,401 @@
+//! Bearer tokens sent to subgraphs
+//!
+//! Tokens can be obtained with the OAuth2 client credentials grant, read from a file that is
+//! reloaded when it changes, or derived from the client's JWT with an OAuth2 token exchange
+//! ([RFC 8693](https://datatracker.ietf.org/doc/html/rfc8693)).
+
+use std::num::NonZeroUsize;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+use arc_swap::ArcSwap;
+use futures::StreamExt;
+use http::header::ACCEPT;
+use http::HeaderMap;
+use lru::LruCache;
+use mime::APPLICATION_JSON;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use sha2::Digest;
+use sha2::Sha256;
+use tokio::sync::oneshot;
+use tokio::sync::Mutex;
+use tower::BoxError;
+use url::Url;
+
+use super::default_header_name;
+use super::default_header_value_prefix;
+use super::extract_jwt;
+use super::Source;
+use super::CLIENT;
+use super::DEFAULT_AUTHENTICATION_NETWORK_TIMEOUT;
+
+const TOKEN_EXCHANGE_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:token-exchange";
+const JWT_TOKEN_TYPE: &str = "urn:ietf:params:oauth:token-type:jwt";
+const DEFAULT_REFRESH_MARGIN: Duration = Duration::from_secs(30);
+const DEFAULT_TOKEN_LIFETIME: Duration = Duration::from_secs(300);
+const EXCHANGED_TOKENS_CACHE_CAPACITY: NonZeroUsize = match NonZeroUsize::new(1000) {
+    Some(capacity) => capacity,
+    None => unreachable!(),
+};
+
+/// Configure OAuth2 client credentials authentication
+#[derive(Clone, JsonSchema, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct OAuth2ClientCredentialsConfig {
+    /// URL of the token endpoint
+    token_endpoint: String,
+    /// Client identifier
+    client_id: String,
+    /// Client secret
+    client_secret: String,
+    /// Scopes requested for the token
+    #[serde(default)]
+    scopes: Vec<String>,
+    /// Audience requested for the token
+    audience: Option<String>,
+    /// The token is refreshed when it expires within this duration, in human-readable format; defaults to 30s
+    #[serde(
+        deserialize_with = "humantime_serde::deserialize",
+        default = "default_refresh_margin"
+    )]
+    #[schemars(with = "String", default = "default_refresh_margin")]
+    refresh_margin: Duration,
+    /// Lifetime of the tokens whose response has no `expires_in`, in human-readable format; defaults to 5m
+    #[serde(
+        deserialize_with = "humantime_serde::deserialize",
+        default = "default_token_lifetime"
+    )]
+    #[schemars(with = "String", default = "default_token_lifetime")]
+    default_token_lifetime: Duration,
+}
+
+/// Configure a static bearer token read from a file
+#[derive(Clone, JsonSchema, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct TokenFileConfig {
+    /// Path of the file containing the token. The file is reloaded when it changes
+    path: PathBuf,
+}
+
+/// Configure forwarding of the client's JWT
+#[derive(Clone, JsonSchema, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ForwardJwtConfig {
+    /// HTTP header of the client request containing the JWT
+    #[serde(default = "default_header_name")]
+    header_name: String,
+    /// Header value prefix
+    #[serde(default = "default_header_value_prefix")]
+    header_value_prefix: String,
+    /// Exchange the JWT for a token intended for the subgraph, instead of forwarding it as is
+    exchange: Option<TokenExchangeConfig>,
+}
+
+/// Configure an OAuth2 token exchange
+#[derive(Clone, JsonSchema, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct TokenExchangeConfig {
+    /// URL of the token endpoint
+    token_endpoint: String,
+    /// Client identifier
+    client_id: String,
+    /// Client secret
+    client_secret: String,
+    /// Audience requested for the exchanged token
+    audience: String,
+    /// Scopes requested for the exchanged token
+    #[serde(default)]
+    scopes: Vec<String>,
+    /// Lifetime of the tokens whose response has no `expires_in`, in human-readable format; defaults to 5m
+    #[serde(
+        deserialize_with = "humantime_serde::deserialize",
+        default = "default_token_lifetime"
+    )]
+    #[schemars(with = "String", default = "default_token_lifetime")]
+    default_token_lifetime: Duration,
+}
+
+fn default_refresh_margin() -> Duration {
+    DEFAULT_REFRESH_MARGIN
+}
+
+fn default_token_lifetime() -> Duration {
+    DEFAULT_TOKEN_LIFETIME
+}
+
+#[derive(Clone)]
+struct CachedToken {
+    token: String,
+    expires_at: Instant,
+}
+
+impl CachedToken {
+    fn is_valid(&self, margin: Duration) -> bool {
+        Instant::now() + margin < self.expires_at
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: Option<u64>,
+}
+
+/// Provides the bearer token for a subgraph request
+#[derive(Clone)]
+pub(crate) enum TokenProvider {
+    ClientCredentials(Arc<ClientCredentials>),
+    File(Arc<TokenFile>),
+    ForwardJwt(Arc<ForwardJwt>),
+}
+
+impl TokenProvider {
+    pub(crate) fn client_credentials(
+        config: &OAuth2ClientCredentialsConfig,
+    ) -> Result<Self, BoxError> {
+        Ok(Self::ClientCredentials(Arc::new(ClientCredentials {
+            token_endpoint: Url::from_str(&config.token_endpoint)?,
+            config: config.clone(),
+            token: Mutex::new(None),
+        })))
+    }
+
+    pub(crate) async fn file(config: &TokenFileConfig) -> Result<Self, BoxError> {
+        let token = read_token_file(&config.path).await?;
+        let token = Arc::new(ArcSwap::from_pointee(token));
+        let (drop_signal, drop_receiver) = oneshot::channel::<()>();
+
+        tokio::task::spawn(watch_token_file(
+            config.path.clone(),
+            token.clone(),
+            drop_receiver,
+        ));
+
+        Ok(Self::File(Arc::new(TokenFile {
+            token,
+            _drop_signal: drop_signal,
+        })))
+    }
+
+    pub(crate) fn forward_jwt(config: &ForwardJwtConfig) -> Result<Self, BoxError> {
+        let exchange = match &config.exchange {
+            Some(exchange) => Some(TokenExchange {
+                token_endpoint: Url::from_str(&exchange.token_endpoint)?,
+                config: exchange.clone(),
+                cache: Mutex::new(LruCache::new(EXCHANGED_TOKENS_CACHE_CAPACITY)),
+            }),
+            None => None,
+        };
+
+        Ok(Self::ForwardJwt(Arc::new(ForwardJwt {
+            source: Source::Header {
+                name: config.header_name.clone(),
+                value_prefix: config.header_value_prefix.clone(),
+            },
+            exchange,
+        })))
+    }
+
+    /// Returns the token to send to the subgraph, if any
+    ///
+    /// `client_headers` are the headers of the client request
+    pub(crate) async fn token(
+        &self,
+        client_headers: &HeaderMap,
+    ) -> Result<Option<String>, BoxError> {
+        match self {
+            Self::ClientCredentials(client_credentials) => {
+                client_credentials.token().await.map(Some)
+            }
+            Self::File(file) => Ok(Some(file.token.load().to_string())),
+            Self::ForwardJwt(forward) => forward.token(client_headers).await,
+        }
+    }
+}
+
+pub(crate) struct ClientCredentials {
+    config: OAuth2ClientCredentialsConfig,
+    token_endpoint: Url,
+    token: Mutex<Option<CachedToken>>,
+}
+
+impl ClientCredentials {
+    async fn token(&self) -> Result<String, BoxError> {
+        // holding the lock while fetching makes concurrent requests wait for a single refresh
+        let mut cached = self.token.lock().await;
+        if let Some(token) = cached.as_ref() {
+            if token.is_valid(self.config.refresh_margin) {
+                return Ok(token.token.clone());
+            }
+        }
+
+        let scope = self.config.scopes.join(" ");
+        let mut params = vec![("grant_type", "client_credentials")];
+        if !scope.is_empty() {
+            params.push(("scope", scope.as_str()));
+        }
+        if let Some(audience) = &self.config.audience {
+            params.push(("audience", audience.as_str()));
+        }
+
+        let token = request_token(
+            &self.token_endpoint,
+            &self.config.client_id,
+            &self.config.client_secret,
+            &params,
+            self.config.default_token_lifetime,
+        )
+        .await?;
+        *cached = Some(token.clone());
+
+        Ok(token.token)
+    }
+}
+
+pub(crate) struct TokenFile {
+    token: Arc<ArcSwap<String>>,
+    _drop_signal: oneshot::Sender<()>,
+}
+
+async fn read_token_file(path: &Path) -> Result<String, BoxError> {
+    let token = tokio::fs::read_to_string(path)
+        .await
+        .map_err(|e| format!("could not read token file {}: {e}", path.display()))?;
+    let token = token.trim();
+    if token.is_empty() {
+        return Err(format!("token file {} is empty", path.display()).into());
+    }
+
+    Ok(token.to_string())
+}
+
+async fn watch_token_file(
+    path: PathBuf,
+    token: Arc<ArcSwap<String>>,
+    mut drop_receiver: oneshot::Receiver<()>,
+) {
+    let mut watch = crate::files::watch(&path);
+
+    loop {
+        tokio::select! {
+            // the _drop_signal was dropped, we must shut down the task
+            _ = &mut drop_receiver => return,
+            event = watch.next() => match event {
+                Some(()) => match read_token_file(&path).await {
+                    Ok(new_token) => token.store(Arc::new(new_token)),
+                    Err(e) => tracing::error!(%e, "could not reload subgraph authentication token"),
+                },
+                None => return,
+            }
+        }
+    }
+}
+
+pub(crate) struct ForwardJwt {
+    source: Source,
+    exchange: Option<TokenExchange>,
+}
+
+impl ForwardJwt {
+    async fn token(&self, client_headers: &HeaderMap) -> Result<Option<String>, BoxError> {
+        let jwt = match extract_jwt(&self.source, client_headers) {
+            Some(Ok(jwt)) => jwt,
+            // the JWT authentication plugin already rejected malformed headers, if it is enabled
+            Some(Err(_)) | None => return Ok(None),
+        };
+
+        match &self.exchange {
+            Some(exchange) => exchange.exchange(jwt).await.map(Some),
+            None => Ok(Some(jwt.to_string())),
+        }
+    }
+}
+
+struct TokenExchange {
+    config: TokenExchangeConfig,
+    token_endpoint: Url,
+    cache: Mutex<LruCache<String, CachedToken>>,
+}
+
+impl TokenExchange {
+    async fn exchange(&self, jwt: &str) -> Result<String, BoxError> {
+        // tokens are credentials, so we do not keep them around in memory
+        let key = hex::encode(Sha256::digest(jwt.as_bytes()));
+
+        if let Some(token) = self.cache.lock().await.get(&key) {
+            if token.is_valid(Duration::ZERO) {
+                return Ok(token.token.clone());
+            }
+        }
+
+        let scope = self.config.scopes.join(" ");
+        let mut params = vec![
+            ("grant_type", TOKEN_EXCHANGE_GRANT_TYPE),
+            ("subject_token", jwt),
+            ("subject_token_type", JWT_TOKEN_TYPE),
+            ("audience", self.config.audience.as_str()),
+        ];
+        if !scope.is_empty() {
+            params.push(("scope", scope.as_str()));
+        }
+
+        let token = request_token(
+            &self.token_endpoint,
+            &self.config.client_id,
+            &self.config.client_secret,
+            &params,
+            self.config.default_token_lifetime,
+        )
+        .await?;
+        self.cache.lock().await.put(key, token.clone());
+
+        Ok(token.token)
+    }
+}
+
+async fn request_token(
+    token_endpoint: &Url,
+    client_id: &str,
+    client_secret: &str,
+    params: &[(&str, &str)],
+    default_lifetime: Duration,
+) -> Result<CachedToken, BoxError> {
+    let client = CLIENT.as_ref().map_err(|e| e.to_string())?.clone();
+
+    let response = client
+        .post(token_endpoint.clone())
+        .basic_auth(client_id, Some(client_secret))
+        .header(ACCEPT, APPLICATION_JSON.essence_str())
+        .form(params)
+        .timeout(DEFAULT_AUTHENTICATION_NETWORK_TIMEOUT)
+        .send()
+        .await
+        .map_err(|e| format!("could not request a token from {token_endpoint}: {e}"))?;
+
+    if !response.status().is_success() {
+        return Err(format!(
+            "could not request a token from {token_endpoint}: status {}",
+            response.status()
+        )
+        .into());
+    }
+
+    let response: TokenResponse = response
+        .json()
+        .await
+        .map_err(|e| format!("invalid token response from {token_endpoint}: {e}"))?;
+
+    Ok(CachedToken {
+        token: response.access_token,
+        // without an expiration, the token is still refreshed regularly in case it was revoked
+        expires_at: Instant::now()
+            + response
+                .expires_in
+                .map_or(default_lifetime, Duration::from_secs),
+    })
+}
@@ -0,0 +1,493 @@
+//! Authorization plugin
+//!
+//! Argument-aware authorization: the resolved arguments of the fields restricted by `@policy`
+//! are stored in the context, so that policies can allow or deny each of those fields depending
+//! on its arguments. Denied fields are then removed from the query, like fields failing the
+//! directives. `@authenticated` and `@requiresScopes` do not depend on arguments.
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use apollo_compiler::ast;
+use apollo_compiler::executable;
+use apollo_compiler::schema;
+use apollo_compiler::ExecutableDocument;
+use apollo_compiler::Node;
+use serde::Deserialize;
+use serde::Serialize;
+use tower::BoxError;
+
+use super::policy::policies_sets_argument;
+use super::policy::POLICY_DIRECTIVE_NAME;
+use super::policy::POLICY_SPEC_BASE_URL;
+use super::policy::POLICY_SPEC_VERSION_RANGE;
+use crate::json_ext::Object;
+use crate::json_ext::Path;
+use crate::json_ext::PathElement;
+use crate::json_ext::Value;
+use crate::spec::query::transform;
+use crate::spec::Schema;
+
+/// Resolved arguments of a restricted field, and the authorization decision for that field
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct FieldArguments {
+    /// Argument values, after replacing variables and applying default values
+    pub(crate) arguments: Object,
+    /// Policies sets required by the `@policy` directive of the field
+    #[serde(default)]
+    pub(crate) policies: Vec<Vec<String>>,
+    /// Policies sets required by the `@policy` directive of the field's type
+    #[serde(default)]
+    pub(crate) type_policies: Vec<Vec<String>>,
+    /// The field is removed from the query if this is `false`. If it is not set, the field is
+    /// only checked against the authorization directives
+    #[serde(default)]
+    pub(crate) authorized: Option<bool>,
+}
+
+/// Identifies a field selection by its parent type, name and arguments, as written in the query.
+///
+/// The same selection in a fragment spread at multiple places gets the same key, which is
+/// consistent with the arguments being identical at every place.
+pub(crate) fn field_key(
+    parent_type: &str,
+    field_name: &str,
+    arguments: &[Node<ast::Argument>],
+) -> String {
+    let arguments = arguments
+        .iter()
+        .map(|argument| argument.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("{parent_type}.{field_name}({arguments})")
+}
+
+/// Finds the fields restricted by `@policy`, on the field or on its type, and resolves their
+/// arguments with the request's variables
+pub(crate) fn collect_field_arguments(
+    schema: &schema::Schema,
+    document: &ExecutableDocument,
+    operation_name: Option<&str>,
+    variables: &Object,
+) -> HashMap<String, FieldArguments> {
+    let Ok(operation) = document.get_operation(operation_name) else {
+        return HashMap::new();
+    };
+
+    let Some(policy_directive_name) = Schema::directive_name(
+        schema,
+        POLICY_SPEC_BASE_URL,
+        POLICY_SPEC_VERSION_RANGE,
+        POLICY_DIRECTIVE_NAME,
+    ) else {
+        return HashMap::new();
+    };
+
+    let mut collector = FieldArgumentsCollector {
+        schema,
+        document,
+        variables,
+        variable_defaults: operation
+            .variables
+            .iter()
+            .filter_map(|variable| Some((&variable.name, variable.default_value.as_ref()?)))
+            .collect(),
+        policy_directive_name,
+        visited_fragments: HashSet::new(),
+        fields: HashMap::new(),
+    };
+    collector.selection_set(&operation.selection_set);
+
+    collector.fields
+}
+
+struct FieldArgumentsCollector<'a> {
+    schema: &'a schema::Schema,
+    document: &'a ExecutableDocument,
+    variables: &'a Object,
+    variable_defaults: HashMap<&'a ast::Name, &'a Node<ast::Value>>,
+    policy_directive_name: String,
+    visited_fragments: HashSet<&'a ast::Name>,
+    fields: HashMap<String, FieldArguments>,
+}
+
+impl<'a> FieldArgumentsCollector<'a> {
+    fn selection_set(&mut self, selection_set: &'a executable::SelectionSet) {
+        for selection in &selection_set.selections {
+            match selection {
+                executable::Selection::Field(field) => {
+                    self.field(selection_set.ty.as_str(), field);
+                    self.selection_set(&field.selection_set);
+                }
+                executable::Selection::FragmentSpread(spread) => {
+                    // the arguments of a fragment are the same wherever it is spread
+                    if self.visited_fragments.insert(&spread.fragment_name) {
+                        if let Some(fragment) = self.document.fragments.get(&spread.fragment_name) {
+                            self.selection_set(&fragment.selection_set);
+                        }
+                    }
+                }
+                executable::Selection::InlineFragment(fragment) => {
+                    self.selection_set(&fragment.selection_set)
+                }
+            }
+        }
+    }
+
+    fn field(&mut self, parent_type: &str, field: &executable::Field) {
+        // directives are looked up in the supergraph schema, the API schema does not keep them
+        let Ok(definition) = self.schema.type_field(parent_type, &field.name) else {
+            return;
+        };
+
+        let field_directive = definition.directives.get(&self.policy_directive_name);
+        let type_directive = self
+            .schema
+            .types
+            .get(definition.ty.inner_named_type())
+            .and_then(|ty| ty.directives().get(&self.policy_directive_name));
+        if field_directive.is_none() && type_directive.is_none() {
+            return;
+        }
+
+        let policies = field_directive
+            .map(|directive| sorted_policies_sets(directive))
+            .unwrap_or_default();
+        let type_policies = type_directive
+            .map(|directive| sorted_policies_sets(directive))
+            .unwrap_or_default();
+
+        let mut arguments = Object::new();
+        for argument_definition in &definition.arguments {
+            let value = field
+                .arguments
+                .iter()
+                .find(|argument| argument.name == argument_definition.name)
+                .and_then(|argument| self.resolve(&argument.value))
+                .or_else(|| {
+                    argument_definition
+                        .default_value
+                        .as_ref()
+                        .and_then(|value| self.resolve(value))
+                });
+            if let Some(value) = value {
+                arguments.insert(argument_definition.name.as_str(), value);
+            }
+        }
+
+        self.fields.insert(
+            field_key(parent_type, &field.name, &field.arguments),
+            FieldArguments {
+                arguments,
+                policies,
+                type_policies,
+                authorized: None,
+            },
+        );
+    }
+
+    /// Converts an argument value to JSON, replacing variables with their value in the request
+    /// or their default value in the operation
+    fn resolve(&self, value: &ast::Value) -> Option<Value> {
+        match value {
+            ast::Value::Variable(name) => {
+                self.variables.get(name.as_str()).cloned().or_else(|| {
+                    self.variable_defaults
+                        .get(name)
+                        .and_then(|value| self.resolve(value))
+                })
+            }
+            ast::Value::Int(value) => Some(value.as_str().parse::<i64>().ok()?.into()),
+            ast::Value::Float(value) => Some(value.try_to_f64().ok()?.into()),
+            ast::Value::Null => Some(Value::Null),
+            ast::Value::String(value) => Some(value.as_str().into()),
+            ast::Value::Boolean(value) => Some((*value).into()),
+            ast::Value::Enum(value) => Some(value.as_str().into()),
+            ast::Value::List(values) => Some(Value::Array(
+                values
+                    .iter()
+                    .map(|value| self.resolve(value).unwrap_or(Value::Null))
+                    .collect(),
+            )),
+            ast::Value::Object(fields) => Some(Value::Object(
+                fields
+                    .iter()
+                    .filter_map(|(name, value)| Some((name.as_str().into(), self.resolve(value)?)))
+                    .collect(),
+            )),
+        }
+    }
+}
+
+/// Policies sets of a `@policy` directive, sorted to get a stable representation
+fn sorted_policies_sets(directive: &ast::Directive) -> Vec<Vec<String>> {
+    policies_sets_argument(directive)
+        .map(|set| {
+            let mut set = set.into_iter().collect::<Vec<_>>();
+            set.sort();
+            set
+        })
+        .collect()
+}
+
+/// Removes the fields denied by argument-aware authorization
+pub(crate) struct ArgumentsFilteringVisitor<'a> {
+    schema: &'a schema::Schema,
+    fragments: HashMap<&'a ast::Name, &'a ast::FragmentDefinition>,
+    dry_run: bool,
+    unauthorized_fields: HashSet<String>,
+    pub(crate) query_has_unauthorized_fields: bool,
+    pub(crate) unauthorized_paths: Vec<Path>,
+    // store the error paths from fragments so we can  add them at
+    // the point of application
+    fragments_unauthorized_paths: HashMap<&'a ast::Name, Vec<Path>>,
+    current_path: Path,
+}
+
+impl<'a> ArgumentsFilteringVisitor<'a> {
+    pub(crate) fn new(
+        schema: &'a schema::Schema,
+        executable: &'a ast::Document,
+        unauthorized_fields: HashSet<String>,
+        dry_run: bool,
+    ) -> Option<Self> {
+        if unauthorized_fields.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            schema,
+            fragments: transform::collect_fragments(executable),
+            dry_run,
+            unauthorized_fields,
+            query_has_unauthorized_fields: false,
+            unauthorized_paths: vec![],
+            fragments_unauthorized_paths: HashMap::new(),
+            current_path: Path::default(),
+        })
+    }
+}
+
+impl<'a> transform::Visitor for ArgumentsFilteringVisitor<'a> {
+    fn field(
+        &mut self,
+        parent_type: &str,
+        field_def: &ast::FieldDefinition,
+        node: &ast::Field,
+    ) -> Result<Option<ast::Field>, BoxError> {
+        let is_field_list = field_def.ty.is_list();
+        let is_authorized = !self.unauthorized_fields.contains(&field_key(
+            parent_type,
+            &node.name,
+            &node.arguments,
+        ));
+
+        self.current_path
+            .push(PathElement::Key(node.name.as_str().into()));
+        if is_field_list {
+            self.current_path.push(PathElement::Flatten);
+        }
+
+        let res = if is_authorized {
+            transform::field(self, field_def, node)
+        } else {
+            self.unauthorized_paths.push(self.current_path.clone());
+            self.query_has_unauthorized_fields = true;
+
+            if self.dry_run {
+                transform::field(self, field_def, node)
+            } else {
+                Ok(None)
+            }
+        };
+
+        if is_field_list {
+            self.current_path.pop();
+        }
+        self.current_path.pop();
+
+        res
+    }
+
+    fn fragment_definition(
+        &mut self,
+        node: &ast::FragmentDefinition,
+    ) -> Result<Option<ast::FragmentDefinition>, BoxError> {
+        let current_unauthorized_paths_index = self.unauthorized_paths.len();
+
+        let res = transform::fragment_definition(self, node);
+
+        if self.unauthorized_paths.len() > current_unauthorized_paths_index {
+            if let Some((name, _)) = self.fragments.get_key_value(&node.name) {
+                self.fragments_unauthorized_paths.insert(
+                    name,
+                    self.unauthorized_paths
+                        .split_off(current_unauthorized_paths_index),
+                );
+            }
+        }
+
+        if let Ok(None) = res {
+            self.fragments.remove(&node.name);
+        }
+
+        res
+    }
+
+    fn fragment_spread(
+        &mut self,
+        node: &ast::FragmentSpread,
+    ) -> Result<Option<ast::FragmentSpread>, BoxError> {
+        // record the fragment errors at the point of application
+        if let Some(paths) = self.fragments_unauthorized_paths.get(&node.fragment_name) {
+            for path in paths {
+                let path = self.current_path.join(path);
+                self.unauthorized_paths.push(path);
+            }
+        }
+
+        if !self.fragments.contains_key(&node.fragment_name) {
+            return Ok(None);
+        }
+
+        transform::fragment_spread(self, node)
+    }
+
+    fn inline_fragment(
+        &mut self,
+        parent_type: &str,
+        node: &ast::InlineFragment,
+    ) -> Result<Option<ast::InlineFragment>, BoxError> {
+        let name = node
+            .type_condition
+            .as_ref()
+            .map(|name| name.as_str().to_string())
+            .unwrap_or_default();
+        self.current_path.push(PathElement::Fragment(name));
+        let res = transform::inline_fragment(self, parent_type, node);
+        self.current_path.pop();
+
+        res
+    }
+
+    fn schema(&self) -> &apollo_compiler::Schema {
+        self.schema
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use apollo_compiler::ast;
+    use apollo_compiler::Schema;
+    use serde_json_bytes::json;
+
+    use super::collect_field_arguments;
+    use super::ArgumentsFilteringVisitor;
+    use crate::json_ext::Path;
+    use crate::spec::query::transform;
+
+    static SCHEMA: &str = r#"
+    schema
+      @link(url: "https://specs.apollo.dev/link/v1.0")
+      @link(url: "https://specs.apollo.dev/join/v0.3", for: EXECUTION)
+      @link(url: "https://specs.apollo.dev/policy/v0.1", for: SECURITY)
+      @link(url: "https://specs.apollo.dev/authenticated/v0.1", for: SECURITY)
+    {
+      query: Query
+    }
+    directive @link(url: String, as: String, for: link__Purpose, import: [link__Import]) repeatable on SCHEMA
+    directive @policy(policies: [[String!]!]!) on OBJECT | FIELD_DEFINITION | INTERFACE | SCALAR | ENUM
+    directive @authenticated on OBJECT | FIELD_DEFINITION | INTERFACE | SCALAR | ENUM
+    scalar link__Import
+      enum link__Purpose {
+    """
+    `SECURITY` features provide metadata necessary to securely resolve fields.
+    """
+    SECURITY
+
+    """
+    `EXECUTION` features provide metadata necessary for operation execution.
+    """
+    EXECUTION
+    }
+
+    type Query {
+      user(id: ID!): User @policy(policies: [["self"], ["admin"]])
+      users(first: Int = 10): [User] @policy(policies: [["admin"]])
+      topProducts(first: Int): [String]
+    }
+
+    type User {
+      id: ID!
+      name: String
+      friends(first: Int): [User]
+    }
+    "#;
+
+    #[test]
+    fn collects_restricted_fields() {
+        let schema = Schema::parse_and_validate(SCHEMA, "schema.graphql").unwrap();
+        let query = r#"
+        query($id: ID!, $first: Int = 5) {
+            user(id: $id) { name friends(first: $first) { id } }
+            users { ...UserName }
+            topProducts(first: 3)
+        }
+
+        fragment UserName on User { name }
+        "#;
+        let doc = ast::Document::parse(query, "query.graphql").unwrap();
+        let executable = doc.to_executable_validate(&schema).unwrap();
+        let variables = json!({ "id": "1" });
+
+        let fields =
+            collect_field_arguments(&schema, &executable, None, variables.as_object().unwrap());
+
+        let mut keys = fields.keys().cloned().collect::<Vec<_>>();
+        keys.sort();
+        assert_eq!(keys, vec!["Query.user(id: $id)", "Query.users()"]);
+
+        let user = &fields["Query.user(id: $id)"];
+        assert_eq!(user.arguments, *json!({ "id": "1" }).as_object().unwrap());
+        assert_eq!(
+            user.policies,
+            vec![vec!["self".to_string()], vec!["admin".to_string()]]
+        );
+        assert_eq!(user.authorized, None);
+
+        // default values of the field arguments are applied
+        assert_eq!(
+            fields["Query.users()"].arguments,
+            *json!({ "first": 10 }).as_object().unwrap()
+        );
+    }
+
+    #[test]
+    fn filters_unauthorized_fields() {
+        let schema = Schema::parse_and_validate(SCHEMA, "schema.graphql").unwrap();
+        let query = r#"
+        query($id: ID!) {
+            user(id: $id) { name }
+            topProducts(first: 3)
+        }
+        "#;
+        let doc = ast::Document::parse(query, "query.graphql").unwrap();
+        doc.to_executable_validate(&schema).unwrap();
+
+        let mut visitor = ArgumentsFilteringVisitor::new(
+            &schema,
+            &doc,
+            HashSet::from(["Query.user(id: $id)".to_string()]),
+            false,
+        )
+        .unwrap();
+        let filtered = transform::document(&mut visitor, &doc).unwrap();
+
+        assert_eq!(
+            filtered.to_string(),
+            "query($id: ID!) {\n  topProducts(first: 3)\n}\n"
+        );
+        assert_eq!(visitor.unauthorized_paths, vec![Path::from("user")]);
+    }
+}
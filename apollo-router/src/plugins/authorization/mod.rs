@@ -3,6 +3,7 @@
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::ops::ControlFlow;
+use std::sync::Arc;
 
 use apollo_compiler::ast;
 use apollo_compiler::ast::Document;
@@ -15,6 +16,8 @@ use tower::BoxError;
 use tower::ServiceBuilder;
 use tower::ServiceExt;
 
+use self::arguments::ArgumentsFilteringVisitor;
+use self::arguments::FieldArguments;
 use self::authenticated::AuthenticatedCheckVisitor;
 use self::authenticated::AuthenticatedVisitor;
 use self::authenticated::AUTHENTICATED_SPEC_BASE_URL;
@@ -30,15 +33,23 @@ use self::scopes::REQUIRES_SCOPES_SPEC_VERSION_RANGE;
 use crate::error::QueryPlannerError;
 use crate::error::ServiceBuildError;
 use crate::graphql;
+use crate::json_ext::Object;
 use crate::json_ext::Path;
 use crate::layers::ServiceBuilderExt;
 use crate::plugin::Plugin;
 use crate::plugin::PluginInit;
 use crate::plugins::authentication::APOLLO_AUTHENTICATION_JWT_CLAIMS;
+use crate::plugins::telemetry::config::AttributeValue;
+use crate::plugins::telemetry::config_new::conditions::Condition;
+use crate::plugins::telemetry::config_new::conditions::SelectorOrValue;
+use crate::plugins::telemetry::config_new::selectors::SupergraphSelector;
+use crate::plugins::telemetry::config_new::Selector;
+use crate::plugins::telemetry::config_new::ToOtelValue;
 use crate::query_planner::FilteredQuery;
 use crate::query_planner::QueryKey;
 use crate::register_plugin;
 use crate::services::execution;
+use crate::services::layers::query_analysis::ParsedDocument;
 use crate::services::supergraph;
 use crate::spec::query::transform;
 use crate::spec::query::traverse;
@@ -48,6 +59,7 @@ use crate::spec::SpecError;
 use crate::Configuration;
 use crate::Context;
 
+pub(crate) mod arguments;
 pub(crate) mod authenticated;
 pub(crate) mod policy;
 pub(crate) mod scopes;
@@ -55,12 +67,19 @@ pub(crate) mod scopes;
 const AUTHENTICATED_KEY: &str = "apollo_authorization::authenticated::required";
 const REQUIRED_SCOPES_KEY: &str = "apollo_authorization::scopes::required";
 const REQUIRED_POLICIES_KEY: &str = "apollo_authorization::policies::required";
+const FIELD_ARGUMENTS_KEY: &str = "apollo_authorization::arguments";
 
 #[derive(Clone, Debug, Default, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub(crate) struct CacheKeyMetadata {
     pub(crate) is_authenticated: bool,
     pub(crate) scopes: Vec<String>,
     pub(crate) policies: Vec<String>,
+    /// fields allowed by policies evaluated with their arguments, see [`arguments::field_key`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) authorized_fields: Vec<String>,
+    /// fields denied by argument-aware policies, see [`arguments::field_key`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) unauthorized_fields: Vec<String>,
 }
 
 /// Authorization plugin
@@ -73,6 +92,50 @@ pub(crate) struct Conf {
     /// `@authenticated`, `@requiresScopes` and `@policy` directives
     #[serde(default)]
     directives: Directives,
+    /// Policies decided by the router, by name. Each policy is a condition evaluated against the
+    /// client request. Policies that are not listed here are left to coprocessors or Rhai scripts
+    #[serde(default)]
+    policies: HashMap<String, Condition<PolicySelector>>,
+}
+
+/// Values available to policies defined in the router configuration
+#[derive(Deserialize, JsonSchema, Clone, Debug)]
+#[serde(deny_unknown_fields, untagged)]
+pub(crate) enum PolicySelector {
+    Argument {
+        /// The name of an argument of the field restricted by the policy.
+        argument: String,
+        /// Optional default value.
+        default: Option<AttributeValue>,
+    },
+    Supergraph(SupergraphSelector),
+}
+
+/// The client request, and the arguments of the field when a policy is evaluated for a field
+pub(crate) struct PolicyRequest {
+    request: supergraph::Request,
+    arguments: Option<Object>,
+}
+
+impl Selector for PolicySelector {
+    type Request = PolicyRequest;
+    type Response = ();
+
+    fn on_request(&self, request: &Self::Request) -> Option<opentelemetry::Value> {
+        match self {
+            PolicySelector::Argument { argument, default } => request
+                .arguments
+                .as_ref()
+                .and_then(|arguments| arguments.get(argument.as_str()))
+                .and_then(|value| value.maybe_to_otel_value())
+                .or_else(|| default.maybe_to_otel_value()),
+            PolicySelector::Supergraph(selector) => selector.on_request(&request.request),
+        }
+    }
+
+    fn on_response(&self, _response: &Self::Response) -> Option<opentelemetry::Value> {
+        None
+    }
 }
 
 #[derive(Clone, Debug, serde_derive_default::Default, Deserialize, JsonSchema)]
@@ -133,6 +196,7 @@ fn default_enable_directives() -> bool {
 
 pub(crate) struct AuthorizationPlugin {
     require_authentication: bool,
+    policies: Arc<HashMap<String, Condition<PolicySelector>>>,
 }
 
 impl AuthorizationPlugin {
@@ -187,6 +251,7 @@ impl AuthorizationPlugin {
             is_authenticated,
             scopes,
             policies,
+            ..
         } = Self::generate_cache_metadata(ast, &schema.definitions, false);
         if is_authenticated {
             context.insert(AUTHENTICATED_KEY, true).unwrap();
@@ -203,6 +268,45 @@ impl AuthorizationPlugin {
         }
     }
 
+    /// Returns true if a policy defined in the configuration reads the arguments of fields
+    pub(crate) fn uses_field_arguments(configuration: &Configuration) -> bool {
+        configuration
+            .apollo_plugins
+            .plugins
+            .iter()
+            .find(|(s, _)| s.as_str() == "authorization")
+            .and_then(|(_, v)| v.get("policies"))
+            .and_then(|v| {
+                serde_json::from_value::<HashMap<String, Condition<PolicySelector>>>(v.clone()).ok()
+            })
+            .is_some_and(|policies| policies.values().any(uses_arguments))
+    }
+
+    /// Stores the resolved arguments of the fields restricted by `@policy`, so that policies can
+    /// be decided per field. This depends on the request's variables, so it cannot be cached
+    /// with the query analysis
+    pub(crate) fn field_arguments_analysis(
+        doc: &ParsedDocument,
+        schema: &Schema,
+        operation_name: Option<&str>,
+        variables: &Object,
+        context: &Context,
+    ) {
+        if !context.contains_key(REQUIRED_POLICIES_KEY) {
+            return;
+        }
+
+        let fields = arguments::collect_field_arguments(
+            &schema.definitions,
+            &doc.executable,
+            operation_name,
+            variables,
+        );
+        if !fields.is_empty() {
+            context.insert(FIELD_ARGUMENTS_KEY, fields).unwrap();
+        }
+    }
+
     pub(crate) fn generate_cache_metadata(
         ast: &Document,
         schema: &apollo_compiler::Schema,
@@ -242,6 +346,8 @@ impl AuthorizationPlugin {
             is_authenticated,
             scopes,
             policies,
+            authorized_fields: Vec::new(),
+            unauthorized_fields: Vec::new(),
         }
     }
 
@@ -288,10 +394,28 @@ impl AuthorizationPlugin {
             .unwrap_or_default();
         policies.sort();
 
+        let mut authorized_fields = Vec::new();
+        let mut unauthorized_fields = Vec::new();
+        if let Ok(Some(fields)) =
+            context.get::<_, HashMap<String, FieldArguments>>(FIELD_ARGUMENTS_KEY)
+        {
+            for (field, arguments) in fields {
+                match arguments.authorized {
+                    Some(true) => authorized_fields.push(field),
+                    Some(false) => unauthorized_fields.push(field),
+                    None => {}
+                }
+            }
+        }
+        authorized_fields.sort();
+        unauthorized_fields.sort();
+
         context.extensions().lock().insert(CacheKeyMetadata {
             is_authenticated,
             scopes,
             policies,
+            authorized_fields,
+            unauthorized_fields,
         });
     }
 
@@ -315,6 +439,20 @@ impl AuthorizationPlugin {
                 .intersection(&right.policies.iter().collect::<HashSet<_>>())
                 .map(|s| s.to_string())
                 .collect(),
+            authorized_fields: left
+                .authorized_fields
+                .iter()
+                .collect::<HashSet<_>>()
+                .intersection(&right.authorized_fields.iter().collect::<HashSet<_>>())
+                .map(|s| s.to_string())
+                .collect(),
+            unauthorized_fields: left
+                .unauthorized_fields
+                .iter()
+                .collect::<HashSet<_>>()
+                .intersection(&right.unauthorized_fields.iter().collect::<HashSet<_>>())
+                .map(|s| s.to_string())
+                .collect(),
         }
     }
 
@@ -355,6 +493,8 @@ impl AuthorizationPlugin {
         let is_authenticated = key.metadata.is_authenticated;
         let scopes = &key.metadata.scopes;
         let policies = &key.metadata.policies;
+        let authorized_fields = &key.metadata.authorized_fields;
+        let unauthorized_fields = &key.metadata.unauthorized_fields;
 
         let mut is_filtered = false;
         let mut unauthorized_paths: Vec<Path> = vec![];
@@ -395,7 +535,8 @@ impl AuthorizationPlugin {
             }
         };
 
-        let filter_res = Self::policies_filter_query(schema, dry_run, &doc, policies)?;
+        let filter_res =
+            Self::policies_filter_query(schema, dry_run, &doc, policies, authorized_fields)?;
 
         let doc = match filter_res {
             None => doc,
@@ -413,6 +554,23 @@ impl AuthorizationPlugin {
             }
         };
 
+        let filter_res = Self::arguments_filter_query(schema, dry_run, &doc, unauthorized_fields)?;
+
+        let doc = match filter_res {
+            None => doc,
+            Some((filtered_doc, paths)) => {
+                unauthorized_paths.extend(paths);
+
+                if filtered_doc.definitions.is_empty() {
+                    return Err(QueryPlannerError::Unauthorized(unauthorized_paths));
+                }
+
+                is_filtered = true;
+
+                filtered_doc
+            }
+        };
+
         if reject_unauthorized && !unauthorized_paths.is_empty() {
             return Err(QueryPlannerError::Unauthorized(unauthorized_paths));
         }
@@ -499,12 +657,14 @@ impl AuthorizationPlugin {
 
         doc: &ast::Document,
         policies: &[String],
+        authorized_fields: &[String],
     ) -> Result<Option<(ast::Document, Vec<Path>)>, QueryPlannerError> {
         if let Some(mut visitor) = PolicyFilteringVisitor::new(
             &schema.definitions,
             doc,
             &schema.implementers_map,
             policies.iter().cloned().collect(),
+            authorized_fields.iter().cloned().collect(),
             dry_run,
         ) {
             let modified_query = transform::document(&mut visitor, doc)
@@ -528,6 +688,40 @@ impl AuthorizationPlugin {
             Ok(None)
         }
     }
+
+    fn arguments_filter_query(
+        schema: &Schema,
+        dry_run: bool,
+        doc: &ast::Document,
+        unauthorized_fields: &[String],
+    ) -> Result<Option<(ast::Document, Vec<Path>)>, QueryPlannerError> {
+        if let Some(mut visitor) = ArgumentsFilteringVisitor::new(
+            &schema.definitions,
+            doc,
+            unauthorized_fields.iter().cloned().collect(),
+            dry_run,
+        ) {
+            let modified_query = transform::document(&mut visitor, doc)
+                .map_err(|e| SpecError::ParsingError(e.to_string()))?;
+
+            if visitor.query_has_unauthorized_fields {
+                tracing::debug!("the query contains fields denied from their arguments: {unauthorized_fields:?}, modified query:\n{modified_query}\nunauthorized paths: {:?}",
+                visitor
+                    .unauthorized_paths
+                    .iter()
+                    .map(|path| path.to_string())
+                    .collect::<Vec<_>>()
+            );
+                Ok(Some((modified_query, visitor.unauthorized_paths)))
+            } else {
+                tracing::debug!("the query does not contain fields denied from their arguments");
+                Ok(None)
+            }
+        } else {
+            tracing::debug!("no field was denied from its arguments");
+            Ok(None)
+        }
+    }
 }
 
 #[async_trait::async_trait]
@@ -537,10 +731,23 @@ impl Plugin for AuthorizationPlugin {
     async fn new(init: PluginInit<Self::Config>) -> Result<Self, BoxError> {
         Ok(AuthorizationPlugin {
             require_authentication: init.config.require_authentication,
+            policies: Arc::new(init.config.policies),
         })
     }
 
     fn supergraph_service(&self, service: supergraph::BoxService) -> supergraph::BoxService {
+        let service = if self.policies.is_empty() {
+            service
+        } else {
+            let policies = self.policies.clone();
+            ServiceBuilder::new()
+                .map_request(move |request: supergraph::Request| {
+                    evaluate_policies(&policies, request)
+                })
+                .service(service)
+                .boxed()
+        };
+
         if self.require_authentication {
             ServiceBuilder::new()
                 .checkpoint(move |request: supergraph::Request| {
@@ -598,6 +805,119 @@ impl Plugin for AuthorizationPlugin {
     }
 }
 
+/// Decides the policies required by the query that are defined in the configuration.
+///
+/// Policies already decided by another plugin are left untouched. Policies using field arguments
+/// are denied for the whole query, then decided for each field requiring them through the
+/// `@policy` directive of the field or of its type: only the fields allowed this way are kept.
+fn evaluate_policies(
+    policies: &HashMap<String, Condition<PolicySelector>>,
+    request: supergraph::Request,
+) -> supergraph::Request {
+    let mut required: HashMap<String, Option<bool>> =
+        match request.context.get(REQUIRED_POLICIES_KEY) {
+            Ok(Some(required)) => required,
+            _ => return request,
+        };
+
+    let mut request = PolicyRequest {
+        request,
+        arguments: None,
+    };
+
+    let mut argument_policies = HashSet::new();
+    let mut evaluated = false;
+    for (policy, result) in required.iter_mut() {
+        if result.is_some() {
+            continue;
+        }
+        if let Some(condition) = policies.get(policy) {
+            if uses_arguments(condition) {
+                // the fields requiring it are allowed individually below
+                argument_policies.insert(policy.clone());
+                *result = Some(false);
+            } else {
+                *result = Some(condition.evaluate_request(&request));
+            }
+            evaluated = true;
+        }
+    }
+
+    if !evaluated {
+        return request.request;
+    }
+    let _ = request
+        .request
+        .context
+        .insert(REQUIRED_POLICIES_KEY, required.clone());
+
+    if argument_policies.is_empty() {
+        return request.request;
+    }
+
+    let mut fields: HashMap<String, FieldArguments> =
+        match request.request.context.get(FIELD_ARGUMENTS_KEY) {
+            Ok(Some(fields)) => fields,
+            _ => return request.request,
+        };
+
+    for field in fields.values_mut() {
+        if field.authorized.is_some()
+            || !field
+                .policies
+                .iter()
+                .chain(&field.type_policies)
+                .flatten()
+                .any(|policy| argument_policies.contains(policy))
+        {
+            continue;
+        }
+
+        request.arguments = Some(std::mem::take(&mut field.arguments));
+        // like the `@policy` directive, at least one set must have all of its policies allowed
+        let is_allowed = |policies_sets: &[Vec<String>]| {
+            policies_sets.is_empty()
+                || policies_sets.iter().any(|set| {
+                    set.iter().all(|policy| {
+                        if argument_policies.contains(policy) {
+                            policies[policy].evaluate_request(&request)
+                        } else {
+                            required.get(policy).copied().flatten().unwrap_or(false)
+                        }
+                    })
+                })
+        };
+        let authorized = is_allowed(&field.policies) && is_allowed(&field.type_policies);
+        field.arguments = request.arguments.take().unwrap_or_default();
+        field.authorized = Some(authorized);
+    }
+
+    let _ = request.request.context.insert(FIELD_ARGUMENTS_KEY, fields);
+
+    request.request
+}
+
+/// Returns true if the condition reads the arguments of a field
+fn uses_arguments(condition: &Condition<PolicySelector>) -> bool {
+    let is_argument = |value: &SelectorOrValue<PolicySelector>| {
+        matches!(
+            value,
+            SelectorOrValue::Selector(PolicySelector::Argument { .. })
+        )
+    };
+    match condition {
+        Condition::Eq(values) | Condition::Gt(values) | Condition::Lt(values) => {
+            values.iter().any(is_argument)
+        }
+        Condition::Matches { value, .. } | Condition::In { value, .. } => is_argument(value),
+        Condition::Exists(selector) => matches!(selector, PolicySelector::Argument { .. }),
+        Condition::All(conditions) | Condition::Any(conditions) => {
+            conditions.iter().any(uses_arguments)
+        }
+        Condition::Not(condition) => uses_arguments(condition),
+    }
+}
+
 // This macro allows us to use it in our plugin registry!
 // register_plugin takes a group name, and a plugin name.
 //
@@ -15,6 +15,7 @@ use apollo_compiler::schema::Implementers;
 use apollo_compiler::schema::Name;
 use tower::BoxError;
 
+use super::arguments::field_key;
 use crate::json_ext::Path;
 use crate::json_ext::PathElement;
 use crate::spec::query::transform;
@@ -193,6 +194,8 @@ pub(crate) struct PolicyFilteringVisitor<'a> {
     implementers_map: &'a HashMap<Name, Implementers>,
     dry_run: bool,
     request_policies: HashSet<String>,
+    /// fields allowed by policies evaluated with their arguments, see [`field_key`]
+    authorized_fields: HashSet<String>,
     pub(crate) query_requires_policies: bool,
     pub(crate) unauthorized_paths: Vec<Path>,
     // store the error paths from fragments so we can  add them at
@@ -202,7 +205,7 @@ pub(crate) struct PolicyFilteringVisitor<'a> {
     policy_directive_name: String,
 }
 
-fn policies_sets_argument(
+pub(crate) fn policies_sets_argument(
     directive: &ast::Directive,
 ) -> impl Iterator<Item = HashSet<String>> + '_ {
     directive
@@ -227,6 +230,7 @@ impl<'a> PolicyFilteringVisitor<'a> {
         executable: &'a ast::Document,
         implementers_map: &'a HashMap<Name, Implementers>,
         successful_policies: HashSet<String>,
+        authorized_fields: HashSet<String>,
         dry_run: bool,
     ) -> Option<Self> {
         Some(Self {
@@ -235,6 +239,7 @@ impl<'a> PolicyFilteringVisitor<'a> {
             implementers_map,
             dry_run,
             request_policies: successful_policies,
+            authorized_fields,
             query_requires_policies: false,
             unauthorized_paths: vec![],
             fragments_unauthorized_paths: HashMap::new(),
@@ -470,7 +475,11 @@ impl<'a> transform::Visitor for PolicyFilteringVisitor<'a> {
         let field_name = &node.name;
         let is_field_list = field_def.ty.is_list();
 
-        let is_authorized = self.is_field_authorized(field_def);
+        // the policies of the field and of its type were already evaluated with its arguments
+        let is_authorized =
+            self.authorized_fields
+                .contains(&field_key(parent_type, field_name, &node.arguments))
+                || self.is_field_authorized(field_def);
 
         let implementors_with_different_requirements =
             self.implementors_with_different_requirements(field_def, node);
@@ -748,7 +757,8 @@ mod tests {
         doc.to_executable_validate(&schema).unwrap();
         let map = schema.implementers_map();
         let mut visitor =
-            PolicyFilteringVisitor::new(&schema, &doc, &map, policies, false).unwrap();
+            PolicyFilteringVisitor::new(&schema, &doc, &map, policies, HashSet::new(), false)
+                .unwrap();
         (
             transform::document(&mut visitor, &doc).unwrap(),
             visitor.unauthorized_paths,
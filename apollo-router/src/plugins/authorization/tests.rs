@@ -1,13 +1,23 @@
+use std::collections::HashMap;
+
 use futures::StreamExt;
 use http::header::ACCEPT;
 use http::header::CONTENT_TYPE;
 use serde_json_bytes::json;
 use tower::ServiceExt;
 
+use super::evaluate_policies;
+use super::AuthorizationPlugin;
+use super::PolicySelector;
+use super::FIELD_ARGUMENTS_KEY;
+use super::REQUIRED_POLICIES_KEY;
 use crate::graphql;
+use crate::json_ext::Path;
 use crate::plugin::test::MockSubgraph;
+use crate::plugins::telemetry::config_new::conditions::Condition;
 use crate::services::router;
 use crate::services::supergraph;
+use crate::Configuration;
 use crate::Context;
 use crate::MockedSubgraphs;
 use crate::TestHarness;
@@ -774,6 +784,597 @@ async fn scopes_directive() {
     insta::assert_json_snapshot!(response);
 }
 
+const POLICY_SCHEMA: &str = r#"schema
+  @link(url: "https://specs.apollo.dev/link/v1.0")
+  @link(url: "https://specs.apollo.dev/join/v0.3", for: EXECUTION)
+  @link(url: "https://specs.apollo.dev/policy/v0.1", for: SECURITY)
+  {
+    query: Query
+}
+directive @link(url: String, as: String, for: link__Purpose, import: [link__Import]) repeatable on SCHEMA
+directive @join__enumValue(graph: join__Graph!) repeatable on ENUM_VALUE
+directive @join__field(graph: join__Graph, requires: join__FieldSet, provides: join__FieldSet, type: String, external: Boolean, override: String, usedOverridden: Boolean) repeatable on FIELD_DEFINITION | INPUT_FIELD_DEFINITION
+directive @join__graph(name: String!, url: String!) on ENUM_VALUE
+directive @join__implements(graph: join__Graph!, interface: String!) repeatable on OBJECT | INTERFACE
+directive @join__type(graph: join__Graph!, key: join__FieldSet, extension: Boolean! = false, resolvable: Boolean! = true, isInterfaceObject: Boolean! = false) repeatable on OBJECT | INTERFACE | UNION | ENUM | INPUT_OBJECT | SCALAR
+directive @join__unionMember(graph: join__Graph!, member: String!) repeatable on UNION
+
+scalar link__Import
+enum link__Purpose {
+    """
+    `SECURITY` features provide metadata necessary to securely resolve fields.
+    """
+    SECURITY
+  
+    """
+    `EXECUTION` features provide metadata necessary for operation execution.
+    """
+    EXECUTION
+  }
+
+scalar federation__Policy
+directive @policy(policies: [[federation__Policy!]!]!) on OBJECT | FIELD_DEFINITION | INTERFACE | SCALAR | ENUM
+
+scalar join__FieldSet
+enum join__Graph {
+   USER @join__graph(name: "user", url: "http://localhost:4001/graphql")
+   ORGA @join__graph(name: "orga", url: "http://localhost:4002/graphql")
+}
+
+type Query
+@join__type(graph: ORGA)
+@join__type(graph: USER){
+   currentUser: User @join__field(graph: USER)
+   orga(id: ID): Organization @join__field(graph: ORGA)
+}
+type User
+@join__type(graph: ORGA, key: "id")
+@join__type(graph: USER, key: "id") {
+   id: ID!
+   name: String
+   phone: String @policy(policies: [["admin"]])
+   activeOrganization: Organization
+}
+type Organization
+@join__type(graph: ORGA, key: "id")
+@join__type(graph: USER, key: "id") {
+   id: ID
+   creatorUser: User
+   name: String
+   nonNullId: ID!
+   suborga: [Organization]
+}"#;
+
+#[tokio::test]
+async fn policy_directive_evaluated_from_configuration() {
+    let subgraphs = MockedSubgraphs([
+    ("user", MockSubgraph::builder().with_json(
+            serde_json::json!{{
+                "query": "query($representations:[_Any!]!){_entities(representations:$representations){...on User{name}}}",
+                "variables": {"representations": [{ "__typename": "User", "id":0 }],}
+            }},
+            serde_json::json! {{ "data": { "_entities":[{"name":"Ada"}] } }},
+        ).with_json(
+            serde_json::json!{{
+                "query": "query($representations:[_Any!]!){_entities(representations:$representations){...on User{name phone}}}",
+                "variables": {"representations": [{ "__typename": "User", "id":0 }],}
+            }},
+            serde_json::json! {{ "data": { "_entities":[{"name":"Ada", "phone": "1234"}] } }},
+        ).build()),
+    ("orga", MockSubgraph::builder().with_json(
+        serde_json::json!{{"query":"{orga(id:1){id creatorUser{__typename id}}}"}},
+        serde_json::json!{{"data": {"orga": { "id": 1, "creatorUser": { "__typename": "User", "id": 0 } }}}}
+    ).build())
+].into_iter().collect());
+
+    let service = TestHarness::builder()
+        .configuration_json(serde_json::json!({
+        "include_subgraph_errors": {
+            "all": true
+        },
+        "authorization": {
+            "directives": {
+                "enabled": true
+            },
+            "policies": {
+                "admin": {
+                    "any": [
+                        { "eq": [{ "jwt_claim": "role" }, "admin"] },
+                        { "eq": [{ "request_header": "x-admin-key" }, "secret"] }
+                    ]
+                }
+            }
+        }}))
+        .unwrap()
+        .schema(POLICY_SCHEMA)
+        .extra_plugin(subgraphs)
+        .build_router()
+        .await
+        .unwrap();
+
+    let req = graphql::Request {
+        query: Some("query { orga(id: 1) { id creatorUser { id name phone } } }".to_string()),
+        ..Default::default()
+    };
+
+    let context = Context::new();
+    context
+        .insert(
+            "apollo_authentication::JWT::claims",
+            json! {{ "role": "user" }},
+        )
+        .unwrap();
+    let request = router::Request {
+        context,
+        router_request: http::Request::builder()
+            .method("POST")
+            .header(CONTENT_TYPE, "application/json")
+            .header(ACCEPT, "application/json")
+            .body(serde_json::to_vec(&req).unwrap().into())
+            .unwrap(),
+    };
+
+    let response = service
+        .clone()
+        .oneshot(request)
+        .await
+        .unwrap()
+        .into_graphql_response_stream()
+        .await
+        .next()
+        .await
+        .unwrap()
+        .unwrap();
+
+    insta::assert_json_snapshot!(response);
+
+    let context = Context::new();
+    context
+        .insert(
+            "apollo_authentication::JWT::claims",
+            json! {{ "role": "admin" }},
+        )
+        .unwrap();
+    let request = router::Request {
+        context,
+        router_request: http::Request::builder()
+            .method("POST")
+            .header(CONTENT_TYPE, "application/json")
+            .header(ACCEPT, "application/json")
+            .body(serde_json::to_vec(&req).unwrap().into())
+            .unwrap(),
+    };
+
+    let response = service
+        .clone()
+        .oneshot(request)
+        .await
+        .unwrap()
+        .into_graphql_response_stream()
+        .await
+        .next()
+        .await
+        .unwrap()
+        .unwrap();
+
+    insta::assert_json_snapshot!(response);
+
+    let request = router::Request {
+        context: Context::new(),
+        router_request: http::Request::builder()
+            .method("POST")
+            .header(CONTENT_TYPE, "application/json")
+            .header(ACCEPT, "application/json")
+            .header("x-admin-key", "secret")
+            .body(serde_json::to_vec(&req).unwrap().into())
+            .unwrap(),
+    };
+
+    let response = service
+        .oneshot(request)
+        .await
+        .unwrap()
+        .into_graphql_response_stream()
+        .await
+        .next()
+        .await
+        .unwrap()
+        .unwrap();
+
+    insta::assert_json_snapshot!(response);
+}
+
+const ARGUMENTS_POLICY_SCHEMA: &str = r#"schema
+  @link(url: "https://specs.apollo.dev/link/v1.0")
+  @link(url: "https://specs.apollo.dev/join/v0.3", for: EXECUTION)
+  @link(url: "https://specs.apollo.dev/policy/v0.1", for: SECURITY)
+  {
+    query: Query
+}
+directive @link(url: String, as: String, for: link__Purpose, import: [link__Import]) repeatable on SCHEMA
+directive @join__enumValue(graph: join__Graph!) repeatable on ENUM_VALUE
+directive @join__field(graph: join__Graph, requires: join__FieldSet, provides: join__FieldSet, type: String, external: Boolean, override: String, usedOverridden: Boolean) repeatable on FIELD_DEFINITION | INPUT_FIELD_DEFINITION
+directive @join__graph(name: String!, url: String!) on ENUM_VALUE
+directive @join__implements(graph: join__Graph!, interface: String!) repeatable on OBJECT | INTERFACE
+directive @join__type(graph: join__Graph!, key: join__FieldSet, extension: Boolean! = false, resolvable: Boolean! = true, isInterfaceObject: Boolean! = false) repeatable on OBJECT | INTERFACE | UNION | ENUM | INPUT_OBJECT | SCALAR
+directive @join__unionMember(graph: join__Graph!, member: String!) repeatable on UNION
+
+scalar link__Import
+enum link__Purpose {
+    """
+    `SECURITY` features provide metadata necessary to securely resolve fields.
+    """
+    SECURITY
+  
+    """
+    `EXECUTION` features provide metadata necessary for operation execution.
+    """
+    EXECUTION
+  }
+
+scalar federation__Policy
+directive @policy(policies: [[federation__Policy!]!]!) on OBJECT | FIELD_DEFINITION | INTERFACE | SCALAR | ENUM
+
+scalar join__FieldSet
+enum join__Graph {
+   USER @join__graph(name: "user", url: "http://localhost:4001/graphql")
+   ORGA @join__graph(name: "orga", url: "http://localhost:4002/graphql")
+}
+
+type Query
+@join__type(graph: ORGA)
+@join__type(graph: USER){
+   currentUser: User @join__field(graph: USER)
+   orga(id: ID): Organization @join__field(graph: ORGA) @policy(policies: [["member"], ["admin"]])
+   team(id: ID): Team @join__field(graph: ORGA)
+}
+type User
+@join__type(graph: ORGA, key: "id")
+@join__type(graph: USER, key: "id") {
+   id: ID!
+   name: String
+   phone: String @policy(policies: [["admin"]])
+   activeOrganization: Organization
+}
+type Organization
+@join__type(graph: ORGA, key: "id")
+@join__type(graph: USER, key: "id") {
+   id: ID
+   creatorUser: User
+   name: String
+   nonNullId: ID!
+   suborga: [Organization]
+}
+type Team
+@join__type(graph: ORGA, key: "id")
+@policy(policies: [["member"]]) {
+   id: ID
+   name: String
+}"#;
+
+#[tokio::test]
+async fn policy_evaluated_from_field_arguments() {
+    let subgraphs = MockedSubgraphs(
+        [
+            (
+                "user",
+                MockSubgraph::builder()
+                    .with_json(
+                        serde_json::json! {{"query":"{currentUser{id}}"}},
+                        serde_json::json! {{"data": {"currentUser": { "id": 0 }}}},
+                    )
+                    .build(),
+            ),
+            (
+                "orga",
+                MockSubgraph::builder()
+                    .with_json(
+                        serde_json::json! {{"query":"{orga(id:\"1\"){id name}}"}},
+                        serde_json::json! {{"data": {"orga": { "id": 1, "name": "Apollo" }}}},
+                    )
+                    .build(),
+            ),
+        ]
+        .into_iter()
+        .collect(),
+    );
+
+    let service = TestHarness::builder()
+        .configuration_json(serde_json::json!({
+        "include_subgraph_errors": {
+            "all": true
+        },
+        "authorization": {
+            "directives": {
+                "enabled": true
+            },
+            "policies": {
+                "member": { "eq": [{ "argument": "id" }, { "jwt_claim": "orga" }] },
+                "admin": { "eq": [{ "jwt_claim": "role" }, "admin"] }
+            }
+        }}))
+        .unwrap()
+        .schema(ARGUMENTS_POLICY_SCHEMA)
+        .extra_plugin(subgraphs)
+        .build_router()
+        .await
+        .unwrap();
+
+    let req = graphql::Request {
+        query: Some(r#"query { currentUser { id } orga(id: "1") { id name } }"#.to_string()),
+        ..Default::default()
+    };
+
+    let context = Context::new();
+    context
+        .insert(
+            "apollo_authentication::JWT::claims",
+            json! {{ "orga": "1" }},
+        )
+        .unwrap();
+    let request = router::Request {
+        context,
+        router_request: http::Request::builder()
+            .method("POST")
+            .header(CONTENT_TYPE, "application/json")
+            .header(ACCEPT, "application/json")
+            .body(serde_json::to_vec(&req).unwrap().into())
+            .unwrap(),
+    };
+
+    let response = service
+        .clone()
+        .oneshot(request)
+        .await
+        .unwrap()
+        .into_graphql_response_stream()
+        .await
+        .next()
+        .await
+        .unwrap()
+        .unwrap();
+
+    insta::assert_json_snapshot!(response);
+
+    let context = Context::new();
+    context
+        .insert(
+            "apollo_authentication::JWT::claims",
+            json! {{ "orga": "2" }},
+        )
+        .unwrap();
+    let request = router::Request {
+        context,
+        router_request: http::Request::builder()
+            .method("POST")
+            .header(CONTENT_TYPE, "application/json")
+            .header(ACCEPT, "application/json")
+            .body(serde_json::to_vec(&req).unwrap().into())
+            .unwrap(),
+    };
+
+    let response = service
+        .clone()
+        .oneshot(request)
+        .await
+        .unwrap()
+        .into_graphql_response_stream()
+        .await
+        .next()
+        .await
+        .unwrap()
+        .unwrap();
+
+    insta::assert_json_snapshot!(response);
+
+    let context = Context::new();
+    context
+        .insert(
+            "apollo_authentication::JWT::claims",
+            json! {{ "orga": "2", "role": "admin" }},
+        )
+        .unwrap();
+    let request = router::Request {
+        context,
+        router_request: http::Request::builder()
+            .method("POST")
+            .header(CONTENT_TYPE, "application/json")
+            .header(ACCEPT, "application/json")
+            .body(serde_json::to_vec(&req).unwrap().into())
+            .unwrap(),
+    };
+
+    let response = service
+        .oneshot(request)
+        .await
+        .unwrap()
+        .into_graphql_response_stream()
+        .await
+        .next()
+        .await
+        .unwrap()
+        .unwrap();
+
+    insta::assert_json_snapshot!(response);
+}
+
+#[tokio::test]
+async fn policy_evaluated_from_field_arguments_on_type() {
+    let subgraphs = MockedSubgraphs(
+        [
+            (
+                "user",
+                MockSubgraph::builder()
+                    .with_json(
+                        serde_json::json! {{"query":"{currentUser{id}}"}},
+                        serde_json::json! {{"data": {"currentUser": { "id": 0 }}}},
+                    )
+                    .build(),
+            ),
+            (
+                "orga",
+                MockSubgraph::builder()
+                    .with_json(
+                        serde_json::json! {{"query":"{team(id:\"1\"){id name}}"}},
+                        serde_json::json! {{"data": {"team": { "id": 1, "name": "Router" }}}},
+                    )
+                    .build(),
+            ),
+        ]
+        .into_iter()
+        .collect(),
+    );
+
+    let service = TestHarness::builder()
+        .configuration_json(serde_json::json!({
+        "include_subgraph_errors": {
+            "all": true
+        },
+        "authorization": {
+            "directives": {
+                "enabled": true
+            },
+            "policies": {
+                "member": { "eq": [{ "argument": "id" }, { "jwt_claim": "orga" }] }
+            }
+        }}))
+        .unwrap()
+        .schema(ARGUMENTS_POLICY_SCHEMA)
+        .extra_plugin(subgraphs)
+        .build_router()
+        .await
+        .unwrap();
+
+    let req = graphql::Request {
+        query: Some(r#"query { currentUser { id } team(id: "1") { id name } }"#.to_string()),
+        ..Default::default()
+    };
+
+    // the `@policy` directive of the `Team` type is evaluated with the arguments of `team`
+    let context = Context::new();
+    context
+        .insert(
+            "apollo_authentication::JWT::claims",
+            json! {{ "orga": "2" }},
+        )
+        .unwrap();
+    let request = router::Request {
+        context,
+        router_request: http::Request::builder()
+            .method("POST")
+            .header(CONTENT_TYPE, "application/json")
+            .header(ACCEPT, "application/json")
+            .body(serde_json::to_vec(&req).unwrap().into())
+            .unwrap(),
+    };
+
+    let response = service
+        .clone()
+        .oneshot(request)
+        .await
+        .unwrap()
+        .into_graphql_response_stream()
+        .await
+        .next()
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(
+        response.data,
+        Some(json! {{ "currentUser": { "id": 0 }, "team": null }})
+    );
+    assert_eq!(response.errors.len(), 1);
+    assert_eq!(response.errors[0].path, Some(Path::from("team")));
+
+    let context = Context::new();
+    context
+        .insert(
+            "apollo_authentication::JWT::claims",
+            json! {{ "orga": "1" }},
+        )
+        .unwrap();
+    let request = router::Request {
+        context,
+        router_request: http::Request::builder()
+            .method("POST")
+            .header(CONTENT_TYPE, "application/json")
+            .header(ACCEPT, "application/json")
+            .body(serde_json::to_vec(&req).unwrap().into())
+            .unwrap(),
+    };
+
+    let response = service
+        .oneshot(request)
+        .await
+        .unwrap()
+        .into_graphql_response_stream()
+        .await
+        .next()
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(
+        response.data,
+        Some(json! {{ "currentUser": { "id": 0 }, "team": { "id": 1, "name": "Router" } }})
+    );
+    assert!(response.errors.is_empty());
+}
+
+#[test]
+fn argument_policies_are_denied_without_field_arguments() {
+    let policies: HashMap<String, Condition<PolicySelector>> =
+        serde_json::from_value(serde_json::json!({
+            "member": { "eq": [{ "argument": "id" }, { "jwt_claim": "orga" }] }
+        }))
+        .unwrap();
+
+    let context = Context::new();
+    context
+        .insert(
+            REQUIRED_POLICIES_KEY,
+            HashMap::from([("member".to_string(), None::<bool>)]),
+        )
+        .unwrap();
+    let request = supergraph::Request::fake_builder()
+        .context(context)
+        .build()
+        .unwrap();
+
+    // no field arguments were collected, so no field can be allowed by the policy
+    let request = evaluate_policies(&policies, request);
+
+    let required: HashMap<String, Option<bool>> =
+        request.context.get(REQUIRED_POLICIES_KEY).unwrap().unwrap();
+    assert_eq!(required["member"], Some(false));
+    assert!(!request.context.contains_key(FIELD_ARGUMENTS_KEY));
+}
+
+#[test]
+fn field_arguments_are_only_collected_for_argument_policies() {
+    let configuration: Configuration = serde_json::from_value(serde_json::json!({
+        "authorization": {
+            "policies": {
+                "admin": { "eq": [{ "jwt_claim": "role" }, "admin"] }
+            }
+        }
+    }))
+    .unwrap();
+    assert!(!AuthorizationPlugin::uses_field_arguments(&configuration));
+
+    let configuration: Configuration = serde_json::from_value(serde_json::json!({
+        "authorization": {
+            "policies": {
+                "admin": { "eq": [{ "jwt_claim": "role" }, "admin"] },
+                "member": { "eq": [{ "argument": "id" }, { "jwt_claim": "orga" }] }
+            }
+        }
+    }))
+    .unwrap();
+    assert!(AuthorizationPlugin::uses_field_arguments(&configuration));
+}
+
 #[tokio::test]
 async fn scopes_directive_reject_unauthorized() {
     let subgraphs = MockedSubgraphs([
@@ -19,7 +19,9 @@ use http::header::TE;
 use http::header::TRAILER;
 use http::header::TRANSFER_ENCODING;
 use http::header::UPGRADE;
+use http::HeaderMap;
 use http::HeaderValue;
+use indexmap::IndexMap;
 use regex::Regex;
 use schemars::JsonSchema;
 use serde::Deserialize;
@@ -30,6 +32,7 @@ use tower::ServiceBuilder;
 use tower::ServiceExt;
 use tower_service::Service;
 
+use crate::layers::ServiceBuilderExt;
 use crate::plugin::serde::deserialize_header_name;
 use crate::plugin::serde::deserialize_header_value;
 use crate::plugin::serde::deserialize_json_query;
@@ -38,8 +41,13 @@ use crate::plugin::serde::deserialize_option_header_value;
 use crate::plugin::serde::deserialize_regex;
 use crate::plugin::Plugin;
 use crate::plugin::PluginInit;
+use crate::plugins::telemetry::config_new::selectors::SubgraphSelector;
+use crate::plugins::telemetry::config_new::Selector;
+use crate::query_planner::fetch::QueryHash;
 use crate::register_plugin;
+use crate::services::execution;
 use crate::services::subgraph;
+use crate::services::supergraph;
 use crate::services::SubgraphRequest;
 
 register_plugin!("apollo", "headers", Headers);
@@ -48,9 +56,11 @@ register_plugin!("apollo", "headers", Headers);
 #[serde(rename_all = "snake_case", deny_unknown_fields)]
 struct HeadersLocation {
     /// Propagate/Insert/Remove headers from request
+    #[serde(default)]
     request: Vec<Operation>,
-    // Propagate/Insert/Remove headers from response
-    // response: Option<Operation>
+    /// Propagate headers from the subgraph responses to the client response
+    #[serde(default)]
+    response: Vec<ResponseOperation>,
 }
 
 #[derive(Clone, JsonSchema, Deserialize)]
@@ -94,6 +104,8 @@ enum Insert {
     FromContext(InsertFromContext),
     /// Insert header with a value coming from body
     FromBody(InsertFromBody),
+    /// Insert header with a value coming from a selector
+    FromSelector(InsertFromSelector),
 }
 
 #[derive(Clone, JsonSchema, Deserialize)]
@@ -143,6 +155,60 @@ struct InsertFromBody {
     default: Option<HeaderValue>,
 }
 
+#[derive(Clone, JsonSchema, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+/// Insert header with a value coming from a selector
+struct InsertFromSelector {
+    /// The target header name
+    #[schemars(with = "String")]
+    #[serde(deserialize_with = "deserialize_header_name")]
+    name: HeaderName,
+
+    /// The selector of the value, with the same syntax as the subgraph selectors of telemetry
+    from_selector: SubgraphSelector,
+
+    /// An optional rewrite of the selected value
+    rewrite: Option<Rewrite>,
+}
+
+impl InsertFromSelector {
+    fn value(&self, request: &SubgraphRequest) -> Option<String> {
+        let value = self
+            .from_selector
+            .on_request(request)?
+            .as_str()
+            .into_owned();
+        match &self.rewrite {
+            Some(rewrite) => rewrite.apply(&value),
+            None => Some(value),
+        }
+    }
+}
+
+#[derive(Clone, JsonSchema, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+/// Rewrite of a header value with a regex, with the same fields and replacement as the `redact`
+/// option of the telemetry selectors
+struct Rewrite {
+    /// The regex matching the value, the header is not inserted if the value does not match
+    #[schemars(with = "String")]
+    #[serde(deserialize_with = "deserialize_regex")]
+    pattern: Regex,
+
+    /// The replacement of the matches, which can refer to capture groups such as `$1`
+    replacement: String,
+}
+
+impl Rewrite {
+    fn apply(&self, value: &str) -> Option<String> {
+        self.pattern.is_match(value).then(|| {
+            self.pattern
+                .replace_all(value, self.replacement.as_str())
+                .into_owned()
+        })
+    }
+}
+
 schemar_fn!(
     propagate_matching,
     String,
@@ -180,6 +246,61 @@ enum Propagate {
     },
 }
 
+#[derive(Clone, JsonSchema, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum ResponseOperation {
+    Propagate(ResponsePropagate),
+}
+
+#[derive(Clone, JsonSchema, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+#[serde(untagged)]
+/// Propagate header from the subgraph response to the client response
+enum ResponsePropagate {
+    /// Propagate header given a header name
+    Named {
+        /// The source header name
+        #[schemars(with = "String")]
+        #[serde(deserialize_with = "deserialize_header_name")]
+        named: HeaderName,
+
+        /// An optional target header name
+        #[schemars(with = "Option<String>", default)]
+        #[serde(deserialize_with = "deserialize_option_header_name", default)]
+        rename: Option<HeaderName>,
+
+        /// How to merge the values of the header coming from several subgraph responses
+        #[serde(default)]
+        merge: Merge,
+    },
+    /// Propagate header given a regex to match header name
+    Matching {
+        /// The regex on header name
+        #[schemars(schema_with = "propagate_matching")]
+        #[serde(deserialize_with = "deserialize_regex")]
+        matching: Regex,
+
+        /// How to merge the values of the header coming from several subgraph responses
+        #[serde(default)]
+        merge: Merge,
+    },
+}
+
+/// How to merge the values of a header coming from several subgraph responses, in plan order
+#[derive(Clone, Copy, Debug, Default, PartialEq, JsonSchema, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Merge {
+    /// Keep the value of the first response
+    First,
+    /// Keep the value of the last response
+    Last,
+    /// Keep all the values, as repeated headers
+    #[default]
+    Append,
+    /// Keep all the values, joined with a comma in a single header
+    Join,
+}
+
 /// Configuration for header propagation
 #[derive(Clone, JsonSchema, Default, Deserialize)]
 #[serde(rename_all = "snake_case", deny_unknown_fields, default)]
@@ -193,6 +314,8 @@ struct Config {
 struct Headers {
     all_operations: Arc<Vec<Operation>>,
     subgraph_operations: HashMap<String, Arc<Vec<Operation>>>,
+    all_response_operations: Arc<Vec<ResponseOperation>>,
+    subgraph_response_operations: HashMap<String, Arc<Vec<ResponseOperation>>>,
 }
 
 #[async_trait::async_trait]
@@ -216,15 +339,111 @@ impl Plugin for Headers {
                 (subgraph_name.clone(), Arc::new(operations))
             })
             .collect();
+        let response_operations: Vec<ResponseOperation> = init
+            .config
+            .all
+            .as_ref()
+            .map(|a| a.response.clone())
+            .unwrap_or_default();
+        let subgraph_response_operations = init
+            .config
+            .subgraphs
+            .iter()
+            .map(|(subgraph_name, op)| {
+                let mut operations = response_operations.clone();
+                operations.append(&mut op.response.clone());
+                (subgraph_name.clone(), Arc::new(operations))
+            })
+            .collect();
 
         Ok(Headers {
             all_operations: Arc::new(operations),
             subgraph_operations,
+            all_response_operations: Arc::new(response_operations),
+            subgraph_response_operations,
         })
     }
 
+    fn supergraph_service(&self, service: supergraph::BoxService) -> supergraph::BoxService {
+        if !self.propagates_responses() {
+            return service;
+        }
+        service
+            .map_response(|mut response: supergraph::Response| {
+                let propagated = response
+                    .context
+                    .extensions()
+                    .lock()
+                    .remove::<PropagatedResponseHeaders>();
+                if let Some(propagated) = propagated {
+                    propagated.apply(response.response.headers_mut());
+                }
+                response
+            })
+            .boxed()
+    }
+
+    fn execution_service(&self, service: execution::BoxService) -> execution::BoxService {
+        if !self.propagates_responses() {
+            return service;
+        }
+        service
+            .map_request(|req: execution::Request| {
+                let plan_order = req
+                    .query_plan
+                    .root
+                    .fetch_nodes()
+                    .map(|fetch| fetch.schema_aware_hash.clone())
+                    .collect();
+                req.context
+                    .extensions()
+                    .lock()
+                    .insert(PropagatedResponseHeaders {
+                        plan_order,
+                        responses: Vec::new(),
+                    });
+                req
+            })
+            .boxed()
+    }
+
     fn subgraph_service(&self, name: &str, service: subgraph::BoxService) -> subgraph::BoxService {
+        let response_operations = self
+            .subgraph_response_operations
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| self.all_response_operations.clone());
+        let reserved_headers: Arc<HashSet<&'static HeaderName>> =
+            Arc::new(RESERVED_HEADERS.iter().collect());
         ServiceBuilder::new()
+            .map_future_with_request_data(
+                |req: &SubgraphRequest| req.query_hash.clone(),
+                move |query_hash: Arc<QueryHash>, fut| {
+                    let response_operations = response_operations.clone();
+                    let reserved_headers = reserved_headers.clone();
+                    async move {
+                        let response: subgraph::ServiceResult = fut.await;
+                        if let Ok(response) = &response {
+                            if !response_operations.is_empty() {
+                                let headers = propagated_response_headers(
+                                    &response_operations,
+                                    &reserved_headers,
+                                    response.response.headers(),
+                                );
+                                if let Some(propagated) = response
+                                    .context
+                                    .extensions()
+                                    .lock()
+                                    .get_mut::<PropagatedResponseHeaders>()
+                                {
+                                    propagated.responses.push((query_hash, headers));
+                                }
+                            }
+                        }
+                        response
+                    }
+                },
+            )
             .layer(HeadersLayer::new(
                 self.subgraph_operations
                     .get(name)
@@ -236,6 +455,147 @@ impl Plugin for Headers {
     }
 }
 
+impl Headers {
+    fn propagates_responses(&self) -> bool {
+        !self.all_response_operations.is_empty()
+            || self
+                .subgraph_response_operations
+                .values()
+                .any(|operations| !operations.is_empty())
+    }
+}
+
+/// Headers selected from the subgraph responses, stored in the context extensions until the
+/// client response is sent
+struct PropagatedResponseHeaders {
+    /// Hashes of the fetches of the query plan, in plan order
+    plan_order: Vec<Arc<QueryHash>>,
+    /// Headers selected from each subgraph response, in arrival order
+    responses: Vec<(Arc<QueryHash>, Vec<PropagatedHeader>)>,
+}
+
+struct PropagatedHeader {
+    name: HeaderName,
+    value: HeaderValue,
+    merge: Merge,
+}
+
+impl PropagatedResponseHeaders {
+    fn apply(mut self, headers: &mut HeaderMap) {
+        // the sort is stable, so responses of the same fetch keep their arrival order
+        let plan_order = self.plan_order;
+        self.responses.sort_by_key(|(query_hash, _)| {
+            plan_order
+                .iter()
+                .position(|hash| hash == query_hash)
+                .unwrap_or(usize::MAX)
+        });
+
+        // the merge strategy of a header is the one of the first rule propagating it
+        let mut merged: IndexMap<HeaderName, (Merge, Vec<HeaderValue>)> = IndexMap::new();
+        for header in self.responses.into_iter().flat_map(|(_, headers)| headers) {
+            merged
+                .entry(header.name)
+                .or_insert_with(|| (header.merge, Vec::new()))
+                .1
+                .push(header.value);
+        }
+
+        for (name, (merge, values)) in merged {
+            match merge {
+                Merge::First => {
+                    headers.insert(name, values[0].clone());
+                }
+                Merge::Last => {
+                    headers.insert(name, values[values.len() - 1].clone());
+                }
+                Merge::Append => {
+                    headers.remove(&name);
+                    for value in values {
+                        headers.append(&name, value);
+                    }
+                }
+                Merge::Join => {
+                    let joined = values
+                        .iter()
+                        .map(|value| value.as_bytes())
+                        .collect::<Vec<_>>()
+                        .join(&b", "[..]);
+                    match HeaderValue::from_bytes(&joined) {
+                        Ok(value) => {
+                            headers.insert(name, value);
+                        }
+                        Err(err) => {
+                            tracing::error!(
+                                "cannot join the values of the header '{}': {:?}",
+                                name,
+                                err
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn propagated_response_headers(
+    operations: &[ResponseOperation],
+    reserved_headers: &HashSet<&'static HeaderName>,
+    headers: &HeaderMap,
+) -> Vec<PropagatedHeader> {
+    let mut already_propagated: HashSet<&HeaderName> = HashSet::new();
+    let mut propagated = Vec::new();
+
+    for operation in operations {
+        match operation {
+            ResponseOperation::Propagate(ResponsePropagate::Named {
+                named,
+                rename,
+                merge,
+            }) => {
+                let name = rename.as_ref().unwrap_or(named);
+                // the reserved headers of the client response are set by the router
+                if reserved_headers.contains(named) || reserved_headers.contains(name) {
+                    continue;
+                }
+                if already_propagated.insert(named) {
+                    propagated.extend(headers.get_all(named).iter().map(|value| {
+                        PropagatedHeader {
+                            name: name.clone(),
+                            value: value.clone(),
+                            merge: *merge,
+                        }
+                    }));
+                }
+            }
+            ResponseOperation::Propagate(ResponsePropagate::Matching { matching, merge }) => {
+                let matched: HashSet<&HeaderName> = headers
+                    .keys()
+                    .filter(|name| {
+                        !reserved_headers.contains(*name)
+                            && !already_propagated.contains(*name)
+                            && matching.is_match(name.as_str())
+                    })
+                    .collect();
+                propagated.extend(
+                    headers
+                        .iter()
+                        .filter(|(name, _)| matched.contains(*name))
+                        .map(|(name, value)| PropagatedHeader {
+                            name: name.clone(),
+                            value: value.clone(),
+                            merge: *merge,
+                        }),
+                );
+                already_propagated.extend(matched);
+            }
+        }
+    }
+
+    propagated
+}
+
 struct HeadersLayer {
     operations: Arc<Vec<Operation>>,
     reserved_headers: Arc<HashSet<&'static HeaderName>>,
@@ -366,6 +726,20 @@ impl<S> HeadersService<S> {
                                 .insert(&from_body.name, default_val.clone());
                         }
                     }
+                    Insert::FromSelector(from_selector) => {
+                        if let Some(val) = from_selector.value(req) {
+                            match HeaderValue::from_str(&val) {
+                                Ok(header_value) => {
+                                    req.subgraph_request
+                                        .headers_mut()
+                                        .insert(&from_selector.name, header_value);
+                                }
+                                Err(err) => {
+                                    tracing::error!("cannot convert from the selector into a header value for header name '{}': {:?}", from_selector.name, err);
+                                }
+                            }
+                        }
+                    }
                 },
                 Operation::Remove(Remove::Named(name)) => {
                     req.subgraph_request.headers_mut().remove(name);
@@ -451,8 +825,10 @@ mod test {
     use tower::BoxError;
 
     use super::*;
+    use crate::context::OPERATION_NAME;
     use crate::graphql::Request;
     use crate::plugin::test::MockSubgraphService;
+    use crate::plugins::authentication::APOLLO_AUTHENTICATION_JWT_CLAIMS;
     use crate::plugins::headers::Config;
     use crate::plugins::headers::HeadersLayer;
     use crate::query_planner::fetch::OperationKind;
@@ -489,6 +865,39 @@ mod test {
         .unwrap();
     }
 
+    #[test]
+    fn test_insert_from_selector_config() {
+        serde_yaml::from_str::<Config>(
+            r#"
+        all:
+            request:
+            - insert:
+                name: "test"
+                from_selector:
+                    supergraph_query_variable: "id"
+                rewrite:
+                    pattern: "^user-(.*)$"
+                    replacement: "$1"
+        "#,
+        )
+        .unwrap();
+
+        assert!(serde_yaml::from_str::<Config>(
+            r#"
+        all:
+            request:
+            - insert:
+                name: "test"
+                from_selector:
+                    jwt_claim: "sub"
+                rewrite:
+                    pattern: "d.*["
+                    replacement: "$1"
+        "#,
+        )
+        .is_err());
+    }
+
     #[test]
     fn test_remove_config() {
         serde_yaml::from_str::<Config>(
@@ -568,6 +977,164 @@ mod test {
         .unwrap();
     }
 
+    #[test]
+    fn test_response_propagate_config() {
+        serde_yaml::from_str::<Config>(
+            r#"
+        all:
+            response:
+                - propagate:
+                    named: "set-cookie"
+        subgraphs:
+          products:
+            response:
+                - propagate:
+                    named: "x-first"
+                    rename: "x-renamed"
+                    merge: first
+                - propagate:
+                    matching: "^x-.*"
+                    merge: join
+        "#,
+        )
+        .unwrap();
+
+        assert!(serde_yaml::from_str::<Config>(
+            r#"
+        all:
+            response:
+                - propagate:
+                    named: "vary"
+                    merge: "concat"
+        "#,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_propagate_response_headers_in_plan_order() -> Result<(), BoxError> {
+        let operations = vec![
+            ResponseOperation::Propagate(ResponsePropagate::Named {
+                named: HeaderName::from_static("set-cookie"),
+                rename: None,
+                merge: Merge::Append,
+            }),
+            ResponseOperation::Propagate(ResponsePropagate::Named {
+                named: HeaderName::from_static("x-first"),
+                rename: None,
+                merge: Merge::First,
+            }),
+            ResponseOperation::Propagate(ResponsePropagate::Named {
+                named: HeaderName::from_static("x-last"),
+                rename: Some(HeaderName::from_static("x-renamed")),
+                merge: Merge::Last,
+            }),
+            ResponseOperation::Propagate(ResponsePropagate::Matching {
+                matching: Regex::from_str("^(vary|content-type)$")?,
+                merge: Merge::Join,
+            }),
+        ];
+        let reserved_headers: HashSet<&'static HeaderName> = RESERVED_HEADERS.iter().collect();
+
+        let first = Arc::new(QueryHash(vec![1]));
+        let second = Arc::new(QueryHash(vec![2]));
+        // the responses arrive in the reverse plan order
+        let propagated = PropagatedResponseHeaders {
+            plan_order: vec![first.clone(), second.clone()],
+            responses: vec![
+                (
+                    second,
+                    propagated_response_headers(
+                        &operations,
+                        &reserved_headers,
+                        &header_map(&[
+                            ("set-cookie", "b=2"),
+                            ("x-first", "second"),
+                            ("x-last", "second"),
+                            ("vary", "accept"),
+                            ("content-type", "text/plain"),
+                        ]),
+                    ),
+                ),
+                (
+                    first,
+                    propagated_response_headers(
+                        &operations,
+                        &reserved_headers,
+                        &header_map(&[
+                            ("set-cookie", "a=1"),
+                            ("x-first", "first"),
+                            ("x-last", "first"),
+                            ("vary", "origin"),
+                        ]),
+                    ),
+                ),
+            ],
+        };
+
+        let mut headers = header_map(&[
+            ("content-type", "application/json"),
+            ("x-renamed", "router"),
+        ]);
+        propagated.apply(&mut headers);
+        let headers = headers
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.to_str().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            headers,
+            vec![
+                ("content-type", "application/json"),
+                ("x-renamed", "second"),
+                ("set-cookie", "a=1"),
+                ("set-cookie", "b=2"),
+                ("x-first", "first"),
+                ("vary", "origin, accept"),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_propagate_response_headers_skips_reserved_headers() {
+        let operations = vec![
+            ResponseOperation::Propagate(ResponsePropagate::Named {
+                named: CONTENT_LENGTH,
+                rename: None,
+                merge: Merge::First,
+            }),
+            ResponseOperation::Propagate(ResponsePropagate::Named {
+                named: HeaderName::from_static("x-encoding"),
+                rename: Some(CONTENT_ENCODING),
+                merge: Merge::First,
+            }),
+            ResponseOperation::Propagate(ResponsePropagate::Named {
+                named: HeaderName::from_static("x-kept"),
+                rename: None,
+                merge: Merge::First,
+            }),
+        ];
+        let reserved_headers: HashSet<&'static HeaderName> = RESERVED_HEADERS.iter().collect();
+
+        let propagated = propagated_response_headers(
+            &operations,
+            &reserved_headers,
+            &header_map(&[
+                ("content-length", "12"),
+                ("x-encoding", "gzip"),
+                ("x-kept", "value"),
+            ]),
+        );
+        assert_eq!(
+            propagated
+                .iter()
+                .map(|header| (header.name.as_str(), header.value.to_str().unwrap()))
+                .collect::<Vec<_>>(),
+            vec![("x-kept", "value")]
+        );
+    }
+
     #[tokio::test]
     async fn test_insert_static() -> Result<(), BoxError> {
         let mut mock = MockSubgraphService::new();
@@ -650,6 +1217,62 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_insert_from_selector() -> Result<(), BoxError> {
+        let mut mock = MockSubgraphService::new();
+        mock.expect_call()
+            .times(1)
+            .withf(|request| {
+                request.assert_headers(vec![
+                    ("aa", "vaa"),
+                    ("ab", "vab"),
+                    ("ac", "vac"),
+                    ("x-operation", "my_operation_name"),
+                    ("x-tenant", "acme"),
+                ])
+            })
+            .returning(example_response);
+
+        let operations = serde_yaml::from_str::<Vec<Operation>>(
+            r#"
+            - insert:
+                name: "x-operation"
+                from_selector:
+                    supergraph_operation_name: string
+            - insert:
+                name: "x-tenant"
+                from_selector:
+                    jwt_claim: "iss"
+                rewrite:
+                    pattern: "^https://(.*)\\.example\\.com/$"
+                    replacement: "$1"
+            - insert:
+                name: "x-not-matching"
+                from_selector:
+                    jwt_claim: "sub"
+                rewrite:
+                    pattern: "^admin-(.*)$"
+                    replacement: "$1"
+            - insert:
+                name: "x-missing"
+                from_selector:
+                    supergraph_query_variable: "missing"
+            "#,
+        )?;
+        let mut service = HeadersLayer::new(Arc::new(operations)).layer(mock);
+
+        let request = example_request();
+        request
+            .context
+            .insert(OPERATION_NAME, "my_operation_name".to_string())?;
+        request.context.insert(
+            APOLLO_AUTHENTICATION_JWT_CLAIMS,
+            serde_json::json!({"iss": "https://acme.example.com/", "sub": "user-1"}),
+        )?;
+        service.ready().await?.call(request).await?;
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_remove_exact() -> Result<(), BoxError> {
         let mut mock = MockSubgraphService::new();
@@ -926,6 +1549,14 @@ mod test {
         Ok(())
     }
 
+    fn header_map(headers: &[(&'static str, &str)]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in headers {
+            map.append(*name, HeaderValue::from_str(value).unwrap());
+        }
+        map
+    }
+
     fn example_response(_: SubgraphRequest) -> Result<SubgraphResponse, BoxError> {
         Ok(SubgraphResponse::new_from_response(
             http::Response::default(),
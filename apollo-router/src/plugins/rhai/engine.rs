@@ -1,9 +1,16 @@
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::collections::HashSet;
 use std::path::PathBuf;
 use std::str::FromStr;
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::time::SystemTime;
 
+use apollo_compiler::executable::ExecutableDocument;
+use apollo_compiler::executable::Name;
+use apollo_compiler::executable::Selection;
+use apollo_compiler::executable::SelectionSet;
 use base64::prelude::BASE64_STANDARD;
 use base64::prelude::BASE64_STANDARD_NO_PAD;
 use base64::prelude::BASE64_URL_SAFE;
@@ -17,6 +24,11 @@ use http::uri::PathAndQuery;
 use http::HeaderMap;
 use http::Method;
 use http::Uri;
+use opentelemetry::metrics::Counter;
+use opentelemetry::metrics::Histogram;
+use opentelemetry::metrics::ObservableGauge;
+use opentelemetry::Key;
+use opentelemetry::KeyValue;
 use rhai::module_resolvers::FileModuleResolver;
 use rhai::plugin::*;
 use rhai::serde::from_dynamic;
@@ -43,9 +55,14 @@ use crate::configuration::expansion;
 use crate::graphql::Request;
 use crate::graphql::Response;
 use crate::http_ext;
+use crate::metrics::aggregation::RegisteredInstruments;
 use crate::plugins::authentication::APOLLO_AUTHENTICATION_JWT_CLAIMS;
 use crate::plugins::cache::entity::CONTEXT_CACHE_KEY;
 use crate::plugins::subscription::SUBSCRIPTION_WS_CUSTOM_CONNECTION_PARAMS;
+use crate::plugins::telemetry::dynamic_attribute::DynAttribute;
+use crate::plugins::telemetry::SUBGRAPH_SPAN_NAME;
+use crate::plugins::telemetry::SUPERGRAPH_SPAN_NAME;
+use crate::services::layers::query_analysis::ParsedDocument;
 use crate::Context;
 
 const CANNOT_ACCESS_HEADERS_ON_A_DEFERRED_RESPONSE: &str =
@@ -187,6 +204,354 @@ mod router_expansion {
     }
 }
 
+#[export_module]
+mod router_sha256 {
+    use sha2::Digest;
+
+    /// Returns the hex encoded SHA-256 digest of a string
+    #[rhai_fn(pure)]
+    pub(crate) fn digest(input: &mut ImmutableString) -> String {
+        hex::encode(sha2::Sha256::digest(input.as_bytes()))
+    }
+}
+
+#[export_module]
+mod router_hmac {
+    use hmac::Mac;
+
+    type HmacSha256 = hmac::Hmac<sha2::Sha256>;
+
+    /// Returns the hex encoded HMAC-SHA256 of a message
+    #[rhai_fn(return_raw)]
+    pub(crate) fn sha256(key: &str, message: &str) -> Result<String, Box<EvalAltResult>> {
+        let mut mac = HmacSha256::new_from_slice(key.as_bytes()).map_err(|e| e.to_string())?;
+        mac.update(message.as_bytes());
+        Ok(hex::encode(mac.finalize().into_bytes()))
+    }
+
+    /// Checks a hex encoded HMAC-SHA256 signature in constant time
+    #[rhai_fn(return_raw)]
+    pub(crate) fn verify_sha256(
+        key: &str,
+        message: &str,
+        signature: &str,
+    ) -> Result<bool, Box<EvalAltResult>> {
+        let Ok(signature) = hex::decode(signature) else {
+            return Ok(false);
+        };
+        let mut mac = HmacSha256::new_from_slice(key.as_bytes()).map_err(|e| e.to_string())?;
+        mac.update(message.as_bytes());
+        Ok(mac.verify_slice(&signature).is_ok())
+    }
+}
+
+#[export_module]
+mod router_uuid {
+    /// Returns a random (version 4) UUID
+    pub(crate) fn v4() -> String {
+        Uuid::new_v4().to_string()
+    }
+
+    /// Checks that a string is a UUID
+    pub(crate) fn is_valid(input: &str) -> bool {
+        Uuid::parse_str(input).is_ok()
+    }
+}
+
+#[export_module]
+mod router_time {
+    use ::time::format_description::well_known::Rfc3339;
+    use ::time::OffsetDateTime;
+
+    /// Returns the current Unix time in seconds
+    pub(crate) fn now() -> i64 {
+        OffsetDateTime::now_utc().unix_timestamp()
+    }
+
+    /// Returns the current Unix time in milliseconds
+    pub(crate) fn now_millis() -> i64 {
+        (OffsetDateTime::now_utc().unix_timestamp_nanos() / 1_000_000) as i64
+    }
+
+    /// Formats a Unix time in seconds as RFC 3339, in UTC
+    #[rhai_fn(return_raw)]
+    pub(crate) fn to_rfc3339(timestamp: i64) -> Result<String, Box<EvalAltResult>> {
+        OffsetDateTime::from_unix_timestamp(timestamp)
+            .map_err(|e| e.to_string())?
+            .format(&Rfc3339)
+            .map_err(|e| e.to_string().into())
+    }
+
+    /// Parses an RFC 3339 date into a Unix time in seconds
+    #[rhai_fn(return_raw)]
+    pub(crate) fn from_rfc3339(input: &str) -> Result<i64, Box<EvalAltResult>> {
+        OffsetDateTime::parse(input, &Rfc3339)
+            .map(|date| date.unix_timestamp())
+            .map_err(|e| e.to_string().into())
+    }
+}
+
+#[export_module]
+mod router_url {
+    /// Parses a URL into a map of its components. The query is also parsed into `query_params`.
+    #[rhai_fn(return_raw)]
+    pub(crate) fn parse(input: &str) -> Result<Map, Box<EvalAltResult>> {
+        let url = url::Url::parse(input).map_err(|e| e.to_string())?;
+        let mut map = Map::new();
+        map.insert("scheme".into(), url.scheme().to_string().into());
+        map.insert(
+            "host".into(),
+            url.host_str()
+                .map(|host| host.to_string().into())
+                .unwrap_or(Dynamic::UNIT),
+        );
+        map.insert(
+            "port".into(),
+            url.port_or_known_default()
+                .map(|port| Dynamic::from(port as i64))
+                .unwrap_or(Dynamic::UNIT),
+        );
+        map.insert("path".into(), url.path().to_string().into());
+        map.insert(
+            "query".into(),
+            url.query()
+                .map(|query| query.to_string().into())
+                .unwrap_or(Dynamic::UNIT),
+        );
+        map.insert(
+            "fragment".into(),
+            url.fragment()
+                .map(|fragment| fragment.to_string().into())
+                .unwrap_or(Dynamic::UNIT),
+        );
+        map.insert(
+            "query_params".into(),
+            query_pairs_to_map(url.query_pairs()).into(),
+        );
+        Ok(map)
+    }
+
+    /// Parses a query string into a map. If a key is repeated, the last value is kept.
+    pub(crate) fn parse_query(input: &str) -> Map {
+        query_pairs_to_map(url::form_urlencoded::parse(
+            input.trim_start_matches('?').as_bytes(),
+        ))
+    }
+
+    /// Builds a query string from a map, in the order of its keys
+    pub(crate) fn build_query(params: Map) -> String {
+        let mut serializer = url::form_urlencoded::Serializer::new(String::new());
+        for (key, value) in params {
+            serializer.append_pair(&key, &value.to_string());
+        }
+        serializer.finish()
+    }
+
+    /// Percent-encodes a string, for use in a URL path or query
+    #[rhai_fn(pure)]
+    pub(crate) fn encode(input: &mut ImmutableString) -> String {
+        urlencoding::encode(input).into_owned()
+    }
+
+    /// Decodes a percent-encoded string
+    #[rhai_fn(pure, return_raw)]
+    pub(crate) fn decode(input: &mut ImmutableString) -> Result<String, Box<EvalAltResult>> {
+        Ok(urlencoding::decode(input)
+            .map_err(|e| e.to_string())?
+            .into_owned())
+    }
+}
+
+fn query_pairs_to_map(pairs: url::form_urlencoded::Parse<'_>) -> Map {
+    pairs
+        .map(|(key, value)| (key.as_ref().into(), value.into_owned().into()))
+        .collect()
+}
+
+/// Creates the `metrics` module, which records OpenTelemetry metrics through the meter provider
+/// of the router
+fn router_metrics_module() -> Module {
+    let mut module = Module::new();
+
+    // The instruments are kept with the engine, they are dropped when the scripts are reloaded
+    let metrics = Arc::new(RhaiMetrics::default());
+    let counter = metrics.clone();
+    module.set_native_fn("counter", move |name: &str, value: Dynamic| {
+        counter.add_to_counter(name, value, Map::new())
+    });
+    let counter = metrics.clone();
+    module.set_native_fn(
+        "counter",
+        move |name: &str, value: Dynamic, attributes: Map| {
+            counter.add_to_counter(name, value, attributes)
+        },
+    );
+    let histogram = metrics.clone();
+    module.set_native_fn("histogram", move |name: &str, value: Dynamic| {
+        histogram.record_histogram(name, value, Map::new())
+    });
+    let histogram = metrics.clone();
+    module.set_native_fn(
+        "histogram",
+        move |name: &str, value: Dynamic, attributes: Map| {
+            histogram.record_histogram(name, value, attributes)
+        },
+    );
+    let gauge = metrics.clone();
+    module.set_native_fn("gauge", move |name: &str, value: Dynamic| {
+        gauge.set_gauge(name, value, Map::new())
+    });
+    module.set_native_fn(
+        "gauge",
+        move |name: &str, value: Dynamic, attributes: Map| {
+            metrics.set_gauge(name, value, attributes)
+        },
+    );
+    module
+}
+
+/// Metrics recorded by the scripts
+#[derive(Default)]
+struct RhaiMetrics {
+    counters: RegisteredInstruments<Counter<f64>>,
+    histograms: RegisteredInstruments<Histogram<f64>>,
+    gauges: RegisteredInstruments<ObservableGauge<f64>>,
+    /// The last value set for each gauge and set of attributes. OpenTelemetry only has
+    /// observable gauges, so the values are kept here and observed when the metrics are
+    /// collected.
+    gauge_values: Arc<Mutex<HashMap<String, Vec<(Vec<KeyValue>, f64)>>>>,
+}
+
+impl RhaiMetrics {
+    fn add_to_counter(
+        &self,
+        name: &str,
+        value: Dynamic,
+        attributes: Map,
+    ) -> Result<(), Box<EvalAltResult>> {
+        let value = metric_value(value)?;
+        if value < 0.0 {
+            return Err(format!("counter {name} cannot be decremented").into());
+        }
+        self.counters
+            .get_or_create(name, |provider| {
+                provider
+                    .meter("apollo/router")
+                    .f64_counter(name.to_string())
+                    .init()
+            })
+            .add(value, &to_key_values(attributes));
+        Ok(())
+    }
+
+    fn record_histogram(
+        &self,
+        name: &str,
+        value: Dynamic,
+        attributes: Map,
+    ) -> Result<(), Box<EvalAltResult>> {
+        let value = metric_value(value)?;
+        self.histograms
+            .get_or_create(name, |provider| {
+                provider
+                    .meter("apollo/router")
+                    .f64_histogram(name.to_string())
+                    .init()
+            })
+            .record(value, &to_key_values(attributes));
+        Ok(())
+    }
+
+    fn set_gauge(
+        &self,
+        name: &str,
+        value: Dynamic,
+        attributes: Map,
+    ) -> Result<(), Box<EvalAltResult>> {
+        let value = metric_value(value)?;
+        let attributes = to_key_values(attributes);
+        {
+            let mut values = self.gauge_values.lock().expect("lock poisoned");
+            let values = values.entry(name.to_string()).or_default();
+            match values
+                .iter_mut()
+                .find(|(existing, _)| *existing == attributes)
+            {
+                Some((_, existing)) => *existing = value,
+                None => values.push((attributes, value)),
+            }
+        }
+
+        // The meter provider keeps the gauge until the telemetry configuration changes, so the
+        // callback only holds a weak reference to the values: once the scripts are reloaded,
+        // the gauges of the previous engine stop reporting.
+        self.gauges.get_or_create(name, |provider| {
+            let values = Arc::downgrade(&self.gauge_values);
+            let gauge_name = name.to_string();
+            provider
+                .meter("apollo/router")
+                .f64_observable_gauge(name.to_string())
+                .with_callback(move |observer| {
+                    let Some(values) = values.upgrade() else {
+                        return;
+                    };
+                    if let Some(values) = values.lock().expect("lock poisoned").get(&gauge_name) {
+                        for (attributes, value) in values {
+                            observer.observe(*value, attributes);
+                        }
+                    }
+                })
+                .init()
+        });
+        Ok(())
+    }
+}
+
+fn metric_value(value: Dynamic) -> Result<f64, Box<EvalAltResult>> {
+    match value.as_int() {
+        Ok(value) => Ok(value as f64),
+        Err(_) => value.as_float().map_err(|type_name| {
+            format!("metric value must be a number, found: {type_name}").into()
+        }),
+    }
+}
+
+fn to_key_values(attributes: Map) -> Vec<KeyValue> {
+    attributes
+        .into_iter()
+        .map(|(key, value)| KeyValue::new(key.to_string(), to_otel_value(value)))
+        .collect()
+}
+
+fn to_otel_value(value: Dynamic) -> opentelemetry::Value {
+    if let Ok(value) = value.as_bool() {
+        value.into()
+    } else if let Ok(value) = value.as_int() {
+        value.into()
+    } else if let Ok(value) = value.as_float() {
+        value.into()
+    } else {
+        value.to_string().into()
+    }
+}
+
+/// The span of the supergraph or subgraph request being processed, available as `span` in
+/// the scripts
+#[derive(Clone, Debug)]
+pub(crate) struct RhaiSpan;
+
+impl RhaiSpan {
+    fn set_attribute(&mut self, key: &str, value: Dynamic) {
+        // The callbacks run in the span of the Rhai plugin, the attribute goes to the closest
+        // supergraph or subgraph span
+        tracing::Span::current().set_dyn_attribute_on_closest(
+            &[SUPERGRAPH_SPAN_NAME, SUBGRAPH_SPAN_NAME],
+            Key::from(key.to_string()),
+            to_otel_value(value),
+        );
+    }
+}
+
 #[export_module]
 mod router_method {
     pub(crate) type Method = http::Method;
@@ -1199,6 +1564,206 @@ mod router_plugin {
                 .unwrap_or_default()
         })
     }
+
+    // Add service usage getter to execution request
+    #[rhai_fn(get = "service_usage")]
+    pub(crate) fn execution_request_service_usage_get(
+        obj: &mut SharedMut<execution::Request>,
+    ) -> Array {
+        obj.with_mut(|request| {
+            let mut services: Vec<&str> = Vec::new();
+            for service in request.query_plan.root.service_usage() {
+                if !services.contains(&service) {
+                    services.push(service);
+                }
+            }
+            services
+                .into_iter()
+                .map(|service| service.to_string().into())
+                .collect()
+        })
+    }
+
+    // Add operation getter to supergraph and execution requests
+    #[rhai_fn(get = "operation")]
+    pub(crate) fn supergraph_request_operation_get(
+        obj: &mut SharedMut<supergraph::Request>,
+    ) -> Dynamic {
+        obj.with_mut(|request| {
+            operation_view(
+                &request.context,
+                request.supergraph_request.body().operation_name.as_deref(),
+            )
+        })
+    }
+
+    #[rhai_fn(get = "operation")]
+    pub(crate) fn execution_request_operation_get(
+        obj: &mut SharedMut<execution::Request>,
+    ) -> Dynamic {
+        obj.with_mut(|request| {
+            operation_view(
+                &request.context,
+                request.supergraph_request.body().operation_name.as_deref(),
+            )
+        })
+    }
+}
+
+/// A read-only view of the operation of a request, built from the document parsed by the router.
+///
+/// Returns `()` if the document could not be parsed or does not contain the operation.
+fn operation_view(context: &Context, operation_name: Option<&str>) -> Dynamic {
+    let document = match context.extensions().lock().get::<ParsedDocument>() {
+        Some(document) => document.clone(),
+        None => return Dynamic::UNIT,
+    };
+    let operation = match document.executable.get_operation(operation_name) {
+        Ok(operation) => operation,
+        Err(_) => return Dynamic::UNIT,
+    };
+
+    let mut root_fields = Vec::new();
+    collect_root_fields(
+        &document.executable,
+        &operation.selection_set,
+        &mut HashSet::new(),
+        &mut root_fields,
+    );
+    let mut fields = BTreeSet::new();
+    collect_field_coordinates(
+        &document.executable,
+        &operation.selection_set,
+        &mut HashSet::new(),
+        &mut fields,
+    );
+    let variables: Map = operation
+        .variables
+        .iter()
+        .map(|variable| {
+            (
+                variable.name.as_str().into(),
+                variable.ty.to_string().into(),
+            )
+        })
+        .collect();
+
+    let mut view = Map::new();
+    view.insert(
+        "kind".into(),
+        operation.operation_type.name().to_string().into(),
+    );
+    view.insert(
+        "name".into(),
+        operation
+            .name
+            .as_ref()
+            .map(|name| name.to_string().into())
+            .unwrap_or(Dynamic::UNIT),
+    );
+    view.insert(
+        "root_fields".into(),
+        root_fields
+            .into_iter()
+            .map(Dynamic::from)
+            .collect::<Array>()
+            .into(),
+    );
+    view.insert(
+        "fields".into(),
+        fields
+            .into_iter()
+            .map(Dynamic::from)
+            .collect::<Array>()
+            .into(),
+    );
+    view.insert("variables".into(), variables.into());
+    view.into()
+}
+
+/// Names of the fields selected at the root of the operation, in the order of the query
+fn collect_root_fields<'a>(
+    document: &'a ExecutableDocument,
+    selection_set: &'a SelectionSet,
+    visited_fragments: &mut HashSet<&'a Name>,
+    root_fields: &mut Vec<String>,
+) {
+    for selection in &selection_set.selections {
+        match selection {
+            Selection::Field(field) => {
+                let name = field.name.to_string();
+                if !root_fields.contains(&name) {
+                    root_fields.push(name);
+                }
+            }
+            Selection::InlineFragment(fragment) => {
+                collect_root_fields(
+                    document,
+                    &fragment.selection_set,
+                    visited_fragments,
+                    root_fields,
+                );
+            }
+            Selection::FragmentSpread(spread) => {
+                if let Some(fragment) = document.fragments.get(&spread.fragment_name) {
+                    if visited_fragments.insert(&spread.fragment_name) {
+                        collect_root_fields(
+                            document,
+                            &fragment.selection_set,
+                            visited_fragments,
+                            root_fields,
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Coordinates (`Type.field`) of all the fields selected by the operation
+fn collect_field_coordinates<'a>(
+    document: &'a ExecutableDocument,
+    selection_set: &'a SelectionSet,
+    visited_fragments: &mut HashSet<&'a Name>,
+    fields: &mut BTreeSet<String>,
+) {
+    for selection in &selection_set.selections {
+        match selection {
+            Selection::Field(field) => {
+                // introspection fields are not part of the schema
+                if !field.name.as_str().starts_with("__") {
+                    fields.insert(format!("{}.{}", selection_set.ty, field.name));
+                }
+                collect_field_coordinates(
+                    document,
+                    &field.selection_set,
+                    visited_fragments,
+                    fields,
+                );
+            }
+            Selection::InlineFragment(fragment) => {
+                collect_field_coordinates(
+                    document,
+                    &fragment.selection_set,
+                    visited_fragments,
+                    fields,
+                );
+            }
+            Selection::FragmentSpread(spread) => {
+                // The fields of a fragment are the same wherever it is spread
+                if let Some(fragment) = document.fragments.get(&spread.fragment_name) {
+                    if visited_fragments.insert(&spread.fragment_name) {
+                        collect_field_coordinates(
+                            document,
+                            &fragment.selection_set,
+                            visited_fragments,
+                            fields,
+                        );
+                    }
+                }
+            }
+        }
+    }
 }
 
 #[derive(Default)]
@@ -1607,7 +2172,12 @@ impl Rhai {
         Ok(())
     }
 
-    pub(super) fn new_rhai_engine(path: Option<PathBuf>, sdl: String, main: PathBuf) -> Engine {
+    pub(super) fn new_rhai_engine(
+        path: Option<PathBuf>,
+        sdl: String,
+        main: PathBuf,
+        config: Dynamic,
+    ) -> Engine {
         let mut engine = Engine::new();
         // If we pass in a path, use it to configure our engine
         // with a FileModuleResolver which allows import to work
@@ -1627,6 +2197,12 @@ impl Rhai {
         let json_module = exported_module!(router_json);
 
         let expansion_module = exported_module!(router_expansion);
+        let sha256_module = exported_module!(router_sha256);
+        let hmac_module = exported_module!(router_hmac);
+        let uuid_module = exported_module!(router_uuid);
+        let time_module = exported_module!(router_time);
+        let url_module = exported_module!(router_url);
+        let metrics_module = router_metrics_module();
 
         // Share main so we can move copies into each closure as required for logging
         let shared_main = Arc::new(main.display().to_string());
@@ -1654,6 +2230,17 @@ impl Rhai {
             // Register our expansion module (not global)
             // Hide the fact that it is an expansion module by calling it "env"
             .register_static_module("env", expansion_module.into())
+            // Register our hashing, uuid, time and url modules (not global)
+            .register_static_module("sha256", sha256_module.into())
+            .register_static_module("hmac", hmac_module.into())
+            .register_static_module("uuid", uuid_module.into())
+            .register_static_module("time", time_module.into())
+            .register_static_module("url", url_module.into())
+            // Register our metrics module (not global)
+            .register_static_module("metrics", metrics_module.into())
+            // Register the span of the current request, see the variable resolver below
+            .register_type_with_name::<RhaiSpan>("Span")
+            .register_fn("set_attribute", RhaiSpan::set_attribute)
             // Register HeaderMap as an iterator so we can loop over contents
             .register_iterator::<HeaderMap>()
             // Register a series of logging functions
@@ -1692,17 +2279,21 @@ impl Rhai {
             SUBSCRIPTION_WS_CUSTOM_CONNECTION_PARAMS.to_string().into(),
         );
         global_variables.insert("APOLLO_ENTITY_CACHE_KEY".into(), CONTEXT_CACHE_KEY.into());
+        // The configuration of this script, from the router configuration
+        global_variables.insert("CONFIG".into(), config);
 
         let shared_globals = Arc::new(global_variables);
 
         // Register a variable resolver.
         // Note: This API is NOT deprecated, but it is considered volatile and may change in the future.
         #[allow(deprecated)]
-        engine.on_var(move |name, _index, _context| {
+        engine.on_var(move |name, _index, context| {
             match name {
                 // Intercept attempts to find "Router" variables and return our "global variables"
                 // Note: Wrapped in an Arc to lighten the load of cloning.
                 "Router" => Ok(Some((*shared_globals).clone().into())),
+                // Scripts can use their own "span" variable
+                "span" if !context.scope().contains("span") => Ok(Some(Dynamic::from(RhaiSpan))),
                 // Return Ok(None) to continue with the normal variable resolution process.
                 _ => Ok(None),
             }
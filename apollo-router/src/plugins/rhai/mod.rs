@@ -1,5 +1,6 @@
 //! Customization via Rhai.
 
+use std::cell::Cell;
 use std::fmt;
 use std::ops::ControlFlow;
 use std::path::PathBuf;
@@ -40,6 +41,8 @@ use tower::ServiceExt;
 
 use self::engine::RhaiService;
 use self::engine::SharedMut;
+use self::store::Store;
+use self::store::StoreConf;
 use crate::error::Error;
 use crate::layers::ServiceBuilderExt;
 use crate::plugin::Plugin;
@@ -50,9 +53,12 @@ use crate::register_plugin;
 mod engine;
 
 pub(crate) const RHAI_SPAN_NAME: &str = "rhai_plugin";
+pub(crate) const APOLLO_RHAI: &str = "apollo.rhai";
 
 mod execution;
 mod router;
+pub(crate) mod runner;
+mod store;
 mod subgraph;
 mod supergraph;
 
@@ -67,12 +73,14 @@ impl EngineBlock {
         scripts: Option<PathBuf>,
         main: PathBuf,
         sdl: Arc<String>,
+        config: Dynamic,
+        limits: &Limits,
+        store: &Store,
     ) -> Result<Self, BoxError> {
-        let engine = Arc::new(Rhai::new_rhai_engine(
-            scripts,
-            sdl.to_string(),
-            main.clone(),
-        ));
+        let mut engine = Rhai::new_rhai_engine(scripts, sdl.to_string(), main.clone(), config);
+        limits.apply(&mut engine);
+        engine.register_static_module("store", store.module().into());
+        let engine = Arc::new(engine);
         let ast = engine
             .compile_file(main.clone())
             .map_err(|err| format!("in Rhai script {}: {}", main.display(), err))?;
@@ -101,10 +109,14 @@ impl EngineBlock {
 /// the engine block will be infrequent in relation to the accesses of it.
 /// We'd love to use AtomicArc if such a thing existed, but since it doesn't
 /// we'll use ArcSwap to accomplish our goal.
-struct Rhai {
+pub(crate) struct Rhai {
     block: Arc<ArcSwap<EngineBlock>>,
     park_flag: Arc<AtomicBool>,
     watcher_handle: Option<std::thread::JoinHandle<()>>,
+    /// The additional instances, executed in order after this one
+    instances: Vec<Rhai>,
+    /// The store shared by this instance and the additional ones
+    store: Store,
 }
 
 /// Configuration for the Rhai Plugin
@@ -115,6 +127,105 @@ pub(crate) struct Conf {
     scripts: Option<PathBuf>,
     /// The main entry point for Rhai script evaluation
     main: Option<String>,
+    /// Configuration passed to the script, available as `Router.CONFIG`
+    #[serde(default)]
+    config: serde_json::Map<String, serde_json::Value>,
+    /// Additional Rhai scripts, executed in order after the main script
+    #[serde(default)]
+    instances: Vec<InstanceConf>,
+    /// Limits of the execution of the scripts
+    #[serde(default)]
+    limits: Limits,
+    /// Key/value store shared by the scripts across requests
+    #[serde(default)]
+    store: StoreConf,
+}
+
+/// Configuration of an additional Rhai script
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct InstanceConf {
+    /// The directory where Rhai scripts can be found
+    scripts: Option<PathBuf>,
+    /// The main entry point for Rhai script evaluation
+    main: Option<String>,
+    /// Configuration passed to the script, available as `Router.CONFIG`
+    #[serde(default)]
+    config: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Limits of the execution of the scripts, applied to the main script and to the instances.
+/// Scripts going over a limit are stopped and the request fails with a `RHAI_LIMIT_EXCEEDED` error.
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields, default)]
+pub(crate) struct Limits {
+    /// Maximum number of operations of a callback, unlimited by default
+    max_operations: Option<u64>,
+    /// Maximum depth of function calls
+    max_call_levels: Option<usize>,
+    /// Maximum length of strings, unlimited by default
+    max_string_size: Option<usize>,
+    /// Maximum size of arrays, unlimited by default
+    max_array_size: Option<usize>,
+    /// Maximum size of object maps, unlimited by default
+    max_map_size: Option<usize>,
+    /// Maximum duration of a callback, unlimited by default
+    #[serde(deserialize_with = "humantime_serde::deserialize")]
+    #[schemars(with = "Option<String>")]
+    timeout: Option<Duration>,
+}
+
+impl Limits {
+    fn apply(&self, engine: &mut Engine) {
+        if let Some(max_operations) = self.max_operations {
+            engine.set_max_operations(max_operations);
+        }
+        if let Some(max_call_levels) = self.max_call_levels {
+            engine.set_max_call_levels(max_call_levels);
+        }
+        if let Some(max_string_size) = self.max_string_size {
+            engine.set_max_string_size(max_string_size);
+        }
+        if let Some(max_array_size) = self.max_array_size {
+            engine.set_max_array_size(max_array_size);
+        }
+        if let Some(max_map_size) = self.max_map_size {
+            engine.set_max_map_size(max_map_size);
+        }
+        if let Some(timeout) = self.timeout {
+            engine.on_progress(move |operations| {
+                // Reading the clock at each operation would slow down the scripts
+                if operations % 256 == 0 && timeout_expired(timeout) {
+                    Some(TIMEOUT_TOKEN.to_string().into())
+                } else {
+                    None
+                }
+            });
+        }
+    }
+}
+
+/// Value with which the scripts going over their timeout are terminated
+const TIMEOUT_TOKEN: &str = "timeout";
+
+/// Error code of the requests failing because a script went over one of its limits
+const RHAI_LIMIT_EXCEEDED: &str = "RHAI_LIMIT_EXCEEDED";
+
+thread_local! {
+    /// Start of the callback executing on this thread, for the timeout
+    static CALLBACK_START: Cell<Option<std::time::Instant>> = Cell::new(None);
+}
+
+fn timeout_expired(timeout: Duration) -> bool {
+    CALLBACK_START.with(|start| start.get().map_or(false, |start| start.elapsed() > timeout))
+}
+
+/// Runs a callback, with the timeout of the engine starting now
+fn with_timeout<T>(callback: impl FnOnce() -> T) -> T {
+    let previous = CALLBACK_START.with(|start| start.replace(Some(std::time::Instant::now())));
+    let result = callback();
+    CALLBACK_START.with(|start| start.set(previous));
+    result
 }
 
 #[async_trait::async_trait]
@@ -123,26 +234,177 @@ impl Plugin for Rhai {
 
     async fn new(init: PluginInit<Self::Config>) -> Result<Self, BoxError> {
         let sdl = init.supergraph_sdl.clone();
-        let scripts_path = match init.config.scripts {
+        let limits = init.config.limits;
+        let store = Store::new(init.config.store).await?;
+        let mut rhai = Rhai::new_instance(
+            init.config.scripts,
+            init.config.main,
+            init.config.config,
+            sdl.clone(),
+            &limits,
+            &store,
+        )?;
+        rhai.instances = init
+            .config
+            .instances
+            .into_iter()
+            .map(|instance| {
+                Rhai::new_instance(
+                    instance.scripts,
+                    instance.main,
+                    instance.config,
+                    sdl.clone(),
+                    &limits,
+                    &store,
+                )
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(rhai)
+    }
+
+    fn router_service(&self, service: router::BoxService) -> router::BoxService {
+        let service = self
+            .instances
+            .iter()
+            .rev()
+            .fold(service, |service, instance| {
+                instance.router_service(service)
+            });
+        const FUNCTION_NAME_SERVICE: &str = "router_service";
+        if !self.ast_has_function(FUNCTION_NAME_SERVICE) {
+            return service;
+        }
+        tracing::debug!("router_service function found");
+        let shared_service = Arc::new(Mutex::new(Some(service)));
+        if let Err(error) = self.run_rhai_service(
+            FUNCTION_NAME_SERVICE,
+            None,
+            ServiceStep::Router(shared_service.clone()),
+            self.block.load().scope.clone(),
+        ) {
+            tracing::error!("service callback failed: {error}");
+        }
+        shared_service.take_unwrap()
+    }
+
+    fn supergraph_service(&self, service: supergraph::BoxService) -> supergraph::BoxService {
+        let service = self
+            .instances
+            .iter()
+            .rev()
+            .fold(service, |service, instance| {
+                instance.supergraph_service(service)
+            });
+        const FUNCTION_NAME_SERVICE: &str = "supergraph_service";
+        if !self.ast_has_function(FUNCTION_NAME_SERVICE) {
+            return service;
+        }
+        tracing::debug!("supergraph_service function found");
+        let shared_service = Arc::new(Mutex::new(Some(service)));
+        if let Err(error) = self.run_rhai_service(
+            FUNCTION_NAME_SERVICE,
+            None,
+            ServiceStep::Supergraph(shared_service.clone()),
+            self.block.load().scope.clone(),
+        ) {
+            tracing::error!("service callback failed: {error}");
+        }
+        shared_service.take_unwrap()
+    }
+
+    fn execution_service(&self, service: execution::BoxService) -> execution::BoxService {
+        let service = self
+            .instances
+            .iter()
+            .rev()
+            .fold(service, |service, instance| {
+                instance.execution_service(service)
+            });
+        const FUNCTION_NAME_SERVICE: &str = "execution_service";
+        if !self.ast_has_function(FUNCTION_NAME_SERVICE) {
+            return service;
+        }
+        tracing::debug!("execution_service function found");
+        let shared_service = Arc::new(Mutex::new(Some(service)));
+        if let Err(error) = self.run_rhai_service(
+            FUNCTION_NAME_SERVICE,
+            None,
+            ServiceStep::Execution(shared_service.clone()),
+            self.block.load().scope.clone(),
+        ) {
+            tracing::error!("service callback failed: {error}");
+        }
+        shared_service.take_unwrap()
+    }
+
+    fn subgraph_service(&self, name: &str, service: subgraph::BoxService) -> subgraph::BoxService {
+        let service = self
+            .instances
+            .iter()
+            .rev()
+            .fold(service, |service, instance| {
+                instance.subgraph_service(name, service)
+            });
+        const FUNCTION_NAME_SERVICE: &str = "subgraph_service";
+        if !self.ast_has_function(FUNCTION_NAME_SERVICE) {
+            return service;
+        }
+        tracing::debug!("subgraph_service function found");
+        let shared_service = Arc::new(Mutex::new(Some(service)));
+        if let Err(error) = self.run_rhai_service(
+            FUNCTION_NAME_SERVICE,
+            Some(name),
+            ServiceStep::Subgraph(shared_service.clone()),
+            self.block.load().scope.clone(),
+        ) {
+            tracing::error!("service callback failed: {error}");
+        }
+        shared_service.take_unwrap()
+    }
+}
+
+impl Rhai {
+    /// Keeps the entries of the store used before a reload
+    pub(crate) fn carry_over_store(&self, previous: &Rhai) {
+        self.store.carry_over(&previous.store);
+    }
+
+    /// Loads a script and watches its directory to reload it when it changes
+    fn new_instance(
+        scripts: Option<PathBuf>,
+        main: Option<String>,
+        config: serde_json::Map<String, serde_json::Value>,
+        sdl: Arc<String>,
+        limits: &Limits,
+        store: &Store,
+    ) -> Result<Self, BoxError> {
+        let scripts_path = match scripts {
             Some(path) => path,
             None => "rhai".into(),
         };
 
-        let main_file = match init.config.main {
+        let main_file = match main {
             Some(main) => main,
             None => "main.rhai".to_string(),
         };
 
         let main = scripts_path.join(main_file);
+        let config = rhai::serde::to_dynamic(config)?;
 
         let watched_path = scripts_path.clone();
         let watched_main = main.clone();
         let watched_sdl = sdl.clone();
+        let watched_config = config.clone();
+        let watched_limits = limits.clone();
+        let watched_store = store.clone();
 
         let block = Arc::new(ArcSwap::from_pointee(EngineBlock::try_new(
             Some(scripts_path),
             main,
             sdl,
+            config,
+            limits,
+            store,
         )?));
         let watched_block = block.clone();
 
@@ -182,6 +444,9 @@ impl Plugin for Rhai {
                                         Some(watching_path.clone()),
                                         watched_main.clone(),
                                         watched_sdl.clone(),
+                                        watched_config.clone(),
+                                        &watched_limits,
+                                        &watched_store,
                                     ) {
                                         Ok(eb) => {
                                             tracing::info!("updating rhai execution engine");
@@ -219,80 +484,10 @@ impl Plugin for Rhai {
             block,
             park_flag,
             watcher_handle: Some(watcher_handle),
+            instances: Vec::new(),
+            store: store.clone(),
         })
     }
-
-    fn router_service(&self, service: router::BoxService) -> router::BoxService {
-        const FUNCTION_NAME_SERVICE: &str = "router_service";
-        if !self.ast_has_function(FUNCTION_NAME_SERVICE) {
-            return service;
-        }
-        tracing::debug!("router_service function found");
-        let shared_service = Arc::new(Mutex::new(Some(service)));
-        if let Err(error) = self.run_rhai_service(
-            FUNCTION_NAME_SERVICE,
-            None,
-            ServiceStep::Router(shared_service.clone()),
-            self.block.load().scope.clone(),
-        ) {
-            tracing::error!("service callback failed: {error}");
-        }
-        shared_service.take_unwrap()
-    }
-
-    fn supergraph_service(&self, service: supergraph::BoxService) -> supergraph::BoxService {
-        const FUNCTION_NAME_SERVICE: &str = "supergraph_service";
-        if !self.ast_has_function(FUNCTION_NAME_SERVICE) {
-            return service;
-        }
-        tracing::debug!("supergraph_service function found");
-        let shared_service = Arc::new(Mutex::new(Some(service)));
-        if let Err(error) = self.run_rhai_service(
-            FUNCTION_NAME_SERVICE,
-            None,
-            ServiceStep::Supergraph(shared_service.clone()),
-            self.block.load().scope.clone(),
-        ) {
-            tracing::error!("service callback failed: {error}");
-        }
-        shared_service.take_unwrap()
-    }
-
-    fn execution_service(&self, service: execution::BoxService) -> execution::BoxService {
-        const FUNCTION_NAME_SERVICE: &str = "execution_service";
-        if !self.ast_has_function(FUNCTION_NAME_SERVICE) {
-            return service;
-        }
-        tracing::debug!("execution_service function found");
-        let shared_service = Arc::new(Mutex::new(Some(service)));
-        if let Err(error) = self.run_rhai_service(
-            FUNCTION_NAME_SERVICE,
-            None,
-            ServiceStep::Execution(shared_service.clone()),
-            self.block.load().scope.clone(),
-        ) {
-            tracing::error!("service callback failed: {error}");
-        }
-        shared_service.take_unwrap()
-    }
-
-    fn subgraph_service(&self, name: &str, service: subgraph::BoxService) -> subgraph::BoxService {
-        const FUNCTION_NAME_SERVICE: &str = "subgraph_service";
-        if !self.ast_has_function(FUNCTION_NAME_SERVICE) {
-            return service;
-        }
-        tracing::debug!("subgraph_service function found");
-        let shared_service = Arc::new(Mutex::new(Some(service)));
-        if let Err(error) = self.run_rhai_service(
-            FUNCTION_NAME_SERVICE,
-            Some(name),
-            ServiceStep::Subgraph(shared_service.clone()),
-            self.block.load().scope.clone(),
-        ) {
-            tracing::error!("service callback failed: {error}");
-        }
-        shared_service.take_unwrap()
-    }
 }
 
 impl Drop for Rhai {
@@ -427,10 +622,7 @@ macro_rules! gen_map_router_deferred_request {
                                 if let Err(error) = result {
                                     tracing::error!("map_request callback failed: {error}");
                                     let error_details = process_error(error);
-                                    let error = Error {
-                                        message: error_details.message.unwrap_or_default(),
-                                        ..Default::default()
-                                    };
+                                    let error = graphql_error(error_details.message, error_details.code);
                                     // We don't have a structured response to work with here. Let's
                                     // throw away our response and custom build an error response
                                     let error_response = graphql::Response::builder()
@@ -561,10 +753,7 @@ macro_rules! gen_map_router_deferred_response {
                             if let Err(error) = result {
                                 tracing::error!("map_response callback failed: {error}");
                                 let error_details = process_error(error);
-                                let error = Error {
-                                    message: error_details.message.unwrap_or_default(),
-                                    ..Default::default()
-                                };
+                                let error = graphql_error(error_details.message, error_details.code);
                                 // We don't have a structured response to work with here. Let's
                                 // throw away our response and custom build an error response
                                 let error_response = graphql::Response::builder()
@@ -610,7 +799,8 @@ macro_rules! gen_map_deferred_response {
                             status: StatusCode::INTERNAL_SERVER_ERROR,
                             message: Some("rhai execution error: empty response".to_string()),
                             position: None,
-                            body: None
+                            body: None,
+                            code: None
                         };
                         return Ok($base::response_failure(
                             context,
@@ -671,10 +861,7 @@ macro_rules! gen_map_deferred_response {
                                 let mut guard = shared_response.lock().unwrap();
                                 let response_opt = guard.take();
                                 let $base::DeferredResponse { mut response, .. } = response_opt.unwrap();
-                                let error = Error {
-                                    message: error_details.message.unwrap_or_default(),
-                                    ..Default::default()
-                                };
+                                let error = graphql_error(error_details.message, error_details.code);
                                 response.errors = vec![error];
                                 return Some(response);
                             }
@@ -778,6 +965,8 @@ struct ErrorDetails {
     message: Option<String>,
     position: Option<Position>,
     body: Option<crate::graphql::Response>,
+    #[serde(skip)]
+    code: Option<String>,
 }
 
 fn default_thrown_status_code() -> StatusCode {
@@ -785,11 +974,30 @@ fn default_thrown_status_code() -> StatusCode {
 }
 
 fn process_error(error: Box<EvalAltResult>) -> ErrorDetails {
+    if let Some(limit) = exceeded_limit(&error) {
+        u64_counter!(
+            "apollo.router.rhai.limit_exceeded",
+            "Number of Rhai callbacks stopped because they went over a limit",
+            1,
+            limit = limit
+        );
+        return ErrorDetails {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            message: Some(format!(
+                "rhai execution error: script exceeded its {limit} limit"
+            )),
+            position: None,
+            body: None,
+            code: Some(RHAI_LIMIT_EXCEEDED.to_string()),
+        };
+    }
+
     let mut error_details = ErrorDetails {
         status: StatusCode::INTERNAL_SERVER_ERROR,
         message: Some(format!("rhai execution error: '{error}'")),
         position: None,
         body: None,
+        code: None,
     };
 
     // We only want to process errors raised in functions
@@ -810,19 +1018,56 @@ fn process_error(error: Box<EvalAltResult>) -> ErrorDetails {
     error_details
 }
 
+/// Returns the name of the limit which stopped a script, if any
+fn exceeded_limit(error: &EvalAltResult) -> Option<&'static str> {
+    match error {
+        EvalAltResult::ErrorInFunctionCall(_, _, inner, _)
+        | EvalAltResult::ErrorInModule(_, inner, _) => exceeded_limit(inner),
+        EvalAltResult::ErrorTooManyOperations(_) => Some("max_operations"),
+        EvalAltResult::ErrorStackOverflow(_) => Some("max_call_levels"),
+        EvalAltResult::ErrorDataTooLarge(data, _) => {
+            if data.starts_with("Length of string") {
+                Some("max_string_size")
+            } else if data.starts_with("Size of array") {
+                Some("max_array_size")
+            } else {
+                Some("max_map_size")
+            }
+        }
+        EvalAltResult::ErrorTerminated(token, _) if token.to_string() == TIMEOUT_TOKEN => {
+            Some("timeout")
+        }
+        _ => None,
+    }
+}
+
+/// The GraphQL error of a failed script
+fn graphql_error(message: Option<String>, code: Option<String>) -> Error {
+    let mut error = Error {
+        message: message.unwrap_or_default(),
+        ..Default::default()
+    };
+    if let Some(code) = code {
+        error.extensions.insert("code", code.into());
+    }
+    error
+}
+
 fn execute(
     rhai_service: &RhaiService,
     callback: &FnPtr,
     args: impl FuncArgs,
 ) -> Result<Dynamic, Box<EvalAltResult>> {
-    if callback.is_curried() {
-        callback.call(&rhai_service.engine, &rhai_service.ast, args)
-    } else {
-        let mut guard = rhai_service.scope.lock().unwrap();
-        rhai_service
-            .engine
-            .call_fn(&mut guard, &rhai_service.ast, callback.fn_name(), args)
-    }
+    with_timeout(|| {
+        if callback.is_curried() {
+            callback.call(&rhai_service.engine, &rhai_service.ast, args)
+        } else {
+            let mut guard = rhai_service.scope.lock().unwrap();
+            rhai_service
+                .engine
+                .call_fn(&mut guard, &rhai_service.ast, callback.fn_name(), args)
+        }
+    })
 }
 
 register_plugin!("apollo", "rhai", Rhai);
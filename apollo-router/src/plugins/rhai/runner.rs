@@ -0,0 +1,500 @@
+//! Offline runner of Rhai scripts against fixture files, used by `router rhai test`.
+//!
+//! A fixture file describes requests sent to a stage of the router, the response of the
+//! next service of that stage, and the expected results after the execution of the script.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use futures::future::ready;
+use futures::StreamExt;
+use http::HeaderMap;
+use http::HeaderName;
+use http::HeaderValue;
+use http::Method;
+use http::StatusCode;
+use serde::Deserialize;
+use serde_json::Value;
+use tower::BoxError;
+use tower::ServiceExt;
+
+use crate::graphql;
+use crate::notification::Notify;
+use crate::plugin::plugins;
+use crate::plugin::DynPlugin;
+use crate::services::execution;
+use crate::services::router;
+use crate::services::subgraph;
+use crate::services::supergraph;
+use crate::Context;
+
+/// A fixture file, with the configuration of the script and its test cases
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Fixture {
+    /// Configuration passed to the script, available as `Router.CONFIG`
+    #[serde(default)]
+    config: serde_json::Map<String, Value>,
+    tests: Vec<TestCase>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TestCase {
+    name: String,
+    service: Stage,
+    /// Name of the subgraph, for the subgraph stage
+    subgraph: Option<String>,
+    /// Request sent to the stage
+    #[serde(default)]
+    request: Message,
+    /// Response of the next service, if the script lets the request through
+    #[serde(default)]
+    response: Message,
+    #[serde(default)]
+    expect: Expectations,
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Stage {
+    Router,
+    Supergraph,
+    Execution,
+    Subgraph,
+}
+
+#[derive(Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+struct Message {
+    status: Option<u16>,
+    headers: HashMap<String, String>,
+    body: Option<Value>,
+    context: serde_json::Map<String, Value>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+struct Expectations {
+    /// Request received by the next service
+    request: Option<Expected>,
+    /// Response returned by the stage
+    response: Option<Expected>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+struct Expected {
+    status: Option<u16>,
+    /// Expected headers, a `null` value means that the header must be absent
+    headers: HashMap<String, Option<String>>,
+    body: Option<Value>,
+    /// Expected context entries, a `null` value means that the entry must be absent
+    context: serde_json::Map<String, Value>,
+    /// Expected messages of the GraphQL errors
+    errors: Option<Vec<String>>,
+}
+
+/// What the next service received, or what the stage returned
+struct Observed {
+    status: Option<StatusCode>,
+    headers: HeaderMap,
+    body: Value,
+    context: serde_json::Map<String, Value>,
+}
+
+impl Observed {
+    fn new(
+        status: Option<StatusCode>,
+        headers: &HeaderMap,
+        body: Value,
+        context: &Context,
+    ) -> Self {
+        Self {
+            status,
+            headers: headers.clone(),
+            body,
+            context: context
+                .iter()
+                .map(|entry| {
+                    (
+                        entry.key().clone(),
+                        serde_json::to_value(entry.value()).unwrap_or_default(),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns the differences with the expectations
+    fn check(&self, expected: &Expected, what: &str, failures: &mut Vec<String>) {
+        if let Some(status) = expected.status {
+            let actual = self.status.map(|status| status.as_u16());
+            if actual != Some(status) {
+                failures.push(format!(
+                    "{what} status: expected {status}, found {}",
+                    actual.map_or_else(|| "none".to_string(), |status| status.to_string())
+                ));
+            }
+        }
+        let mut headers: Vec<_> = expected.headers.iter().collect();
+        headers.sort();
+        for (name, value) in headers {
+            let actual = self
+                .headers
+                .get_all(name.as_str())
+                .iter()
+                .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned())
+                .collect::<Vec<_>>();
+            let actual = (!actual.is_empty()).then(|| actual.join(", "));
+            if &actual != value {
+                failures.push(format!(
+                    "{what} header {name}: expected {}, found {}",
+                    describe(value.as_deref()),
+                    describe(actual.as_deref())
+                ));
+            }
+        }
+        if let Some(body) = &expected.body {
+            if body != &self.body {
+                failures.push(format!("{what} body: expected {body}, found {}", self.body));
+            }
+        }
+        for (key, value) in &expected.context {
+            let actual = self.context.get(key).unwrap_or(&Value::Null);
+            if actual != value {
+                failures.push(format!(
+                    "{what} context entry {key}: expected {value}, found {actual}"
+                ));
+            }
+        }
+        if let Some(errors) = &expected.errors {
+            let actual = self
+                .body
+                .get("errors")
+                .and_then(Value::as_array)
+                .map(|errors| {
+                    errors
+                        .iter()
+                        .map(|error| error.get("message").cloned().unwrap_or_default())
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+            let errors = errors
+                .iter()
+                .map(|error| Value::String(error.clone()))
+                .collect::<Vec<_>>();
+            if actual != errors {
+                failures.push(format!(
+                    "{what} errors: expected {}, found {}",
+                    Value::Array(errors),
+                    Value::Array(actual)
+                ));
+            }
+        }
+    }
+}
+
+fn describe(value: Option<&str>) -> String {
+    value.map_or_else(|| "no value".to_string(), |value| format!("{value:?}"))
+}
+
+/// Outcome of a test case
+struct Run {
+    request: Option<Observed>,
+    response: Observed,
+}
+
+/// Results of the test cases of a set of fixture files
+#[derive(Debug, Default)]
+pub(crate) struct Summary {
+    pub(crate) passed: usize,
+    pub(crate) failed: usize,
+}
+
+/// Runs the test cases of fixture files against a Rhai script, printing their results
+pub(crate) async fn run_fixtures(
+    scripts: &Path,
+    main: &str,
+    supergraph: Option<&Path>,
+    fixtures: &[PathBuf],
+) -> Result<Summary, BoxError> {
+    let sdl = match supergraph {
+        Some(path) => std::fs::read_to_string(path)
+            .map_err(|e| format!("could not read {}: {e}", path.display()))?,
+        None => String::new(),
+    };
+    let sdl = Arc::new(sdl);
+    let mut summary = Summary::default();
+    for path in fixtures {
+        let fixture: Fixture = serde_yaml::from_str(
+            &std::fs::read_to_string(path)
+                .map_err(|e| format!("could not read {}: {e}", path.display()))?,
+        )
+        .map_err(|e| format!("invalid fixture {}: {e}", path.display()))?;
+
+        let configuration = serde_json::json!({
+            "scripts": scripts,
+            "main": main,
+            "config": fixture.config,
+        });
+        let plugin = plugins()
+            .find(|factory| factory.name == "apollo.rhai")
+            .expect("the Rhai plugin is always registered")
+            .create_instance(&configuration, sdl.clone(), Notify::default())
+            .await?;
+
+        for case in &fixture.tests {
+            let failures = match run(plugin.as_ref(), case).await {
+                Ok(outcome) => check(case, &outcome),
+                Err(error) => vec![error.to_string()],
+            };
+            if failures.is_empty() {
+                summary.passed += 1;
+                println!("test {} ... ok", case.name);
+            } else {
+                summary.failed += 1;
+                println!("test {} ... FAILED ({})", case.name, path.display());
+                for failure in failures {
+                    println!("    {failure}");
+                }
+            }
+        }
+    }
+    println!(
+        "\ntest result: {}. {} passed; {} failed",
+        if summary.failed == 0 { "ok" } else { "FAILED" },
+        summary.passed,
+        summary.failed
+    );
+    Ok(summary)
+}
+
+fn check(case: &TestCase, outcome: &Run) -> Vec<String> {
+    let mut failures = Vec::new();
+    if let Some(expected) = &case.expect.request {
+        match &outcome.request {
+            Some(request) => request.check(expected, "request", &mut failures),
+            None => failures.push("the request did not reach the next service".to_string()),
+        }
+    }
+    if let Some(expected) = &case.expect.response {
+        outcome.response.check(expected, "response", &mut failures);
+    }
+    failures
+}
+
+async fn run(plugin: &dyn DynPlugin, case: &TestCase) -> Result<Run, BoxError> {
+    let recorded = Arc::new(Mutex::new(None));
+    let next_response = case.response.clone();
+    let context = context(&case.request, Context::new())?;
+
+    let response = match case.service {
+        Stage::Router => {
+            let next = {
+                let recorded = recorded.clone();
+                router::BoxService::new(tower::service_fn(move |request: router::Request| {
+                    let recorded = recorded.clone();
+                    let next_response = next_response.clone();
+                    async move {
+                        let (parts, body) = request.router_request.into_parts();
+                        let body = json_body(hyper::body::to_bytes(body).await?.as_ref());
+                        *recorded.lock().expect("lock poisoned") =
+                            Some(Observed::new(None, &parts.headers, body, &request.context));
+                        let context = self::context(&next_response, request.context)?;
+                        Ok::<_, BoxError>(router::Response {
+                            response: http_response(&next_response, raw_body(&next_response)?)?,
+                            context,
+                        })
+                    }
+                }))
+            };
+            let request = router::Request {
+                router_request: http_request(&case.request, raw_body(&case.request)?)?,
+                context,
+            };
+            let response = plugin.router_service(next).oneshot(request).await?;
+            let (parts, body) = response.response.into_parts();
+            let body = json_body(hyper::body::to_bytes(body).await?.as_ref());
+            Observed::new(Some(parts.status), &parts.headers, body, &response.context)
+        }
+        Stage::Supergraph | Stage::Execution => {
+            let next = {
+                let recorded = recorded.clone();
+                move |request: &http::Request<graphql::Request>, context: Context| {
+                    *recorded.lock().expect("lock poisoned") = Some(Observed::new(
+                        None,
+                        request.headers(),
+                        serde_json::to_value(request.body()).unwrap_or_default(),
+                        &context,
+                    ));
+                    let context = self::context(&next_response, context)?;
+                    let (parts, body) =
+                        http_response(&next_response, graphql_response(&next_response)?)?
+                            .into_parts();
+                    Ok::<_, BoxError>(supergraph::Response::new_from_response(
+                        http::Response::from_parts(
+                            parts,
+                            futures::stream::once(ready(body)).boxed(),
+                        ),
+                        context,
+                    ))
+                }
+            };
+            let supergraph_request = http_request(&case.request, graphql_request(&case.request)?)?;
+            let mut response = if let Stage::Supergraph = case.service {
+                let next = supergraph::BoxService::new(tower::service_fn(
+                    move |request: supergraph::Request| {
+                        ready(next(&request.supergraph_request, request.context))
+                    },
+                ));
+                plugin
+                    .supergraph_service(next)
+                    .oneshot(supergraph::Request {
+                        supergraph_request,
+                        context,
+                    })
+                    .await?
+            } else {
+                let next = execution::BoxService::new(tower::service_fn(
+                    move |request: execution::Request| {
+                        ready(next(&request.supergraph_request, request.context))
+                    },
+                ));
+                plugin
+                    .execution_service(next)
+                    .oneshot(
+                        execution::Request::fake_builder()
+                            .supergraph_request(supergraph_request)
+                            .context(context)
+                            .build(),
+                    )
+                    .await?
+            };
+            // Only the primary response is checked
+            let body = response.next_response().await.unwrap_or_default();
+            Observed::new(
+                Some(response.response.status()),
+                response.response.headers(),
+                serde_json::to_value(body)?,
+                &response.context,
+            )
+        }
+        Stage::Subgraph => {
+            let name = case
+                .subgraph
+                .clone()
+                .ok_or("the subgraph stage requires a `subgraph` name")?;
+            let next = {
+                let recorded = recorded.clone();
+                subgraph::BoxService::new(tower::service_fn(move |request: subgraph::Request| {
+                    *recorded.lock().expect("lock poisoned") = Some(Observed::new(
+                        None,
+                        request.subgraph_request.headers(),
+                        serde_json::to_value(request.subgraph_request.body()).unwrap_or_default(),
+                        &request.context,
+                    ));
+                    ready(
+                        self::context(&next_response, request.context).and_then(|context| {
+                            Ok(subgraph::Response::new_from_response(
+                                http_response(&next_response, graphql_response(&next_response)?)?,
+                                context,
+                            ))
+                        }),
+                    )
+                }))
+            };
+            let request = subgraph::Request::fake_builder()
+                .subgraph_request(http_request(
+                    &case.request,
+                    graphql_request(&case.request)?,
+                )?)
+                .subgraph_name(name.clone())
+                .context(context)
+                .build();
+            let response = plugin
+                .subgraph_service(&name, next)
+                .oneshot(request)
+                .await?;
+            Observed::new(
+                Some(response.response.status()),
+                response.response.headers(),
+                serde_json::to_value(response.response.body())?,
+                &response.context,
+            )
+        }
+    };
+
+    let request = recorded.lock().expect("lock poisoned").take();
+    Ok(Run { request, response })
+}
+
+/// Adds the context entries of a message
+fn context(message: &Message, context: Context) -> Result<Context, BoxError> {
+    for (key, value) in &message.context {
+        context.insert(key.clone(), value.clone())?;
+    }
+    Ok(context)
+}
+
+fn header_map(message: &Message) -> Result<HeaderMap, BoxError> {
+    let mut headers = HeaderMap::new();
+    for (name, value) in &message.headers {
+        headers.append(
+            HeaderName::try_from(name.as_str())?,
+            HeaderValue::try_from(value.as_str())?,
+        );
+    }
+    Ok(headers)
+}
+
+fn http_request<T>(message: &Message, body: T) -> Result<http::Request<T>, BoxError> {
+    let mut request = http::Request::builder()
+        .method(Method::POST)
+        .uri("http://localhost/")
+        .body(body)?;
+    *request.headers_mut() = header_map(message)?;
+    Ok(request)
+}
+
+fn http_response<T>(message: &Message, body: T) -> Result<http::Response<T>, BoxError> {
+    let mut response = http::Response::builder()
+        .status(message.status.unwrap_or(200))
+        .body(body)?;
+    *response.headers_mut() = header_map(message)?;
+    Ok(response)
+}
+
+fn graphql_request(message: &Message) -> Result<graphql::Request, BoxError> {
+    Ok(serde_json::from_value(
+        message
+            .body
+            .clone()
+            .unwrap_or_else(|| Value::Object(Default::default())),
+    )?)
+}
+
+fn graphql_response(message: &Message) -> Result<graphql::Response, BoxError> {
+    Ok(serde_json::from_value(
+        message
+            .body
+            .clone()
+            .unwrap_or_else(|| Value::Object(Default::default())),
+    )?)
+}
+
+fn raw_body(message: &Message) -> Result<router::Body, BoxError> {
+    Ok(match &message.body {
+        Some(body) => router::Body::from(serde_json::to_vec(body)?),
+        None => router::Body::empty(),
+    })
+}
+
+/// Parses a body as JSON, or keeps it as a string
+fn json_body(body: &[u8]) -> Value {
+    serde_json::from_slice(body)
+        .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(body).into_owned()))
+}
@@ -0,0 +1,306 @@
+//! Key/value store shared by the Rhai scripts across requests.
+
+use std::future::Future;
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use lru::LruCache;
+use rhai::Dynamic;
+use rhai::EvalAltResult;
+use rhai::Module;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::runtime::Handle;
+use tower::BoxError;
+
+use crate::cache::redis::RedisCacheStorage;
+use crate::cache::redis::RedisKey;
+use crate::cache::redis::RedisValue;
+use crate::configuration::RedisCache;
+
+const DEFAULT_STORE_CAPACITY: NonZeroUsize = match NonZeroUsize::new(10_000) {
+    Some(v) => v,
+    None => unreachable!(),
+};
+
+/// Configuration of the key/value store shared by the scripts, available as the `store` module
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields, default)]
+pub(crate) struct StoreConf {
+    /// Maximum number of entries kept in memory, the least recently used entries are evicted first
+    capacity: NonZeroUsize,
+    /// Default TTL of the entries, they do not expire by default
+    #[serde(deserialize_with = "humantime_serde::deserialize")]
+    #[schemars(with = "Option<String>")]
+    ttl: Option<Duration>,
+    /// Keeps the entries in Redis instead of in memory, to share them between routers.
+    /// The `ttl` and `reset_ttl` options of this section are ignored in favor of the store's `ttl`
+    redis: Option<RedisCache>,
+}
+
+impl Default for StoreConf {
+    fn default() -> Self {
+        Self {
+            capacity: DEFAULT_STORE_CAPACITY,
+            ttl: None,
+            redis: None,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Entry {
+    value: Value,
+    expires_at: Option<Instant>,
+}
+
+impl Entry {
+    fn new(value: Value, ttl: Option<Duration>) -> Self {
+        Self {
+            value,
+            expires_at: ttl.map(|ttl| Instant::now() + ttl),
+        }
+    }
+
+    fn is_expired(&self) -> bool {
+        self.expires_at
+            .map_or(false, |expires_at| expires_at <= Instant::now())
+    }
+}
+
+#[derive(Clone)]
+enum Backend {
+    InMemory(Arc<Mutex<LruCache<String, Entry>>>),
+    Redis {
+        storage: RedisCacheStorage,
+        runtime: Handle,
+    },
+}
+
+/// Key/value store shared by the main script and the instances
+///
+/// Every operation is atomic: in memory they are executed under a lock, and in Redis
+/// they map to a single command or transaction.
+#[derive(Clone)]
+pub(crate) struct Store {
+    backend: Backend,
+    ttl: Option<Duration>,
+}
+
+impl Store {
+    pub(crate) async fn new(conf: StoreConf) -> Result<Self, BoxError> {
+        let backend = match conf.redis {
+            Some(redis) => {
+                let mut storage = RedisCacheStorage::new(redis).await?;
+                // The TTL is passed with each insertion, so that reads do not reset it
+                storage.set_ttl(None);
+                Backend::Redis {
+                    storage,
+                    runtime: Handle::current(),
+                }
+            }
+            None => Backend::InMemory(Arc::new(Mutex::new(LruCache::new(conf.capacity)))),
+        };
+        Ok(Self {
+            backend,
+            ttl: conf.ttl,
+        })
+    }
+
+    /// Keeps the entries of the in-memory store used before a reload. Redis entries outlive
+    /// the router, so there is nothing to carry over.
+    pub(crate) fn carry_over(&self, previous: &Store) {
+        if let (Backend::InMemory(entries), Backend::InMemory(previous)) =
+            (&self.backend, &previous.backend)
+        {
+            if Arc::ptr_eq(entries, previous) {
+                return;
+            }
+            let previous = previous.lock().expect("lock poisoned");
+            let mut entries = entries.lock().expect("lock poisoned");
+            // least recently used first, so that the most recently used entries are kept
+            // if the capacity shrank
+            for (key, entry) in previous.iter().rev() {
+                if !entry.is_expired() {
+                    entries.put(key.clone(), entry.clone());
+                }
+            }
+        }
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Option<Value> {
+        match &self.backend {
+            Backend::InMemory(entries) => {
+                let mut entries = entries.lock().expect("lock poisoned");
+                match entries.get(key) {
+                    Some(entry) if entry.is_expired() => {
+                        entries.pop(key);
+                        None
+                    }
+                    Some(entry) => Some(entry.value.clone()),
+                    None => None,
+                }
+            }
+            Backend::Redis { storage, runtime } => block_on(
+                runtime,
+                storage.get::<String, Value>(RedisKey(key.to_string())),
+            )
+            .map(|value| value.0),
+        }
+    }
+
+    pub(crate) fn set(&self, key: &str, value: Value, ttl: Option<Duration>) {
+        let ttl = ttl.or(self.ttl);
+        match &self.backend {
+            Backend::InMemory(entries) => {
+                entries
+                    .lock()
+                    .expect("lock poisoned")
+                    .put(key.to_string(), Entry::new(value, ttl));
+            }
+            Backend::Redis { storage, runtime } => block_on(
+                runtime,
+                storage.insert(RedisKey(key.to_string()), RedisValue(value), ttl),
+            ),
+        }
+    }
+
+    /// Increments the integer stored at a key, starting from 0 if the key is absent
+    ///
+    /// An absent key is created with the default TTL, an existing key keeps its expiration.
+    pub(crate) fn incr(&self, key: &str, amount: i64) -> Result<i64, String> {
+        match &self.backend {
+            Backend::InMemory(entries) => {
+                let mut entries = entries.lock().expect("lock poisoned");
+                let current = match entries.get(key) {
+                    Some(entry) if !entry.is_expired() => Some(entry),
+                    _ => None,
+                };
+                let (value, expires_at) = match current {
+                    Some(entry) => (
+                        entry
+                            .value
+                            .as_i64()
+                            .ok_or_else(|| format!("the value of {key} is not an integer"))?,
+                        entry.expires_at,
+                    ),
+                    None => (0, Entry::new(Value::Null, self.ttl).expires_at),
+                };
+                let value = value
+                    .checked_add(amount)
+                    .ok_or_else(|| format!("incrementing {key} would overflow"))?;
+                entries.put(
+                    key.to_string(),
+                    Entry {
+                        value: value.into(),
+                        expires_at,
+                    },
+                );
+                Ok(value)
+            }
+            Backend::Redis { storage, runtime } => block_on(
+                runtime,
+                storage.incr(RedisKey(key.to_string()), amount, self.ttl),
+            )
+            .map_err(|e| format!("could not increment {key}: {e}")),
+        }
+    }
+
+    /// Deletes a key, and returns whether it was present
+    pub(crate) fn delete(&self, key: &str) -> bool {
+        match &self.backend {
+            Backend::InMemory(entries) => entries
+                .lock()
+                .expect("lock poisoned")
+                .pop(key)
+                .map_or(false, |entry| !entry.is_expired()),
+            Backend::Redis { storage, runtime } => {
+                block_on(runtime, storage.delete(vec![RedisKey(key.to_string())]))
+                    .map_or(false, |deleted| deleted > 0)
+            }
+        }
+    }
+
+    /// Creates the `store` module of the scripts, with TTLs expressed in seconds
+    pub(crate) fn module(&self) -> Module {
+        let mut module = Module::new();
+
+        let store = self.clone();
+        module.set_native_fn(
+            "get",
+            move |key: &str| -> Result<Dynamic, Box<EvalAltResult>> {
+                store
+                    .get(key)
+                    .map_or(Ok(Dynamic::UNIT), rhai::serde::to_dynamic)
+            },
+        );
+
+        let store = self.clone();
+        module.set_native_fn(
+            "set",
+            move |key: &str, value: Dynamic| -> Result<(), Box<EvalAltResult>> {
+                store.set(key, rhai::serde::from_dynamic(&value)?, None);
+                Ok(())
+            },
+        );
+        let store = self.clone();
+        module.set_native_fn(
+            "set",
+            move |key: &str, value: Dynamic, ttl: i64| -> Result<(), Box<EvalAltResult>> {
+                store.set(
+                    key,
+                    rhai::serde::from_dynamic(&value)?,
+                    Some(ttl_secs(ttl)?),
+                );
+                Ok(())
+            },
+        );
+
+        let store = self.clone();
+        module.set_native_fn(
+            "incr",
+            move |key: &str| -> Result<i64, Box<EvalAltResult>> { Ok(store.incr(key, 1)?) },
+        );
+        let store = self.clone();
+        module.set_native_fn(
+            "incr",
+            move |key: &str, amount: i64| -> Result<i64, Box<EvalAltResult>> {
+                Ok(store.incr(key, amount)?)
+            },
+        );
+
+        let store = self.clone();
+        module.set_native_fn(
+            "delete",
+            move |key: &str| -> Result<bool, Box<EvalAltResult>> { Ok(store.delete(key)) },
+        );
+
+        module
+    }
+}
+
+fn ttl_secs(ttl: i64) -> Result<Duration, Box<EvalAltResult>> {
+    u64::try_from(ttl)
+        .ok()
+        .filter(|ttl| *ttl > 0)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("the TTL must be a positive number of seconds, found: {ttl}").into())
+}
+
+/// Waits for a Redis command from the synchronous execution of a script
+///
+/// Scripts run on the runtime's worker threads, so each call goes through `block_in_place`:
+/// the worker is blocked for the Redis round trip while its other tasks are moved to another
+/// worker. This is the cost of exposing a synchronous API to the scripts, and the reason the
+/// in-memory backend is the default.
+fn block_on<F: Future>(runtime: &Handle, future: F) -> F::Output {
+    if Handle::try_current().is_ok() {
+        tokio::task::block_in_place(|| runtime.block_on(future))
+    } else {
+        runtime.block_on(future)
+    }
+}
@@ -4,8 +4,8 @@ use std::ops::ControlFlow;
 
 use tower::BoxError;
 
+use super::graphql_error;
 use super::ErrorDetails;
-use crate::graphql::Error;
 pub(crate) use crate::services::supergraph::*;
 use crate::Context;
 
@@ -28,10 +28,10 @@ pub(super) fn request_failure(
             .build()?
     } else {
         Response::error_builder()
-            .errors(vec![Error {
-                message: error_details.message.unwrap_or_default(),
-                ..Default::default()
-            }])
+            .errors(vec![graphql_error(
+                error_details.message,
+                error_details.code,
+            )])
             .context(context)
             .status_code(error_details.status)
             .build()?
@@ -53,10 +53,10 @@ pub(super) fn response_failure(context: Context, error_details: ErrorDetails) ->
             .build()
     } else {
         Response::error_builder()
-            .errors(vec![Error {
-                message: error_details.message.unwrap_or_default(),
-                ..Default::default()
-            }])
+            .errors(vec![graphql_error(
+                error_details.message,
+                error_details.code,
+            )])
             .status_code(error_details.status)
             .context(context)
             .build()
@@ -1,29 +1,51 @@
 //! Rhai module tests.
 
+use std::io::Write;
+use std::path::Path;
 use std::str::FromStr;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::time::Duration;
 use std::time::SystemTime;
 
 use http::HeaderMap;
 use http::Method;
 use http::StatusCode;
+use opentelemetry::KeyValue;
+use rhai::Dynamic;
 use rhai::Engine;
 use rhai::EvalAltResult;
+use rhai::Map;
+use rhai::Scope;
+use serde_json::json;
 use serde_json::Value;
 use tower::util::BoxService;
 use tower::BoxError;
 use tower::Service;
 use tower::ServiceExt;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Registry;
 use uuid::Uuid;
 
+use super::exceeded_limit;
 use super::process_error;
+use super::runner::run_fixtures;
+use super::store::Store;
+use super::store::StoreConf;
 use super::subgraph;
+use super::with_timeout;
+use super::Limits;
 use super::PathBuf;
 use super::Rhai;
 use crate::graphql::Error;
 use crate::graphql::Request;
 use crate::http_ext;
+use crate::metrics::aggregation::MeterProviderType;
+use crate::metrics::collect_metrics;
+use crate::metrics::meter_provider;
+use crate::metrics::test_utils::MetricType;
+use crate::metrics::FutureMetricsExt;
 use crate::plugin::test::MockExecutionService;
 use crate::plugin::test::MockSupergraphService;
 use crate::plugin::DynPlugin;
@@ -31,10 +53,19 @@ use crate::plugins::rhai::engine::RhaiExecutionDeferredResponse;
 use crate::plugins::rhai::engine::RhaiExecutionResponse;
 use crate::plugins::rhai::engine::RhaiSupergraphDeferredResponse;
 use crate::plugins::rhai::engine::RhaiSupergraphResponse;
+use crate::plugins::telemetry::dynamic_attribute::DynAttributeLayer;
+use crate::plugins::telemetry::dynamic_attribute::LogAttributes;
+use crate::plugins::telemetry::SUPERGRAPH_SPAN_NAME;
+use crate::query_planner::PlanNode;
+use crate::query_planner::QueryPlan;
+use crate::services::layers::query_analysis::ParsedDocument;
 use crate::services::ExecutionRequest;
 use crate::services::SubgraphRequest;
 use crate::services::SupergraphRequest;
 use crate::services::SupergraphResponse;
+use crate::spec::Query;
+use crate::spec::Schema;
+use crate::Configuration;
 use crate::Context;
 
 #[tokio::test]
@@ -156,7 +187,7 @@ async fn rhai_plugin_execution_service_error() -> Result<(), BoxError> {
 // A Rhai engine suitable for minimal testing. There are no scripts and the SDL is an empty
 // string.
 fn new_rhai_test_engine() -> Engine {
-    Rhai::new_rhai_engine(None, "".to_string(), PathBuf::new())
+    Rhai::new_rhai_engine(None, "".to_string(), PathBuf::new(), Map::new().into())
 }
 
 // Some of these tests rely extensively on internal implementation details of the tracing_test crate.
@@ -584,6 +615,603 @@ fn it_can_generate_uuid() {
     assert_eq!(uuid_v4_rhai, uuid_parsed.to_string());
 }
 
+#[test]
+fn it_can_hash_sha256() {
+    let engine = new_rhai_test_engine();
+    let digest: String = engine
+        .eval(r#"sha256::digest("hello world")"#)
+        .expect("can hash string");
+    assert_eq!(
+        digest,
+        "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9"
+    );
+}
+
+#[test]
+fn it_can_sign_and_verify_hmac_sha256() {
+    let engine = new_rhai_test_engine();
+    let signature: String = engine
+        .eval(r#"hmac::sha256("key", "The quick brown fox jumps over the lazy dog")"#)
+        .expect("can sign message");
+    assert_eq!(
+        signature,
+        "f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+    );
+    let valid: bool = engine
+        .eval(
+            r#"hmac::verify_sha256("key", "The quick brown fox jumps over the lazy dog", "f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8")"#,
+        )
+        .expect("can verify signature");
+    assert!(valid);
+    let valid: bool = engine
+        .eval(r#"hmac::verify_sha256("key", "tampered", "f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8")"#)
+        .expect("can verify signature");
+    assert!(!valid);
+    let valid: bool = engine
+        .eval(r#"hmac::verify_sha256("key", "message", "not hex")"#)
+        .expect("can verify signature");
+    assert!(!valid);
+}
+
+#[test]
+fn it_can_generate_uuid_with_module() {
+    let engine = new_rhai_test_engine();
+    let uuid: String = engine.eval(r#"uuid::v4()"#).expect("can get uuid");
+    assert_eq!(
+        Uuid::parse_str(&uuid)
+            .expect("can parse uuid from string")
+            .get_version_num(),
+        4
+    );
+    let valid: bool = engine
+        .eval(r#"uuid::is_valid(uuid::v4())"#)
+        .expect("can validate uuid");
+    assert!(valid);
+    let valid: bool = engine
+        .eval(r#"uuid::is_valid("not a uuid")"#)
+        .expect("can validate uuid");
+    assert!(!valid);
+}
+
+#[test]
+fn it_can_use_time_module() {
+    let engine = new_rhai_test_engine();
+    let st = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .expect("can get system time")
+        .as_secs() as i64;
+    let now: i64 = engine.eval(r#"time::now()"#).expect("can get time");
+    assert!(st <= now && now <= st + 1);
+    let now_millis: i64 = engine.eval(r#"time::now_millis()"#).expect("can get time");
+    assert!(st * 1000 <= now_millis && now_millis <= (st + 2) * 1000);
+
+    let formatted: String = engine
+        .eval(r#"time::to_rfc3339(1700000000)"#)
+        .expect("can format time");
+    assert_eq!(formatted, "2023-11-14T22:13:20Z");
+    let parsed: i64 = engine
+        .eval(r#"time::from_rfc3339("2023-11-14T23:13:20+01:00")"#)
+        .expect("can parse time");
+    assert_eq!(parsed, 1700000000);
+    assert!(engine
+        .eval::<i64>(r#"time::from_rfc3339("yesterday")"#)
+        .is_err());
+}
+
+#[test]
+fn it_can_parse_url() {
+    let engine = new_rhai_test_engine();
+    let url: Map = engine
+        .eval(r#"url::parse("https://example.com/products/1?sort=price&page=2#reviews")"#)
+        .expect("can parse url");
+    assert_eq!(url["scheme"].clone().into_string().unwrap(), "https");
+    assert_eq!(url["host"].clone().into_string().unwrap(), "example.com");
+    assert_eq!(url["port"].as_int().unwrap(), 443);
+    assert_eq!(url["path"].clone().into_string().unwrap(), "/products/1");
+    assert_eq!(
+        url["query"].clone().into_string().unwrap(),
+        "sort=price&page=2"
+    );
+    assert_eq!(url["fragment"].clone().into_string().unwrap(), "reviews");
+    let page: String = engine
+        .eval(r#"url::parse("https://example.com/?sort=price&page=2").query_params.page"#)
+        .expect("can read query params");
+    assert_eq!(page, "2");
+    assert!(engine.eval::<Map>(r#"url::parse("not a url")"#).is_err());
+}
+
+#[test]
+fn it_can_manipulate_query_strings() {
+    let engine = new_rhai_test_engine();
+    let query: String = engine
+        .eval(
+            r#"
+            let params = url::parse_query("?b=1&a=hello%20world");
+            params.c = "x&y";
+            url::build_query(params)
+            "#,
+        )
+        .expect("can manipulate query string");
+    assert_eq!(query, "a=hello+world&b=1&c=x%26y");
+    let encoded: String = engine
+        .eval(r#"url::encode("a b/c")"#)
+        .expect("can encode string");
+    assert_eq!(encoded, "a%20b%2Fc");
+    let decoded: String = engine
+        .eval(r#"url::decode("a%20b%2Fc")"#)
+        .expect("can decode string");
+    assert_eq!(decoded, "a b/c");
+}
+
+#[test]
+fn it_can_read_the_operation() {
+    let configuration = Configuration::default();
+    let schema = Schema::parse(
+        include_str!("../../testdata/supergraph.graphql"),
+        &configuration,
+    )
+    .unwrap();
+    let query = "query TopProducts($first: Int, $withMe: Boolean!) {
+        topProducts(first: $first) { upc ...ProductName }
+        ... on Query { me @include(if: $withMe) { id } }
+    }
+    fragment ProductName on Product { name }";
+    let context = Context::new();
+    context
+        .extensions()
+        .lock()
+        .insert::<ParsedDocument>(Query::parse_document(query, &schema, &configuration));
+    let request = SupergraphRequest::fake_builder()
+        .query(query)
+        .operation_name("TopProducts")
+        .context(context)
+        .build()
+        .unwrap();
+
+    let engine = new_rhai_test_engine();
+    let mut scope = Scope::new();
+    scope.push("request", Arc::new(Mutex::new(Some(request))));
+    let operation: Map = engine
+        .eval_with_scope(&mut scope, "request.operation")
+        .expect("can read the operation");
+    assert_eq!(operation["kind"].clone().into_string().unwrap(), "query");
+    assert_eq!(
+        operation["name"].clone().into_string().unwrap(),
+        "TopProducts"
+    );
+    let to_strings = |value: &Dynamic| -> Vec<String> {
+        value
+            .clone()
+            .into_array()
+            .unwrap()
+            .into_iter()
+            .map(|value| value.into_string().unwrap())
+            .collect()
+    };
+    assert_eq!(to_strings(&operation["root_fields"]), ["topProducts", "me"]);
+    assert_eq!(
+        to_strings(&operation["fields"]),
+        [
+            "Product.name",
+            "Product.upc",
+            "Query.me",
+            "Query.topProducts",
+            "User.id"
+        ]
+    );
+    let variables = operation["variables"].clone().cast::<Map>();
+    assert_eq!(variables["first"].clone().into_string().unwrap(), "Int");
+    assert_eq!(
+        variables["withMe"].clone().into_string().unwrap(),
+        "Boolean!"
+    );
+
+    // Without a parsed document, there is no operation
+    let mut scope = Scope::new();
+    scope.push(
+        "request",
+        Arc::new(Mutex::new(Some(
+            SupergraphRequest::fake_builder().build().unwrap(),
+        ))),
+    );
+    assert!(engine
+        .eval_with_scope::<Dynamic>(&mut scope, "request.operation")
+        .unwrap()
+        .is_unit());
+}
+
+#[test]
+fn it_can_read_the_service_usage() {
+    let root: PlanNode = serde_json::from_value(serde_json::json!({
+        "kind": "Sequence",
+        "nodes": [
+            {
+                "kind": "Fetch",
+                "serviceName": "products",
+                "variableUsages": [],
+                "operation": "{topProducts{__typename upc}}",
+                "operationKind": "query"
+            },
+            {
+                "kind": "Fetch",
+                "serviceName": "reviews",
+                "variableUsages": [],
+                "operation": "{me{id}}",
+                "operationKind": "query"
+            },
+            {
+                "kind": "Fetch",
+                "serviceName": "products",
+                "variableUsages": [],
+                "operation": "{me{name}}",
+                "operationKind": "query"
+            }
+        ]
+    }))
+    .unwrap();
+    let request = ExecutionRequest::fake_builder()
+        .query_plan(QueryPlan::fake_builder().root(root).build())
+        .build();
+
+    let engine = new_rhai_test_engine();
+    let mut scope = Scope::new();
+    scope.push("request", Arc::new(Mutex::new(Some(request))));
+    let services: String = engine
+        .eval_with_scope(
+            &mut scope,
+            r#"request.service_usage.reduce(|acc, service| acc + service + ",", "")"#,
+        )
+        .expect("can read the service usage");
+    assert_eq!(services, "products,reviews,");
+}
+
+#[tokio::test]
+async fn it_can_record_metrics() {
+    async {
+        let engine = new_rhai_test_engine();
+        engine
+            .eval::<()>(
+                r#"
+                metrics::counter("rhai.tenant.rejected", 1, #{"tenant": "acme"});
+                metrics::counter("rhai.tenant.rejected", 2, #{"tenant": "acme"});
+                metrics::histogram("rhai.tenant.latency", 2.5);
+                metrics::gauge("rhai.tenant.quota", 10);
+                metrics::gauge("rhai.tenant.quota", 7);
+                "#,
+            )
+            .expect("can record metrics");
+        assert_counter!("rhai.tenant.rejected", 3.0, "tenant" = "acme");
+        assert_histogram!("rhai.tenant.latency", 2.5);
+        assert_gauge!("rhai.tenant.quota", 7.0);
+
+        assert!(engine
+            .eval::<()>(r#"metrics::counter("rhai.tenant.rejected", -1)"#)
+            .is_err());
+        assert!(engine
+            .eval::<()>(r#"metrics::histogram("rhai.tenant.latency", "slow")"#)
+            .is_err());
+    }
+    .with_metrics()
+    .await;
+}
+
+#[tokio::test]
+async fn it_recreates_metrics_after_reloads() {
+    async {
+        let engine = new_rhai_test_engine();
+        engine
+            .eval::<()>(
+                r#"
+                metrics::gauge("rhai.tenant.quota", 10, #{"tenant": "acme"});
+                metrics::counter("rhai.tenant.rejected", 1);
+                metrics::counter("rhai.tenant.rejected", 1);
+                "#,
+            )
+            .expect("can record metrics");
+        assert_gauge!("rhai.tenant.quota", 10.0, "tenant" = "acme");
+        assert_counter!("rhai.tenant.rejected", 2.0);
+        // the instruments are created once
+        assert_eq!(meter_provider().registered_instruments(), 2);
+
+        // a telemetry reload drops the instruments, they are created again on their next use
+        meter_provider().set(MeterProviderType::PublicPrometheus, None);
+        assert_eq!(meter_provider().registered_instruments(), 0);
+        engine
+            .eval::<()>(r#"metrics::gauge("rhai.tenant.quota", 7, #{"tenant": "acme"})"#)
+            .expect("can set a gauge");
+        assert_gauge!("rhai.tenant.quota", 7.0, "tenant" = "acme");
+        assert_eq!(meter_provider().registered_instruments(), 1);
+
+        // a script reload replaces the engine, the gauges of the previous one stop reporting
+        drop(engine);
+        let engine = new_rhai_test_engine();
+        engine
+            .eval::<()>(r#"metrics::gauge("rhai.tenant.quota", 3, #{"tenant": "other"})"#)
+            .expect("can set a gauge");
+        assert_gauge!("rhai.tenant.quota", 3.0, "tenant" = "other");
+        assert!(!collect_metrics().assert(
+            "rhai.tenant.quota",
+            MetricType::Gauge,
+            7.0,
+            &[KeyValue::new("tenant", "acme")]
+        ));
+    }
+    .with_metrics()
+    .await;
+}
+
+#[tokio::test]
+async fn it_can_share_state_through_the_store() {
+    let store = Store::new(StoreConf::default()).await.unwrap();
+    // Two engines sharing the same store, like the main script and an instance
+    let mut first = new_rhai_test_engine();
+    first.register_static_module("store", store.module().into());
+    let mut second = new_rhai_test_engine();
+    second.register_static_module("store", store.module().into());
+
+    first
+        .eval::<()>(r#"store::set("tenant", #{ name: "acme", plan: "pro" })"#)
+        .unwrap();
+    assert_eq!(
+        second
+            .eval::<String>(r#"store::get("tenant").plan"#)
+            .unwrap(),
+        "pro"
+    );
+    assert!(second
+        .eval::<Dynamic>(r#"store::get("missing")"#)
+        .unwrap()
+        .is_unit());
+
+    assert_eq!(first.eval::<i64>(r#"store::incr("hits")"#).unwrap(), 1);
+    assert_eq!(second.eval::<i64>(r#"store::incr("hits", 5)"#).unwrap(), 6);
+    assert_eq!(first.eval::<i64>(r#"store::get("hits")"#).unwrap(), 6);
+    assert!(first.eval::<i64>(r#"store::incr("tenant")"#).is_err());
+
+    assert!(first.eval::<bool>(r#"store::delete("hits")"#).unwrap());
+    assert!(!second.eval::<bool>(r#"store::delete("hits")"#).unwrap());
+    assert!(first.eval::<()>(r#"store::set("hits", 1, 0)"#).is_err());
+}
+
+#[tokio::test]
+async fn it_expires_store_entries() {
+    let store = Store::new(serde_json::from_value::<StoreConf>(json!({ "ttl": "50ms" })).unwrap())
+        .await
+        .unwrap();
+
+    store.set("default", json!(1), None);
+    store.set("longer", json!(2), Some(Duration::from_secs(60)));
+    assert_eq!(store.incr("counter", 1), Ok(1));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    assert_eq!(store.get("default"), None);
+    assert_eq!(store.get("longer"), Some(json!(2)));
+    // An expired counter starts again from 0
+    assert_eq!(store.incr("counter", 1), Ok(1));
+}
+
+#[tokio::test]
+async fn it_evicts_the_least_recently_used_store_entries() {
+    let store = Store::new(serde_json::from_value::<StoreConf>(json!({ "capacity": 2 })).unwrap())
+        .await
+        .unwrap();
+
+    store.set("a", json!("a"), None);
+    store.set("b", json!("b"), None);
+    assert_eq!(store.get("a"), Some(json!("a")));
+    store.set("c", json!("c"), None);
+
+    assert_eq!(store.get("b"), None);
+    assert_eq!(store.get("a"), Some(json!("a")));
+    assert_eq!(store.get("c"), Some(json!("c")));
+}
+
+#[tokio::test]
+async fn it_carries_over_store_entries() {
+    let previous = Store::new(StoreConf::default()).await.unwrap();
+    previous.set("kept", json!("kept"), None);
+    previous.set("expired", json!(1), Some(Duration::from_millis(10)));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let store = Store::new(StoreConf::default()).await.unwrap();
+    store.carry_over(&previous);
+
+    assert_eq!(store.get("kept"), Some(json!("kept")));
+    assert_eq!(store.get("expired"), None);
+    // the stores are independent after the reload
+    store.set("new", json!(1), None);
+    assert_eq!(previous.get("new"), None);
+}
+
+#[tokio::test]
+async fn it_runs_scripts_against_fixture_files() {
+    let summary = run_fixtures(
+        Path::new("tests/fixtures"),
+        "require_authentication.rhai",
+        None,
+        &[PathBuf::from("tests/fixtures/require_authentication.yaml")],
+    )
+    .await
+    .unwrap();
+    assert_eq!((summary.passed, summary.failed), (2, 0));
+
+    let mut fixture = tempfile::NamedTempFile::new().unwrap();
+    fixture
+        .write_all(
+            br#"
+tests:
+  - name: expects the wrong status
+    service: supergraph
+    expect:
+      response:
+        status: 200
+  - name: requires the name of the subgraph
+    service: subgraph
+"#,
+        )
+        .unwrap();
+    let summary = run_fixtures(
+        Path::new("tests/fixtures"),
+        "require_authentication.rhai",
+        None,
+        &[fixture.path().to_path_buf()],
+    )
+    .await
+    .unwrap();
+    assert_eq!((summary.passed, summary.failed), (0, 2));
+}
+
+#[tokio::test]
+async fn it_stops_scripts_going_over_limits() {
+    async {
+        let mut engine = new_rhai_test_engine();
+        Limits {
+            max_operations: Some(1000),
+            max_string_size: Some(10),
+            max_array_size: Some(10),
+            ..Default::default()
+        }
+        .apply(&mut engine);
+
+        let error = engine.eval::<()>("loop {}").unwrap_err();
+        assert_eq!(exceeded_limit(&error), Some("max_operations"));
+        let error = engine
+            .eval::<()>(r#"let s = "0123456789"; s += "a";"#)
+            .unwrap_err();
+        assert_eq!(exceeded_limit(&error), Some("max_string_size"));
+        let error = engine
+            .eval::<()>("let a = []; for i in 0..20 { a.push(i); }")
+            .unwrap_err();
+        assert_eq!(exceeded_limit(&error), Some("max_array_size"));
+
+        let error_details = process_error(error);
+        assert_eq!(error_details.status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(error_details.code.as_deref(), Some("RHAI_LIMIT_EXCEEDED"));
+        assert_counter!(
+            "apollo.router.rhai.limit_exceeded",
+            1,
+            "limit" = "max_array_size"
+        );
+
+        // Other errors are not limit violations
+        let error = engine.eval::<()>(r#"throw "failed""#).unwrap_err();
+        assert_eq!(exceeded_limit(&error), None);
+    }
+    .with_metrics()
+    .await;
+}
+
+#[test]
+fn it_stops_scripts_going_over_their_timeout() {
+    let mut engine = new_rhai_test_engine();
+    Limits {
+        timeout: Some(Duration::from_millis(10)),
+        ..Default::default()
+    }
+    .apply(&mut engine);
+
+    let error = with_timeout(|| engine.eval::<()>("loop {}")).unwrap_err();
+    assert_eq!(exceeded_limit(&error), Some("timeout"));
+    // Without a callback in progress, there is no timeout
+    assert!(engine.eval::<i64>("40 + 2").is_ok());
+}
+
+#[test]
+fn it_can_set_span_attributes() {
+    let subscriber = tracing_subscriber::registry().with(DynAttributeLayer::new());
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let engine = new_rhai_test_engine();
+    let supergraph_span = tracing::info_span!(SUPERGRAPH_SPAN_NAME);
+    {
+        let _supergraph = supergraph_span.enter();
+        // the callbacks run in the span of the plugin, nested in the supergraph span
+        let rhai_span = tracing::info_span!("rhai_plugin");
+        let _rhai = rhai_span.enter();
+        engine
+            .eval::<()>(r#"span.set_attribute("tenant", "acme")"#)
+            .expect("can set a span attribute");
+    }
+
+    let tenant = supergraph_span.with_subscriber(|(id, dispatch)| {
+        let registry = dispatch
+            .downcast_ref::<Registry>()
+            .expect("the subscriber is a registry");
+        let span = registry.span(id).expect("the span exists");
+        let extensions = span.extensions();
+        extensions
+            .get::<LogAttributes>()
+            .expect("the span has attributes")
+            .attributes()
+            .iter()
+            .find(|kv| kv.key.as_str() == "tenant")
+            .map(|kv| kv.value.as_str().into_owned())
+    });
+    assert_eq!(tenant, Some(Some("acme".to_string())));
+
+    // Scripts can still declare their own span variable
+    let span: i64 = engine
+        .eval("let span = 3; span")
+        .expect("can shadow the span variable");
+    assert_eq!(span, 3);
+}
+
+#[tokio::test]
+async fn it_runs_instances_in_order_with_their_config() -> Result<(), BoxError> {
+    let mut mock_service = MockSupergraphService::new();
+    mock_service
+        .expect_call()
+        .times(1)
+        .returning(move |req: SupergraphRequest| {
+            assert_eq!(
+                req.context.get::<_, Vec<String>>("order").unwrap(),
+                Some(vec![
+                    "first".to_string(),
+                    "second".to_string(),
+                    "third".to_string()
+                ])
+            );
+            Ok(SupergraphResponse::fake_builder()
+                .context(req.context)
+                .build()
+                .unwrap())
+        });
+
+    let dyn_plugin: Box<dyn DynPlugin> = crate::plugin::plugins()
+        .find(|factory| factory.name == "apollo.rhai")
+        .expect("Plugin not found")
+        .create_instance_without_schema(&serde_json::json!({
+            "scripts": "tests/fixtures",
+            "main": "config_test.rhai",
+            "config": { "name": "first" },
+            "instances": [
+                {
+                    "scripts": "tests/fixtures",
+                    "main": "config_test.rhai",
+                    "config": { "name": "second" }
+                },
+                {
+                    "scripts": "tests/fixtures",
+                    "main": "config_test.rhai",
+                    "config": { "name": "third" }
+                }
+            ]
+        }))
+        .await
+        .unwrap();
+
+    let mut supergraph_service = dyn_plugin.supergraph_service(BoxService::new(mock_service));
+    let supergraph_req = SupergraphRequest::fake_builder().build()?;
+    let service_response = supergraph_service
+        .ready()
+        .await?
+        .call(supergraph_req)
+        .await?;
+    assert_eq!(StatusCode::OK, service_response.response.status());
+
+    Ok(())
+}
+
 async fn base_globals_function(fn_name: &str) -> Result<bool, Box<rhai::EvalAltResult>> {
     let dyn_plugin: Box<dyn DynPlugin> = crate::plugin::plugins()
         .find(|factory| factory.name == "apollo.rhai")
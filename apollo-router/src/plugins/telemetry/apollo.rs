@@ -146,7 +146,7 @@ const fn client_name_header_default_str() -> &'static str {
     "apollographql-client-name"
 }
 
-const fn client_name_header_default() -> HeaderName {
+pub(crate) const fn client_name_header_default() -> HeaderName {
     HeaderName::from_static(client_name_header_default_str())
 }
 
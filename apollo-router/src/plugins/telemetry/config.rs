@@ -88,7 +88,6 @@ pub(crate) struct Instrumentation {
     pub(crate) events: config_new::events::Events,
     /// Span configuration
     pub(crate) spans: config_new::spans::Spans,
-    #[serde(skip)]
     /// Instrument configuration
     pub(crate) instruments: config_new::instruments::Instruments,
 }
@@ -103,6 +102,8 @@ pub(crate) struct Metrics {
     pub(crate) otlp: otlp::Config,
     /// Prometheus exporter configuration
     pub(crate) prometheus: metrics::prometheus::Config,
+    /// Field usage metrics configuration
+    pub(crate) field_usage: metrics::field_usage::Config,
 }
 
 #[derive(Clone, Debug, Deserialize, JsonSchema)]
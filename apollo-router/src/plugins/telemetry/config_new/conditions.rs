@@ -1,7 +1,11 @@
+use std::cmp::Ordering;
+
 use opentelemetry::Value;
+use regex::Regex;
 use schemars::JsonSchema;
 use serde::Deserialize;
 
+use crate::plugin::serde::deserialize_regex;
 use crate::plugins::telemetry::config::AttributeValue;
 use crate::plugins::telemetry::config_new::Selector;
 
@@ -11,6 +15,28 @@ use crate::plugins::telemetry::config_new::Selector;
 pub(crate) enum Condition<T> {
     /// A condition to check a selection against a value.
     Eq([SelectorOrValue<T>; 2]),
+    /// A condition to check that a selection is greater than a value. Both must be numbers.
+    Gt([SelectorOrValue<T>; 2]),
+    /// A condition to check that a selection is less than a value. Both must be numbers.
+    Lt([SelectorOrValue<T>; 2]),
+    /// A condition to check that a selection matches a regular expression.
+    Matches {
+        /// The selection to check.
+        value: SelectorOrValue<T>,
+        /// The regular expression. Values that are not strings are matched as strings.
+        #[schemars(with = "String")]
+        #[serde(deserialize_with = "deserialize_regex")]
+        pattern: Regex,
+    },
+    /// A condition to check that a selection is one of a set of values.
+    In {
+        /// The selection to check.
+        value: SelectorOrValue<T>,
+        /// The accepted values.
+        values: Vec<AttributeValue>,
+    },
+    /// A condition to check that a selector finds a value.
+    Exists(T),
     /// All sub-conditions must be true.
     All(Vec<Condition<T>>),
     /// At least one sub-conditions must be true.
@@ -20,8 +46,9 @@ pub(crate) enum Condition<T> {
 }
 
 impl Condition<()> {
+    /// A condition that is always true, as all of its (no) sub-conditions are.
     pub(crate) fn empty<T>() -> Condition<T> {
-        Condition::Any(vec![])
+        Condition::All(vec![])
     }
 }
 
@@ -52,11 +79,97 @@ where
                     .or_else(|| eq[1].on_response(response));
                 left == right
             }
+            Condition::Gt(gt) => {
+                compare(
+                    select(&gt[0], request, response),
+                    select(&gt[1], request, response),
+                ) == Some(Ordering::Greater)
+            }
+            Condition::Lt(lt) => {
+                compare(
+                    select(&lt[0], request, response),
+                    select(&lt[1], request, response),
+                ) == Some(Ordering::Less)
+            }
+            Condition::Matches { value, pattern } => {
+                is_match(pattern, select(value, request, response))
+            }
+            Condition::In { value, values } => is_in(values, select(value, request, response)),
+            Condition::Exists(selector) => select(selector, request, response).is_some(),
             Condition::All(all) => all.iter().all(|c| c.evaluate(request, response)),
             Condition::Any(any) => any.iter().any(|c| c.evaluate(request, response)),
             Condition::Not(not) => !not.evaluate(request, response),
         }
     }
+
+    /// Evaluates the condition against the request only.
+    ///
+    /// A selection that does not find a value is never equal to anything, so a missing header
+    /// or claim cannot satisfy an `eq` condition.
+    pub(crate) fn evaluate_request(&self, request: &T::Request) -> bool {
+        match self {
+            Condition::Eq(eq) => match (eq[0].on_request(request), eq[1].on_request(request)) {
+                (Some(left), Some(right)) => left == right,
+                _ => false,
+            },
+            Condition::Gt(gt) => {
+                compare(gt[0].on_request(request), gt[1].on_request(request))
+                    == Some(Ordering::Greater)
+            }
+            Condition::Lt(lt) => {
+                compare(lt[0].on_request(request), lt[1].on_request(request))
+                    == Some(Ordering::Less)
+            }
+            Condition::Matches { value, pattern } => is_match(pattern, value.on_request(request)),
+            Condition::In { value, values } => is_in(values, value.on_request(request)),
+            Condition::Exists(selector) => selector.on_request(request).is_some(),
+            Condition::All(all) => all.iter().all(|c| c.evaluate_request(request)),
+            Condition::Any(any) => any.iter().any(|c| c.evaluate_request(request)),
+            Condition::Not(not) => !not.evaluate_request(request),
+        }
+    }
+}
+
+/// We don't know if the selection was for the request or result, so we try both.
+fn select<S: Selector>(
+    selector: &S,
+    request: &S::Request,
+    response: &S::Response,
+) -> Option<Value> {
+    selector
+        .on_request(request)
+        .or_else(|| selector.on_response(response))
+}
+
+/// Compares two values as numbers. Strings are parsed, so that numeric headers can be compared.
+fn compare(left: Option<Value>, right: Option<Value>) -> Option<Ordering> {
+    as_number(&left?)?.partial_cmp(&as_number(&right?)?)
+}
+
+fn as_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::I64(value) => Some(*value as f64),
+        Value::F64(value) => Some(*value),
+        Value::String(value) => value.as_str().trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn is_match(pattern: &Regex, value: Option<Value>) -> bool {
+    match value {
+        Some(Value::String(value)) => pattern.is_match(value.as_str()),
+        Some(value) => pattern.is_match(&value.to_string()),
+        None => false,
+    }
+}
+
+fn is_in(values: &[AttributeValue], value: Option<Value>) -> bool {
+    match value {
+        Some(value) => values
+            .iter()
+            .any(|candidate| Value::from(candidate.clone()) == value),
+        None => false,
+    }
 }
 
 impl<T> Selector for SelectorOrValue<T>
@@ -84,7 +197,9 @@ where
 #[cfg(test)]
 mod test {
     use opentelemetry::Value;
+    use regex::Regex;
 
+    use crate::plugins::telemetry::config::AttributeValue;
     use crate::plugins::telemetry::config_new::conditions::Condition;
     use crate::plugins::telemetry::config_new::conditions::SelectorOrValue;
     use crate::plugins::telemetry::config_new::Selector;
@@ -153,6 +268,31 @@ mod test {
         .evaluate(&None, &None));
     }
 
+    #[test]
+    fn test_condition_evaluate_request() {
+        assert!(Condition::<TestSelector>::Eq([
+            SelectorOrValue::Selector(TestSelector),
+            SelectorOrValue::Value(1i64.into()),
+        ])
+        .evaluate_request(&Some(1i64)));
+        assert!(!Condition::<TestSelector>::Eq([
+            SelectorOrValue::Selector(TestSelector),
+            SelectorOrValue::Value(2i64.into()),
+        ])
+        .evaluate_request(&Some(1i64)));
+        // missing values never match
+        assert!(!Condition::<TestSelector>::Eq([
+            SelectorOrValue::Selector(TestSelector),
+            SelectorOrValue::Selector(TestSelector),
+        ])
+        .evaluate_request(&None));
+        assert!(Condition::<TestSelector>::Not(Box::new(Condition::Eq([
+            SelectorOrValue::Selector(TestSelector),
+            SelectorOrValue::Value(1i64.into()),
+        ])))
+        .evaluate_request(&None));
+    }
+
     #[test]
     fn test_condition_not() {
         assert!(Condition::<TestSelector>::Not(Box::new(Condition::Eq([
@@ -220,4 +360,113 @@ mod test {
         ])
         .evaluate(&None, &None));
     }
+
+    #[test]
+    fn test_condition_gt_lt() {
+        assert!(Condition::<TestSelector>::Gt([
+            SelectorOrValue::Selector(TestSelector),
+            SelectorOrValue::Value(1i64.into()),
+        ])
+        .evaluate(&Some(2i64), &None));
+        assert!(!Condition::<TestSelector>::Gt([
+            SelectorOrValue::Selector(TestSelector),
+            SelectorOrValue::Value(2i64.into()),
+        ])
+        .evaluate(&Some(2i64), &None));
+        assert!(Condition::<TestSelector>::Lt([
+            SelectorOrValue::Selector(TestSelector),
+            SelectorOrValue::Value(AttributeValue::F64(2.5)),
+        ])
+        .evaluate(&None, &Some(2i64)));
+        // numeric strings are compared as numbers
+        assert!(Condition::<TestSelector>::Gt([
+            SelectorOrValue::Value("10".into()),
+            SelectorOrValue::Value("9".into()),
+        ])
+        .evaluate(&None, &None));
+        // missing or non numeric values never match
+        assert!(!Condition::<TestSelector>::Lt([
+            SelectorOrValue::Selector(TestSelector),
+            SelectorOrValue::Value(1i64.into()),
+        ])
+        .evaluate(&None, &None));
+        assert!(!Condition::<TestSelector>::Gt([
+            SelectorOrValue::Value("abc".into()),
+            SelectorOrValue::Value(1i64.into()),
+        ])
+        .evaluate(&None, &None));
+        assert!(!Condition::<TestSelector>::Gt([
+            SelectorOrValue::Selector(TestSelector),
+            SelectorOrValue::Value(1i64.into()),
+        ])
+        .evaluate_request(&None));
+    }
+
+    #[test]
+    fn test_condition_matches() {
+        assert!(Condition::<TestSelector>::Matches {
+            value: SelectorOrValue::Value("application/json".into()),
+            pattern: Regex::new("^application/").unwrap(),
+        }
+        .evaluate(&None, &None));
+        assert!(!Condition::<TestSelector>::Matches {
+            value: SelectorOrValue::Value("text/html".into()),
+            pattern: Regex::new("^application/").unwrap(),
+        }
+        .evaluate(&None, &None));
+        // values that are not strings are matched as strings
+        assert!(Condition::<TestSelector>::Matches {
+            value: SelectorOrValue::Selector(TestSelector),
+            pattern: Regex::new("^5[0-9]{2}$").unwrap(),
+        }
+        .evaluate_request(&Some(503i64)));
+        assert!(!Condition::<TestSelector>::Matches {
+            value: SelectorOrValue::Selector(TestSelector),
+            pattern: Regex::new(".*").unwrap(),
+        }
+        .evaluate_request(&None));
+    }
+
+    #[test]
+    fn test_condition_in() {
+        let condition = Condition::<TestSelector>::In {
+            value: SelectorOrValue::Selector(TestSelector),
+            values: vec![1i64.into(), 2i64.into()],
+        };
+        assert!(condition.evaluate(&Some(1i64), &None));
+        assert!(condition.evaluate(&None, &Some(2i64)));
+        assert!(!condition.evaluate(&Some(3i64), &None));
+        assert!(!condition.evaluate(&None, &None));
+        assert!(condition.evaluate_request(&Some(2i64)));
+        assert!(!condition.evaluate_request(&None));
+    }
+
+    #[test]
+    fn test_condition_exists() {
+        let condition = Condition::<TestSelector>::Exists(TestSelector);
+        assert!(condition.evaluate(&Some(1i64), &None));
+        assert!(condition.evaluate(&None, &Some(1i64)));
+        assert!(!condition.evaluate(&None, &None));
+        assert!(condition.evaluate_request(&Some(1i64)));
+        assert!(!condition.evaluate_request(&None));
+    }
+
+    #[test]
+    fn test_condition_deserialize() {
+        let condition: Condition<AttributeValue> = serde_yaml::from_str(
+            r#"
+all:
+  - gt: [2, 1]
+  - matches:
+      value: "application/json"
+      pattern: "^application/"
+  - in:
+      value: 1
+      values: [1, 2]
+  - exists: foo
+"#,
+        )
+        .unwrap();
+        assert!(matches!(condition, Condition::All(all) if all.len() == 4));
+    }
 }
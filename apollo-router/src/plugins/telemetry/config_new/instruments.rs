@@ -1,8 +1,22 @@
+use std::collections::LinkedList;
 use std::fmt::Debug;
+use std::sync::Arc;
+use std::time::Instant;
 
+use opentelemetry::metrics::Counter;
+use opentelemetry::metrics::Histogram;
+use opentelemetry::metrics::Unit;
+use opentelemetry::metrics::UpDownCounter;
+use opentelemetry::Key;
+use opentelemetry::KeyValue;
 use schemars::JsonSchema;
 use serde::Deserialize;
+use serde_json_bytes::Value;
+use tower::BoxError;
 
+use crate::configuration::ConfigurationError;
+use crate::graphql;
+use crate::metrics::aggregation::RegisteredInstruments;
 use crate::plugins::telemetry::config_new::attributes::DefaultAttributeRequirementLevel;
 use crate::plugins::telemetry::config_new::attributes::RouterAttributes;
 use crate::plugins::telemetry::config_new::attributes::SubgraphAttributes;
@@ -12,8 +26,13 @@ use crate::plugins::telemetry::config_new::extendable::Extendable;
 use crate::plugins::telemetry::config_new::selectors::RouterSelector;
 use crate::plugins::telemetry::config_new::selectors::SubgraphSelector;
 use crate::plugins::telemetry::config_new::selectors::SupergraphSelector;
+use crate::plugins::telemetry::config_new::DefaultForLevel;
+use crate::plugins::telemetry::config_new::Selector;
+use crate::plugins::telemetry::config_new::Selectors;
+use crate::services::supergraph;
+
+const GRAPHQL_ERROR_CODE: Key = Key::from_static_str("graphql.error.code");
 
-#[allow(dead_code)]
 #[derive(Clone, Deserialize, JsonSchema, Debug, Default)]
 #[serde(deny_unknown_fields, default)]
 pub(crate) struct Instruments {
@@ -21,12 +40,281 @@ pub(crate) struct Instruments {
     default_attribute_requirement_level: DefaultAttributeRequirementLevel,
 
     /// Router service instruments. For more information see documentation on Router lifecycle.
+    #[allow(dead_code)]
+    #[serde(skip)]
     router: Extendable<RouterInstruments, Instrument<RouterAttributes, RouterSelector>>,
     /// Supergraph service instruments. For more information see documentation on Router lifecycle.
-    supergraph:
-        Extendable<SupergraphInstruments, Instrument<SupergraphAttributes, SupergraphSelector>>,
+    supergraph: Extendable<
+        SupergraphInstruments,
+        Instrument<SupergraphAttributes, SupergraphSelector, SupergraphValue>,
+    >,
     /// Subgraph service instruments. For more information see documentation on Router lifecycle.
+    #[allow(dead_code)]
+    #[serde(skip)]
     subgraph: Extendable<SubgraphInstruments, Instrument<SubgraphAttributes, SubgraphSelector>>,
+
+    /// The metrics of the custom supergraph instruments, created on their first use rather than
+    /// on each request
+    #[serde(skip)]
+    supergraph_metrics: Arc<CustomMetrics>,
+}
+
+impl Instruments {
+    /// Update the defaults for instruments configuration regarding the `default_attribute_requirement_level`
+    pub(crate) fn update_defaults(&mut self) {
+        self.supergraph
+            .defaults_for_levels(self.default_attribute_requirement_level);
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), ConfigurationError> {
+        for (name, instrument) in &self.supergraph.custom {
+            match (&instrument.ty, &instrument.value) {
+                (InstrumentType::Gauge, _) => {
+                    return Err(ConfigurationError::InvalidConfiguration {
+                        message: "supergraph instruments configuration is invalid",
+                        error: format!("instrument '{name}' is a gauge, which is not supported by supergraph instruments"),
+                    });
+                }
+                (
+                    InstrumentType::Counter | InstrumentType::Histogram,
+                    SupergraphValue::Standard(Standard::Active),
+                ) => {
+                    return Err(ConfigurationError::InvalidConfiguration {
+                        message: "supergraph instruments configuration is invalid",
+                        error: format!("instrument '{name}' has the value 'active', which requires the 'up_down_counter' type"),
+                    });
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Starts measuring a supergraph request.
+    ///
+    /// The request attributes and the conditions of the custom instruments are evaluated on the
+    /// request, the values are recorded when the response is received.
+    pub(crate) fn on_supergraph_request(
+        &self,
+        request: &supergraph::Request,
+    ) -> SupergraphRequestInstruments {
+        let standard = &self.supergraph.attributes;
+        let custom = self
+            .supergraph
+            .custom
+            .iter()
+            .filter(|(_, instrument)| instrument.condition.evaluate_request(request))
+            .map(|(name, instrument)| {
+                let attributes = instrument.attributes.on_request(request);
+                let value = match &instrument.value {
+                    SupergraphValue::Standard(Standard::Active) => {
+                        let attributes: Vec<KeyValue> = attributes.iter().cloned().collect();
+                        instrument
+                            .metric(name, &self.supergraph_metrics)
+                            .record(1.0, &attributes);
+                        None
+                    }
+                    SupergraphValue::Custom(selector) => {
+                        selector.on_request(request).and_then(to_f64)
+                    }
+                    _ => None,
+                };
+                CustomRequestInstrument {
+                    name: name.clone(),
+                    attributes,
+                    value,
+                }
+            })
+            .collect();
+
+        SupergraphRequestInstruments {
+            start: Instant::now(),
+            operation_duration: standard.graphql_operation_duration.on_request(request),
+            errors: standard.graphql_errors.on_request(request),
+            custom,
+        }
+    }
+
+    /// Records the instruments measured on the supergraph response, or on the error if the request
+    /// failed, and returns the instruments measured on each GraphQL response sent to the client.
+    pub(crate) fn on_supergraph_response(
+        &self,
+        request: SupergraphRequestInstruments,
+        response: Result<&supergraph::Response, &BoxError>,
+    ) -> GraphQLInstruments {
+        let response_attributes =
+            |attributes: &Extendable<SupergraphAttributes, SupergraphSelector>| match response {
+                Ok(response) => attributes.on_response(response),
+                Err(error) => attributes.on_error(error),
+            };
+        let duration = request.start.elapsed().as_secs_f64();
+        let standard = &self.supergraph.attributes;
+
+        if let Some(mut attributes) = request.operation_duration {
+            if let Some(extendable) = standard.graphql_operation_duration.attributes() {
+                attributes.extend(response_attributes(extendable));
+            }
+            let attributes: Vec<KeyValue> = attributes.into_iter().collect();
+            f64_histogram!(
+                "graphql.operation.duration",
+                "Duration of GraphQL operations",
+                duration,
+                attributes
+            );
+        }
+
+        let errors = request.errors.map(|mut attributes| {
+            if let Some(extendable) = standard.graphql_errors.attributes() {
+                attributes.extend(response_attributes(extendable));
+            }
+            attributes.into_iter().collect()
+        });
+
+        let mut custom = Vec::new();
+        for request_instrument in request.custom {
+            let Some(instrument) = self.supergraph.custom.get(&request_instrument.name) else {
+                continue;
+            };
+            let metric = instrument.metric(&request_instrument.name, &self.supergraph_metrics);
+            let mut attributes = request_instrument.attributes;
+            if let SupergraphValue::Standard(Standard::Active) = &instrument.value {
+                // The request is no longer active, with the same attributes it was counted with
+                let attributes: Vec<KeyValue> = attributes.into_iter().collect();
+                metric.record(-1.0, &attributes);
+                continue;
+            }
+            attributes.extend(response_attributes(&instrument.attributes));
+            let attributes: Vec<KeyValue> = attributes.into_iter().collect();
+            match &instrument.value {
+                SupergraphValue::Standard(Standard::Duration) => {
+                    metric.record(duration, &attributes)
+                }
+                SupergraphValue::Standard(Standard::Unit) => metric.record(1.0, &attributes),
+                SupergraphValue::Standard(Standard::Active) => {}
+                SupergraphValue::GraphQL(value) => custom.push(GraphQLInstrument {
+                    metric,
+                    value: value.clone(),
+                    attributes,
+                }),
+                SupergraphValue::Custom(selector) => {
+                    let value = request_instrument.value.or_else(|| {
+                        response
+                            .ok()
+                            .and_then(|response| selector.on_response(response))
+                            .and_then(to_f64)
+                    });
+                    if let Some(value) = value {
+                        metric.record(value, &attributes);
+                    }
+                }
+            }
+        }
+
+        GraphQLInstruments { errors, custom }
+    }
+}
+
+/// The supergraph instruments of a request, with the attributes and values found on the request.
+pub(crate) struct SupergraphRequestInstruments {
+    start: Instant,
+    operation_duration: Option<LinkedList<KeyValue>>,
+    errors: Option<LinkedList<KeyValue>>,
+    custom: Vec<CustomRequestInstrument>,
+}
+
+struct CustomRequestInstrument {
+    name: String,
+    attributes: LinkedList<KeyValue>,
+    value: Option<f64>,
+}
+
+/// The instruments measured on each GraphQL response sent to the client, which is several
+/// responses for deferred queries and subscriptions.
+pub(crate) struct GraphQLInstruments {
+    errors: Option<Vec<KeyValue>>,
+    custom: Vec<GraphQLInstrument>,
+}
+
+struct GraphQLInstrument {
+    metric: Metric,
+    value: GraphQLValue,
+    attributes: Vec<KeyValue>,
+}
+
+impl GraphQLInstruments {
+    pub(crate) fn on_graphql_response(&self, response: &graphql::Response) {
+        if let Some(attributes) = &self.errors {
+            let errors = response.errors.iter().chain(
+                response
+                    .incremental
+                    .iter()
+                    .flat_map(|incremental| incremental.errors.iter()),
+            );
+            for error in errors {
+                let mut attributes = attributes.clone();
+                if let Some(code) = error.extensions.get("code").and_then(|code| code.as_str()) {
+                    attributes.push(KeyValue::new(GRAPHQL_ERROR_CODE, code.to_string()));
+                }
+                u64_counter!(
+                    "graphql.errors",
+                    "Number of GraphQL errors sent to clients",
+                    1,
+                    attributes
+                );
+            }
+        }
+
+        for instrument in &self.custom {
+            if let Some(value) = instrument.value.on_graphql_response(response) {
+                instrument
+                    .metric
+                    .record(value as f64, &instrument.attributes);
+            }
+        }
+    }
+}
+
+/// The metrics of custom instruments, by instrument name.
+///
+/// They are registered with the meter provider, so they are created again after the meter
+/// providers change.
+#[derive(Default)]
+struct CustomMetrics {
+    counters: RegisteredInstruments<Counter<f64>>,
+    up_down_counters: RegisteredInstruments<UpDownCounter<f64>>,
+    histograms: RegisteredInstruments<Histogram<f64>>,
+}
+
+impl Debug for CustomMetrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CustomMetrics").finish_non_exhaustive()
+    }
+}
+
+/// The metric of a custom instrument
+enum Metric {
+    Counter(Arc<Counter<f64>>),
+    UpDownCounter(Arc<UpDownCounter<f64>>),
+    Histogram(Arc<Histogram<f64>>),
+}
+
+impl Metric {
+    fn record(&self, value: f64, attributes: &[KeyValue]) {
+        match self {
+            Metric::Counter(counter) => counter.add(value, attributes),
+            Metric::UpDownCounter(counter) => counter.add(value, attributes),
+            Metric::Histogram(histogram) => histogram.record(value, attributes),
+        }
+    }
+}
+
+fn to_f64(value: opentelemetry::Value) -> Option<f64> {
+    match value {
+        opentelemetry::Value::I64(value) => Some(value as f64),
+        opentelemetry::Value::F64(value) => Some(value),
+        opentelemetry::Value::String(value) => value.as_str().trim().parse().ok(),
+        _ => None,
+    }
 }
 
 #[allow(dead_code)]
@@ -54,7 +342,6 @@ struct RouterInstruments {
         DefaultedStandardInstrument<Extendable<RouterAttributes, RouterSelector>>,
 }
 
-#[allow(dead_code)]
 #[derive(Clone, Deserialize, JsonSchema, Debug)]
 #[serde(deny_unknown_fields, untagged)]
 enum DefaultedStandardInstrument<T> {
@@ -68,10 +355,70 @@ impl<T> Default for DefaultedStandardInstrument<T> {
     }
 }
 
-#[allow(dead_code)]
+impl<T> DefaultedStandardInstrument<T> {
+    fn attributes(&self) -> Option<&T> {
+        match self {
+            DefaultedStandardInstrument::Bool(_) => None,
+            DefaultedStandardInstrument::Extendable { attributes } => Some(attributes),
+        }
+    }
+}
+
+impl<T> DefaultedStandardInstrument<T>
+where
+    T: Selectors,
+{
+    /// The request attributes of the instrument, or `None` if it is disabled
+    fn on_request(&self, request: &T::Request) -> Option<LinkedList<KeyValue>> {
+        match self {
+            DefaultedStandardInstrument::Bool(enabled) => enabled.then(LinkedList::new),
+            DefaultedStandardInstrument::Extendable { attributes } => {
+                Some(attributes.on_request(request))
+            }
+        }
+    }
+}
+
+impl<T> DefaultForLevel for DefaultedStandardInstrument<T>
+where
+    T: DefaultForLevel + Default,
+{
+    fn defaults_for_level(&mut self, requirement_level: DefaultAttributeRequirementLevel) {
+        match self {
+            DefaultedStandardInstrument::Bool(true) => {
+                let mut attributes = T::default();
+                attributes.defaults_for_level(requirement_level);
+                *self = DefaultedStandardInstrument::Extendable { attributes };
+            }
+            DefaultedStandardInstrument::Bool(false) => {}
+            DefaultedStandardInstrument::Extendable { attributes } => {
+                attributes.defaults_for_level(requirement_level)
+            }
+        }
+    }
+}
+
 #[derive(Clone, Deserialize, JsonSchema, Debug, Default)]
 #[serde(deny_unknown_fields, default)]
-struct SupergraphInstruments {}
+struct SupergraphInstruments {
+    /// Histogram of GraphQL operation duration
+    #[serde(rename = "graphql.operation.duration")]
+    graphql_operation_duration:
+        DefaultedStandardInstrument<Extendable<SupergraphAttributes, SupergraphSelector>>,
+
+    /// Counter of GraphQL errors, with the error code as the `graphql.error.code` attribute
+    #[serde(rename = "graphql.errors")]
+    graphql_errors:
+        DefaultedStandardInstrument<Extendable<SupergraphAttributes, SupergraphSelector>>,
+}
+
+impl DefaultForLevel for SupergraphInstruments {
+    fn defaults_for_level(&mut self, requirement_level: DefaultAttributeRequirementLevel) {
+        self.graphql_operation_duration
+            .defaults_for_level(requirement_level);
+        self.graphql_errors.defaults_for_level(requirement_level);
+    }
+}
 
 #[allow(dead_code)]
 #[derive(Clone, Deserialize, JsonSchema, Debug, Default)]
@@ -90,9 +437,8 @@ struct SubgraphInstruments {
     http_client_response_body_size: bool,
 }
 
-#[allow(dead_code)]
 #[derive(Clone, Deserialize, JsonSchema, Debug)]
-pub(crate) struct Instrument<A, E>
+pub(crate) struct Instrument<A, E, V = InstrumentValue<E>>
 where
     A: Default + Debug,
     E: Debug,
@@ -102,7 +448,7 @@ where
     ty: InstrumentType,
 
     /// The value of the instrument.
-    value: InstrumentValue<E>,
+    value: V,
 
     /// The description of the instrument.
     description: String,
@@ -119,7 +465,53 @@ where
     condition: Condition<E>,
 }
 
-#[allow(dead_code)]
+impl<A, E, V> Instrument<A, E, V>
+where
+    A: Default + Debug,
+    E: Debug,
+{
+    /// The metric of the instrument, created on its first use
+    fn metric(&self, name: &str, metrics: &CustomMetrics) -> Metric {
+        let description = || self.description.clone();
+        let unit = || Unit::new(self.unit.clone());
+        match self.ty {
+            InstrumentType::Counter => {
+                Metric::Counter(metrics.counters.get_or_create(name, |provider| {
+                    provider
+                        .meter("apollo/router")
+                        .f64_counter(name.to_string())
+                        .with_description(description())
+                        .with_unit(unit())
+                        .init()
+                }))
+            }
+            InstrumentType::UpDownCounter => {
+                Metric::UpDownCounter(metrics.up_down_counters.get_or_create(name, |provider| {
+                    provider
+                        .meter("apollo/router")
+                        .f64_up_down_counter(name.to_string())
+                        .with_description(description())
+                        .with_unit(unit())
+                        .init()
+                }))
+            }
+            InstrumentType::Histogram => {
+                Metric::Histogram(metrics.histograms.get_or_create(name, |provider| {
+                    provider
+                        .meter("apollo/router")
+                        .f64_histogram(name.to_string())
+                        .with_description(description())
+                        .with_unit(unit())
+                        .init()
+                }))
+            }
+            InstrumentType::Gauge => {
+                unreachable!("gauges are rejected when the configuration is validated")
+            }
+        }
+    }
+}
+
 #[derive(Clone, Deserialize, JsonSchema, Debug)]
 #[serde(deny_unknown_fields, rename_all = "snake_case")]
 pub(crate) enum InstrumentType {
@@ -144,7 +536,6 @@ pub(crate) enum InstrumentValue<T> {
     Custom(T),
 }
 
-#[allow(dead_code)]
 #[derive(Clone, Deserialize, JsonSchema, Debug)]
 #[serde(deny_unknown_fields, rename_all = "snake_case")]
 pub(crate) enum Standard {
@@ -152,3 +543,158 @@ pub(crate) enum Standard {
     Unit,
     Active,
 }
+
+#[derive(Clone, Deserialize, JsonSchema, Debug)]
+#[serde(deny_unknown_fields, rename_all = "snake_case", untagged)]
+pub(crate) enum SupergraphValue {
+    Standard(Standard),
+    GraphQL(GraphQLValue),
+    Custom(SupergraphSelector),
+}
+
+/// Values computed from each GraphQL response sent to the client, which is several responses
+/// for deferred queries and subscriptions.
+#[derive(Clone, Deserialize, JsonSchema, Debug)]
+#[serde(deny_unknown_fields, rename_all = "snake_case")]
+pub(crate) enum GraphQLValue {
+    /// The number of GraphQL errors in the response.
+    ErrorCount,
+    /// The size of the serialized response in bytes.
+    ResponseSize,
+    /// The number of fields in the response data. Each item of a list counts its fields.
+    FieldCount,
+}
+
+impl GraphQLValue {
+    pub(crate) fn on_graphql_response(&self, response: &graphql::Response) -> Option<i64> {
+        match self {
+            GraphQLValue::ErrorCount => Some(
+                response.errors.len()
+                    + response
+                        .incremental
+                        .iter()
+                        .map(|incremental| incremental.errors.len())
+                        .sum::<usize>(),
+            ),
+            GraphQLValue::ResponseSize => {
+                serde_json::to_vec(response).ok().map(|bytes| bytes.len())
+            }
+            GraphQLValue::FieldCount => Some(
+                response.data.as_ref().map(field_count).unwrap_or_default()
+                    + response
+                        .incremental
+                        .iter()
+                        .filter_map(|incremental| incremental.data.as_ref())
+                        .map(field_count)
+                        .sum::<usize>(),
+            ),
+        }
+        .map(|value| value as i64)
+    }
+}
+
+fn field_count(value: &Value) -> usize {
+    match value {
+        Value::Object(object) => object.values().map(|value| 1 + field_count(value)).sum(),
+        Value::Array(items) => items.iter().map(field_count).sum(),
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json_bytes::json;
+
+    use super::GraphQLValue;
+    use super::Instruments;
+    use crate::graphql;
+    use crate::metrics::meter_provider;
+
+    #[test]
+    fn test_graphql_values() {
+        let response = graphql::Response::builder()
+            .data(json!({
+                "topProducts": [
+                    { "name": "Table", "reviews": [{ "body": "Great" }, { "body": "Sturdy" }] },
+                    { "name": "Chair", "reviews": null }
+                ]
+            }))
+            .error(
+                graphql::Error::builder()
+                    .message("one")
+                    .extension_code("ONE")
+                    .build(),
+            )
+            .error(
+                graphql::Error::builder()
+                    .message("two")
+                    .extension_code("TWO")
+                    .build(),
+            )
+            .build();
+
+        assert_eq!(
+            GraphQLValue::ErrorCount.on_graphql_response(&response),
+            Some(2)
+        );
+        // topProducts, 2 x (name, reviews) and 2 x body
+        assert_eq!(
+            GraphQLValue::FieldCount.on_graphql_response(&response),
+            Some(7)
+        );
+        assert_eq!(
+            GraphQLValue::ResponseSize.on_graphql_response(&response),
+            Some(serde_json::to_vec(&response).unwrap().len() as i64)
+        );
+        assert_eq!(
+            GraphQLValue::FieldCount.on_graphql_response(&graphql::Response::default()),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let instruments = |ty: &str, value: &str| -> Instruments {
+            serde_json::from_value(serde_json::json!({
+                "supergraph": {
+                    "acme.requests": {
+                        "type": ty,
+                        "value": value,
+                        "unit": "{request}",
+                        "description": "requests"
+                    }
+                }
+            }))
+            .unwrap()
+        };
+
+        assert!(instruments("counter", "unit").validate().is_ok());
+        assert!(instruments("up_down_counter", "active").validate().is_ok());
+        assert!(instruments("counter", "active").validate().is_err());
+        assert!(instruments("gauge", "unit").validate().is_err());
+    }
+
+    #[test]
+    fn test_metrics_are_created_once() {
+        let instruments: Instruments = serde_json::from_value(serde_json::json!({
+            "supergraph": {
+                "acme.requests": {
+                    "type": "counter",
+                    "value": "unit",
+                    "unit": "{request}",
+                    "description": "requests"
+                }
+            }
+        }))
+        .unwrap();
+        let instrument = &instruments.supergraph.custom["acme.requests"];
+
+        for _ in 0..2 {
+            instrument
+                .metric("acme.requests", &instruments.supergraph_metrics)
+                .record(1.0, &[]);
+        }
+        assert_counter!("acme.requests", 2.0);
+        assert_eq!(meter_provider().registered_instruments(), 1);
+    }
+}
@@ -2,6 +2,7 @@ use std::collections::BTreeMap;
 use std::io::IsTerminal;
 use std::time::Duration;
 
+use bytesize::ByteSize;
 use schemars::gen::SchemaGenerator;
 use schemars::schema::InstanceType;
 use schemars::schema::Metadata;
@@ -19,6 +20,7 @@ use serde::Deserializer;
 use crate::configuration::ConfigurationError;
 use crate::plugins::telemetry::config::AttributeValue;
 use crate::plugins::telemetry::config_new::experimental_when_header::HeaderLoggingCondition;
+use crate::plugins::telemetry::otlp;
 use crate::plugins::telemetry::resource::ConfigResource;
 use crate::services::SupergraphRequest;
 
@@ -30,9 +32,10 @@ pub(crate) struct Logging {
     pub(crate) common: LoggingCommon,
     /// Settings for logging to stdout.
     pub(crate) stdout: StdOut,
-    #[serde(skip)]
     /// Settings for logging to a file.
     pub(crate) file: File,
+    /// Settings for exporting logs with the OpenTelemetry Protocol (OTLP).
+    pub(crate) otlp: otlp::Config,
 
     /// Log configuration to log request and response for subgraphs and supergraph
     /// Note that this will be removed when events are implemented.
@@ -48,15 +51,22 @@ impl Logging {
         });
 
         if misconfiguration {
-            Err(ConfigurationError::InvalidConfiguration {
+            return Err(ConfigurationError::InvalidConfiguration {
                 message: "'experimental_when_header' configuration for logging is invalid",
                 error: String::from(
                     "body and headers must not be both false because it doesn't enable any logs",
                 ),
-            })
-        } else {
-            Ok(())
+            });
+        }
+
+        if self.file.enabled && self.file.path.is_empty() {
+            return Err(ConfigurationError::InvalidConfiguration {
+                message: "'file' configuration for logging is invalid",
+                error: String::from("a path is required to log to a file"),
+            });
         }
+
+        Ok(())
     }
 
     /// Returns if we should display the request/response headers and body given the `SupergraphRequest`
@@ -146,20 +156,38 @@ impl Default for RateLimit {
 }
 
 /// Log to a file
-#[allow(dead_code)]
-#[derive(Deserialize, JsonSchema, Clone, Default, Debug)]
+#[derive(Deserialize, JsonSchema, Clone, Debug)]
 #[serde(deny_unknown_fields, default)]
 pub(crate) struct File {
     /// Set to true to log to a file.
     pub(crate) enabled: bool,
-    /// The path pattern of the file to log to.
+    /// The path of the file to log to. Rolled over files are written next to it, with the rollover time as suffix.
     pub(crate) path: String,
-    /// The format of the log file.
+    /// The format of the log file. (default: json)
     pub(crate) format: Format,
     /// The period to rollover the log file.
     pub(crate) rollover: Rollover,
+    /// Roll over the log file when it reaches this size, for example `100MB`.
+    #[schemars(with = "Option<String>")]
+    pub(crate) max_size: Option<ByteSize>,
+    /// The number of rolled over log files to keep. All of them are kept if not set.
+    pub(crate) max_files: Option<usize>,
     /// Log rate limiting. The limit is set per type of log message
-    pub(crate) rate_limit: Option<RateLimit>,
+    pub(crate) rate_limit: RateLimit,
+}
+
+impl Default for File {
+    fn default() -> Self {
+        File {
+            enabled: false,
+            path: String::new(),
+            format: Format::Json(JsonFormat::default()),
+            rollover: Rollover::default(),
+            max_size: None,
+            max_files: None,
+            rate_limit: RateLimit::default(),
+        }
+    }
 }
 
 /// The format for logging.
@@ -168,17 +196,19 @@ pub(crate) enum Format {
     // !!!!WARNING!!!!, if you change this enum then be sure to add the changes to the JsonSchema AND the custom deserializer.
 
     // Want to see support for these formats? Please open an issue!
-    // /// https://docs.aws.amazon.com/AmazonCloudWatch/latest/logs/CWL_AnalyzeLogData-discoverable-fields.html
-    // Aws,
     // /// https://github.com/trentm/node-bunyan
     // Bunyan,
-    // /// https://go2docs.graylog.org/5-0/getting_in_log_data/ingest_gelf.html#:~:text=The%20Graylog%20Extended%20Log%20Format,UDP%2C%20TCP%2C%20or%20HTTP.
-    // Gelf,
-    //
-    // /// https://cloud.google.com/logging/docs/structured-logging
-    // Google,
     // /// https://github.com/open-telemetry/opentelemetry-rust/tree/main/opentelemetry-appender-log
     // OpenTelemetry,
+    /// https://docs.aws.amazon.com/AmazonCloudWatch/latest/logs/CWL_AnalyzeLogData-discoverable-fields.html
+    Aws(AwsFormat),
+
+    /// https://go2docs.graylog.org/5-0/getting_in_log_data/gelf.html
+    Gelf(GelfFormat),
+
+    /// https://cloud.google.com/logging/docs/structured-logging
+    Google(GoogleFormat),
+
     /// https://docs.rs/tracing-subscriber/latest/tracing_subscriber/fmt/format/struct.Json.html
     Json(JsonFormat),
 
@@ -195,6 +225,9 @@ impl JsonSchema for Format {
     fn json_schema(gen: &mut SchemaGenerator) -> Schema {
         // Does nothing, but will compile error if the
         let types = vec![
+            ("aws", AwsFormat::json_schema(gen), "AWS CloudWatch structured logging https://docs.aws.amazon.com/AmazonCloudWatch/latest/logs/CWL_AnalyzeLogData-discoverable-fields.html"),
+            ("gelf", GelfFormat::json_schema(gen), "Graylog Extended Log Format https://go2docs.graylog.org/5-0/getting_in_log_data/gelf.html"),
+            ("google", GoogleFormat::json_schema(gen), "Google Cloud structured logging https://cloud.google.com/logging/docs/structured-logging"),
             ("json", JsonFormat::json_schema(gen), "Tracing subscriber https://docs.rs/tracing-subscriber/latest/tracing_subscriber/fmt/format/struct.Json.html"),
             ("text", TextFormat::json_schema(gen), "Tracing subscriber https://docs.rs/tracing-subscriber/latest/tracing_subscriber/fmt/format/struct.Full.html"),
         ];
@@ -273,6 +306,9 @@ impl<'de> Deserialize<'de> for Format {
                 E: serde::de::Error,
             {
                 match value {
+                    "aws" => Ok(Format::Aws(AwsFormat::default())),
+                    "gelf" => Ok(Format::Gelf(GelfFormat::default())),
+                    "google" => Ok(Format::Google(GoogleFormat::default())),
                     "json" => Ok(Format::Json(JsonFormat::default())),
                     "text" => Ok(Format::Text(TextFormat::default())),
                     _ => Err(E::custom(format!("unknown log format: {}", value))),
@@ -286,6 +322,9 @@ impl<'de> Deserialize<'de> for Format {
                 let key = map.next_key::<String>()?;
 
                 match key.as_deref() {
+                    Some("aws") => Ok(Format::Aws(map.next_value::<AwsFormat>()?)),
+                    Some("gelf") => Ok(Format::Gelf(map.next_value::<GelfFormat>()?)),
+                    Some("google") => Ok(Format::Google(map.next_value::<GoogleFormat>()?)),
                     Some("json") => Ok(Format::Json(map.next_value::<JsonFormat>()?)),
                     Some("text") => Ok(Format::Text(map.next_value::<TextFormat>()?)),
                     Some(value) => Err(serde::de::Error::custom(format!(
@@ -416,9 +455,116 @@ impl Default for TextFormat {
     }
 }
 
+#[derive(Deserialize, JsonSchema, Clone, Debug, Eq, PartialEq)]
+#[serde(deny_unknown_fields, rename_all = "snake_case", default)]
+pub(crate) struct AwsFormat {
+    /// Include the timestamp with the log event. (default: true)
+    pub(crate) display_timestamp: bool,
+    /// Include the target with the log event. (default: true)
+    pub(crate) display_target: bool,
+    /// Include the filename with the log event.
+    pub(crate) display_filename: bool,
+    /// Include the line number with the log event.
+    pub(crate) display_line_number: bool,
+    /// Include all of the containing span information with the log event. (default: true)
+    pub(crate) display_span_list: bool,
+    /// Include the resource with the log event. (default: true)
+    pub(crate) display_resource: bool,
+    /// Include the trace id (if any) with the log event, in the AWS X-Ray format. (default: true)
+    pub(crate) display_trace_id: bool,
+    /// Include the span id (if any) with the log event. (default: true)
+    pub(crate) display_span_id: bool,
+}
+
+impl Default for AwsFormat {
+    fn default() -> Self {
+        AwsFormat {
+            display_timestamp: true,
+            display_target: true,
+            display_filename: false,
+            display_line_number: false,
+            display_span_list: true,
+            display_resource: true,
+            display_trace_id: true,
+            display_span_id: true,
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema, Clone, Debug, Eq, PartialEq)]
+#[serde(deny_unknown_fields, rename_all = "snake_case", default)]
+pub(crate) struct GelfFormat {
+    /// The host sent with the log event. (default: the `host.name` resource attribute, or the system host name)
+    pub(crate) host: Option<String>,
+    /// Include the target with the log event. (default: true)
+    pub(crate) display_target: bool,
+    /// Include the filename with the log event.
+    pub(crate) display_filename: bool,
+    /// Include the line number with the log event.
+    pub(crate) display_line_number: bool,
+    /// Include the attributes of the containing spans with the log event. (default: true)
+    pub(crate) display_span_attributes: bool,
+    /// Include the resource with the log event.
+    pub(crate) display_resource: bool,
+    /// Include the trace id (if any) with the log event. (default: true)
+    pub(crate) display_trace_id: bool,
+    /// Include the span id (if any) with the log event. (default: true)
+    pub(crate) display_span_id: bool,
+}
+
+impl Default for GelfFormat {
+    fn default() -> Self {
+        GelfFormat {
+            host: None,
+            display_target: true,
+            display_filename: false,
+            display_line_number: false,
+            display_span_attributes: true,
+            display_resource: false,
+            display_trace_id: true,
+            display_span_id: true,
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema, Clone, Debug, Eq, PartialEq)]
+#[serde(deny_unknown_fields, rename_all = "snake_case", default)]
+pub(crate) struct GoogleFormat {
+    /// The Google Cloud project id, used to link log entries to their trace in Cloud Trace. Without it, only the trace id is included.
+    pub(crate) project_id: Option<String>,
+    /// Include the timestamp with the log event. (default: true)
+    pub(crate) display_timestamp: bool,
+    /// Include the target with the log event. (default: true)
+    pub(crate) display_target: bool,
+    /// Include the source location (filename and line number) with the log event.
+    pub(crate) display_source_location: bool,
+    /// Include all of the containing span information with the log event. (default: true)
+    pub(crate) display_span_list: bool,
+    /// Include the resource as labels with the log event. (default: true)
+    pub(crate) display_resource: bool,
+    /// Include the trace id (if any) with the log event. (default: true)
+    pub(crate) display_trace_id: bool,
+    /// Include the span id (if any) with the log event. (default: true)
+    pub(crate) display_span_id: bool,
+}
+
+impl Default for GoogleFormat {
+    fn default() -> Self {
+        GoogleFormat {
+            project_id: None,
+            display_timestamp: true,
+            display_target: true,
+            display_source_location: false,
+            display_span_list: true,
+            display_resource: true,
+            display_trace_id: true,
+            display_span_id: true,
+        }
+    }
+}
+
 /// The period to rollover the log file.
-#[allow(dead_code)]
-#[derive(Deserialize, JsonSchema, Clone, Default, Debug)]
+#[derive(Deserialize, JsonSchema, Clone, Default, Debug, Eq, PartialEq)]
 #[serde(deny_unknown_fields, rename_all = "snake_case")]
 pub(crate) enum Rollover {
     /// Roll over every hour.
@@ -436,7 +582,9 @@ mod test {
     use serde_json::json;
 
     use crate::plugins::telemetry::config_new::experimental_when_header::HeaderLoggingCondition;
+    use crate::plugins::telemetry::config_new::logging::File;
     use crate::plugins::telemetry::config_new::logging::Format;
+    use crate::plugins::telemetry::config_new::logging::GoogleFormat;
     use crate::plugins::telemetry::config_new::logging::Logging;
     use crate::services::SupergraphRequest;
     #[test]
@@ -449,6 +597,22 @@ mod test {
         assert_eq!(format, Format::Text(Default::default()));
         let format = serde_json::from_value::<Format>(json!({"json":{}})).unwrap();
         assert_eq!(format, Format::Json(Default::default()));
+        let format = serde_json::from_value::<Format>(json!("google")).unwrap();
+        assert_eq!(format, Format::Google(Default::default()));
+        let format =
+            serde_json::from_value::<Format>(json!({"google":{"project_id":"my-project"}}))
+                .unwrap();
+        assert_eq!(
+            format,
+            Format::Google(GoogleFormat {
+                project_id: Some("my-project".to_string()),
+                ..Default::default()
+            })
+        );
+        let format = serde_json::from_value::<Format>(json!("aws")).unwrap();
+        assert_eq!(format, Format::Aws(Default::default()));
+        let format = serde_json::from_value::<Format>(json!({"gelf":{}})).unwrap();
+        assert_eq!(format, Format::Gelf(Default::default()));
     }
 
     #[test]
@@ -478,6 +642,19 @@ mod test {
         let validate_res = logging_conf.validate();
         assert!(validate_res.is_err());
         assert_eq!(validate_res.unwrap_err().to_string(), "'experimental_when_header' configuration for logging is invalid: body and headers must not be both false because it doesn't enable any logs");
+        let logging_conf = Logging {
+            file: File {
+                enabled: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let validate_res = logging_conf.validate();
+        assert_eq!(
+            validate_res.unwrap_err().to_string(),
+            "'file' configuration for logging is invalid: a path is required to log to a file"
+        );
     }
 
     #[test]
@@ -2,6 +2,7 @@ use access_json::JSONQuery;
 use derivative::Derivative;
 use jsonpath_rust::JsonPathFinder;
 use jsonpath_rust::JsonPathInst;
+use regex::Regex;
 use schemars::JsonSchema;
 use serde::Deserialize;
 #[cfg(test)]
@@ -13,6 +14,8 @@ use crate::context::OPERATION_KIND;
 use crate::context::OPERATION_NAME;
 use crate::plugin::serde::deserialize_json_query;
 use crate::plugin::serde::deserialize_jsonpath;
+use crate::plugin::serde::deserialize_regex;
+use crate::plugins::authentication::APOLLO_AUTHENTICATION_JWT_CLAIMS;
 use crate::plugins::telemetry::config::AttributeValue;
 use crate::plugins::telemetry::config_new::get_baggage;
 use crate::plugins::telemetry::config_new::trace_id;
@@ -68,6 +71,65 @@ pub(crate) enum OperationKind {
     String,
 }
 
+/// Redaction of the value of a selector, for instance to mask tokens or emails
+#[derive(Deserialize, JsonSchema, Clone, Debug)]
+#[cfg_attr(test, derive(Serialize))]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Redact {
+    /// The regular expression matching the parts of the value to redact.
+    #[schemars(with = "String")]
+    #[serde(deserialize_with = "deserialize_regex")]
+    #[cfg_attr(test, serde(serialize_with = "serialize_regex"))]
+    pattern: Regex,
+    /// The replacement of the matched parts, which can refer to capture groups such as `$1`. (default: `[REDACTED]`)
+    #[serde(default = "default_redact_replacement")]
+    replacement: String,
+}
+
+fn default_redact_replacement() -> String {
+    "[REDACTED]".to_string()
+}
+
+#[cfg(test)]
+fn serialize_regex<S>(regex: &Regex, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.serialize_str(regex.as_str())
+}
+
+impl Redact {
+    /// Replaces the matches of the pattern in the value. Values that are not strings are
+    /// redacted as strings, so that numbers such as account ids can be masked too.
+    fn apply(&self, value: opentelemetry::Value) -> opentelemetry::Value {
+        match value {
+            opentelemetry::Value::String(value) => self.replace(value.as_str()).into(),
+            opentelemetry::Value::Array(opentelemetry::Array::String(values)) => {
+                opentelemetry::Value::Array(opentelemetry::Array::String(
+                    values
+                        .iter()
+                        .map(|value| self.replace(value.as_str()).into())
+                        .collect(),
+                ))
+            }
+            value => {
+                let string_value = value.to_string();
+                if self.pattern.is_match(&string_value) {
+                    self.replace(&string_value).into()
+                } else {
+                    value
+                }
+            }
+        }
+    }
+
+    fn replace(&self, value: &str) -> String {
+        self.pattern
+            .replace_all(value, self.replacement.as_str())
+            .into_owned()
+    }
+}
+
 #[derive(Deserialize, JsonSchema, Clone, Debug)]
 #[serde(deny_unknown_fields, untagged)]
 pub(crate) enum RouterSelector {
@@ -75,10 +137,8 @@ pub(crate) enum RouterSelector {
     RequestHeader {
         /// The name of the request header.
         request_header: String,
-        #[serde(skip)]
-        #[allow(dead_code)]
-        /// Optional redaction pattern.
-        redact: Option<String>,
+        /// Optional redaction of the value.
+        redact: Option<Redact>,
         /// Optional default value.
         default: Option<AttributeValue>,
     },
@@ -86,10 +146,8 @@ pub(crate) enum RouterSelector {
     ResponseHeader {
         /// The name of the request header.
         response_header: String,
-        #[serde(skip)]
-        #[allow(dead_code)]
-        /// Optional redaction pattern.
-        redact: Option<String>,
+        /// Optional redaction of the value.
+        redact: Option<Redact>,
         /// Optional default value.
         default: Option<AttributeValue>,
     },
@@ -107,10 +165,8 @@ pub(crate) enum RouterSelector {
     ResponseContext {
         /// The response context key.
         response_context: String,
-        #[serde(skip)]
-        #[allow(dead_code)]
-        /// Optional redaction pattern.
-        redact: Option<String>,
+        /// Optional redaction of the value.
+        redact: Option<Redact>,
         /// Optional default value.
         default: Option<AttributeValue>,
     },
@@ -118,10 +174,8 @@ pub(crate) enum RouterSelector {
     Baggage {
         /// The name of the baggage item.
         baggage: String,
-        #[serde(skip)]
-        #[allow(dead_code)]
-        /// Optional redaction pattern.
-        redact: Option<String>,
+        /// Optional redaction of the value.
+        redact: Option<Redact>,
         /// Optional default value.
         default: Option<AttributeValue>,
     },
@@ -129,10 +183,8 @@ pub(crate) enum RouterSelector {
     Env {
         /// The name of the environment variable
         env: String,
-        #[serde(skip)]
-        #[allow(dead_code)]
-        /// Optional redaction pattern.
-        redact: Option<String>,
+        /// Optional redaction of the value.
+        redact: Option<Redact>,
         /// Optional default value.
         default: Option<String>,
     },
@@ -146,10 +198,8 @@ pub(crate) enum SupergraphSelector {
     OperationName {
         /// The operation name from the query.
         operation_name: OperationName,
-        #[serde(skip)]
-        #[allow(dead_code)]
-        /// Optional redaction pattern.
-        redact: Option<String>,
+        /// Optional redaction of the value.
+        redact: Option<Redact>,
         /// Optional default value.
         default: Option<String>,
     },
@@ -164,80 +214,72 @@ pub(crate) enum SupergraphSelector {
         // Allow dead code is required because there is only one variant in Query and we need to avoid the dead code warning.
         #[allow(dead_code)]
         query: Query,
-        #[serde(skip)]
-        #[allow(dead_code)]
-        /// Optional redaction pattern.
-        redact: Option<String>,
+        /// Optional redaction of the value.
+        redact: Option<Redact>,
         /// Optional default value.
         default: Option<String>,
     },
     QueryVariable {
         /// The name of a graphql query variable.
         query_variable: String,
-        #[serde(skip)]
-        #[allow(dead_code)]
-        /// Optional redaction pattern.
-        redact: Option<String>,
+        /// Optional redaction of the value.
+        redact: Option<Redact>,
         /// Optional default value.
         default: Option<AttributeValue>,
     },
     RequestHeader {
         /// The name of the request header.
         request_header: String,
-        #[serde(skip)]
-        #[allow(dead_code)]
-        /// Optional redaction pattern.
-        redact: Option<String>,
+        /// Optional redaction of the value.
+        redact: Option<Redact>,
         /// Optional default value.
         default: Option<String>,
     },
     ResponseHeader {
         /// The name of the response header.
         response_header: String,
-        #[serde(skip)]
-        #[allow(dead_code)]
-        /// Optional redaction pattern.
-        redact: Option<String>,
+        /// Optional redaction of the value.
+        redact: Option<Redact>,
         /// Optional default value.
         default: Option<String>,
     },
     RequestContext {
         /// The request context key.
         request_context: String,
-        #[serde(skip)]
-        #[allow(dead_code)]
-        /// Optional redaction pattern.
-        redact: Option<String>,
+        /// Optional redaction of the value.
+        redact: Option<Redact>,
         /// Optional default value.
         default: Option<AttributeValue>,
     },
     ResponseContext {
         /// The response context key.
         response_context: String,
-        #[serde(skip)]
-        #[allow(dead_code)]
-        /// Optional redaction pattern.
-        redact: Option<String>,
+        /// Optional redaction of the value.
+        redact: Option<Redact>,
+        /// Optional default value.
+        default: Option<AttributeValue>,
+    },
+    JwtClaim {
+        /// The name of a claim of the authenticated JWT.
+        jwt_claim: String,
+        /// Optional redaction of the value.
+        redact: Option<Redact>,
         /// Optional default value.
         default: Option<AttributeValue>,
     },
     Baggage {
         /// The name of the baggage item.
         baggage: String,
-        #[serde(skip)]
-        #[allow(dead_code)]
-        /// Optional redaction pattern.
-        redact: Option<String>,
+        /// Optional redaction of the value.
+        redact: Option<Redact>,
         /// Optional default value.
         default: Option<AttributeValue>,
     },
     Env {
         /// The name of the environment variable
         env: String,
-        #[serde(skip)]
-        #[allow(dead_code)]
-        /// Optional redaction pattern.
-        redact: Option<String>,
+        /// Optional redaction of the value.
+        redact: Option<Redact>,
         /// Optional default value.
         default: Option<String>,
     },
@@ -251,10 +293,8 @@ pub(crate) enum SubgraphSelector {
     SubgraphOperationName {
         /// The operation name from the subgraph query.
         subgraph_operation_name: OperationName,
-        #[serde(skip)]
-        #[allow(dead_code)]
-        /// Optional redaction pattern.
-        redact: Option<String>,
+        /// Optional redaction of the value.
+        redact: Option<Redact>,
         /// Optional default value.
         default: Option<String>,
     },
@@ -269,20 +309,16 @@ pub(crate) enum SubgraphSelector {
         // Allow dead code is required because there is only one variant in Query and we need to avoid the dead code warning.
         #[allow(dead_code)]
         subgraph_query: Query,
-        #[serde(skip)]
-        #[allow(dead_code)]
-        /// Optional redaction pattern.
-        redact: Option<String>,
+        /// Optional redaction of the value.
+        redact: Option<Redact>,
         /// Optional default value.
         default: Option<String>,
     },
     SubgraphQueryVariable {
         /// The name of a subgraph query variable.
         subgraph_query_variable: String,
-        #[serde(skip)]
-        #[allow(dead_code)]
-        /// Optional redaction pattern.
-        redact: Option<String>,
+        /// Optional redaction of the value.
+        redact: Option<Redact>,
         /// Optional default value.
         default: Option<AttributeValue>,
     },
@@ -292,10 +328,8 @@ pub(crate) enum SubgraphSelector {
         #[schemars(with = "String")]
         #[serde(deserialize_with = "deserialize_json_query")]
         subgraph_response_body: JSONQuery,
-        #[serde(skip)]
-        #[allow(dead_code)]
-        /// Optional redaction pattern.
-        redact: Option<String>,
+        /// Optional redaction of the value.
+        redact: Option<Redact>,
         /// Optional default value.
         default: Option<AttributeValue>,
     },
@@ -305,10 +339,8 @@ pub(crate) enum SubgraphSelector {
         #[derivative(Debug = "ignore")]
         #[serde(deserialize_with = "deserialize_jsonpath")]
         subgraph_response_data: JsonPathInst,
-        #[serde(skip)]
-        #[allow(dead_code)]
-        /// Optional redaction pattern.
-        redact: Option<String>,
+        /// Optional redaction of the value.
+        redact: Option<Redact>,
         /// Optional default value.
         default: Option<AttributeValue>,
     },
@@ -318,30 +350,24 @@ pub(crate) enum SubgraphSelector {
         #[derivative(Debug = "ignore")]
         #[serde(deserialize_with = "deserialize_jsonpath")]
         subgraph_response_errors: JsonPathInst,
-        #[serde(skip)]
-        #[allow(dead_code)]
-        /// Optional redaction pattern.
-        redact: Option<String>,
+        /// Optional redaction of the value.
+        redact: Option<Redact>,
         /// Optional default value.
         default: Option<AttributeValue>,
     },
     SubgraphRequestHeader {
         /// The name of a subgraph request header.
         subgraph_request_header: String,
-        #[serde(skip)]
-        #[allow(dead_code)]
-        /// Optional redaction pattern.
-        redact: Option<String>,
+        /// Optional redaction of the value.
+        redact: Option<Redact>,
         /// Optional default value.
         default: Option<String>,
     },
     SubgraphResponseHeader {
         /// The name of a subgraph response header.
         subgraph_response_header: String,
-        #[serde(skip)]
-        #[allow(dead_code)]
-        /// Optional redaction pattern.
-        redact: Option<String>,
+        /// Optional redaction of the value.
+        redact: Option<Redact>,
         /// Optional default value.
         default: Option<String>,
     },
@@ -352,10 +378,8 @@ pub(crate) enum SubgraphSelector {
     SupergraphOperationName {
         /// The supergraph query operation name.
         supergraph_operation_name: OperationName,
-        #[serde(skip)]
-        #[allow(dead_code)]
-        /// Optional redaction pattern.
-        redact: Option<String>,
+        /// Optional redaction of the value.
+        redact: Option<Redact>,
         /// Optional default value.
         default: Option<String>,
     },
@@ -370,82 +394,163 @@ pub(crate) enum SubgraphSelector {
         // Allow dead code is required because there is only one variant in Query and we need to avoid the dead code warning.
         #[allow(dead_code)]
         supergraph_query: Query,
-        #[serde(skip)]
-        #[allow(dead_code)]
-        /// Optional redaction pattern.
-        redact: Option<String>,
+        /// Optional redaction of the value.
+        redact: Option<Redact>,
         /// Optional default value.
         default: Option<String>,
     },
     SupergraphQueryVariable {
         /// The supergraph query variable name.
         supergraph_query_variable: String,
-        #[serde(skip)]
-        #[allow(dead_code)]
-        /// Optional redaction pattern.
-        redact: Option<String>,
+        /// Optional redaction of the value.
+        redact: Option<Redact>,
         /// Optional default value.
         default: Option<AttributeValue>,
     },
     SupergraphRequestHeader {
         /// The supergraph request header name.
         supergraph_request_header: String,
-        #[serde(skip)]
-        #[allow(dead_code)]
-        /// Optional redaction pattern.
-        redact: Option<String>,
+        /// Optional redaction of the value.
+        redact: Option<Redact>,
         /// Optional default value.
         default: Option<String>,
     },
+    SupergraphRequestExtension {
+        /// The name of an entry of the supergraph request extensions.
+        supergraph_request_extension: String,
+        /// Optional redaction of the value.
+        redact: Option<Redact>,
+        /// Optional default value.
+        default: Option<AttributeValue>,
+    },
     RequestContext {
         /// The request context key.
         request_context: String,
-        #[serde(skip)]
-        #[allow(dead_code)]
-        /// Optional redaction pattern.
-        redact: Option<String>,
+        /// Optional redaction of the value.
+        redact: Option<Redact>,
         /// Optional default value.
         default: Option<AttributeValue>,
     },
     ResponseContext {
         /// The response context key.
         response_context: String,
-        #[serde(skip)]
-        #[allow(dead_code)]
-        /// Optional redaction pattern.
-        redact: Option<String>,
+        /// Optional redaction of the value.
+        redact: Option<Redact>,
+        /// Optional default value.
+        default: Option<AttributeValue>,
+    },
+    JwtClaim {
+        /// The name of a claim of the authenticated JWT.
+        jwt_claim: String,
+        /// Optional redaction of the value.
+        redact: Option<Redact>,
         /// Optional default value.
         default: Option<AttributeValue>,
     },
     Baggage {
         /// The name of the baggage item.
         baggage: String,
-        #[serde(skip)]
-        #[allow(dead_code)]
-        /// Optional redaction pattern.
-        redact: Option<String>,
+        /// Optional redaction of the value.
+        redact: Option<Redact>,
         /// Optional default value.
         default: Option<AttributeValue>,
     },
     Env {
         /// The name of the environment variable
         env: String,
-        #[serde(skip)]
-        #[allow(dead_code)]
-        /// Optional redaction pattern.
-        redact: Option<String>,
+        /// Optional redaction of the value.
+        redact: Option<Redact>,
         /// Optional default value.
         default: Option<String>,
     },
     Static(String),
 }
 
+impl RouterSelector {
+    /// Applies the redaction configured on the selector, if any
+    fn redact(&self, value: Option<opentelemetry::Value>) -> Option<opentelemetry::Value> {
+        let redact = match self {
+            RouterSelector::RequestHeader { redact, .. }
+            | RouterSelector::ResponseHeader { redact, .. }
+            | RouterSelector::ResponseContext { redact, .. }
+            | RouterSelector::Baggage { redact, .. }
+            | RouterSelector::Env { redact, .. } => redact,
+            RouterSelector::ResponseStatus { .. }
+            | RouterSelector::TraceId { .. }
+            | RouterSelector::Static(_) => return value,
+        };
+        match redact {
+            Some(redact) => value.map(|value| redact.apply(value)),
+            None => value,
+        }
+    }
+}
+
+impl SupergraphSelector {
+    /// Applies the redaction configured on the selector, if any
+    fn redact(&self, value: Option<opentelemetry::Value>) -> Option<opentelemetry::Value> {
+        let redact = match self {
+            SupergraphSelector::OperationName { redact, .. }
+            | SupergraphSelector::Query { redact, .. }
+            | SupergraphSelector::QueryVariable { redact, .. }
+            | SupergraphSelector::RequestHeader { redact, .. }
+            | SupergraphSelector::ResponseHeader { redact, .. }
+            | SupergraphSelector::RequestContext { redact, .. }
+            | SupergraphSelector::ResponseContext { redact, .. }
+            | SupergraphSelector::JwtClaim { redact, .. }
+            | SupergraphSelector::Baggage { redact, .. }
+            | SupergraphSelector::Env { redact, .. } => redact,
+            SupergraphSelector::OperationKind { .. } | SupergraphSelector::Static(_) => {
+                return value
+            }
+        };
+        match redact {
+            Some(redact) => value.map(|value| redact.apply(value)),
+            None => value,
+        }
+    }
+}
+
+impl SubgraphSelector {
+    /// Applies the redaction configured on the selector, if any
+    fn redact(&self, value: Option<opentelemetry::Value>) -> Option<opentelemetry::Value> {
+        let redact = match self {
+            SubgraphSelector::SubgraphOperationName { redact, .. }
+            | SubgraphSelector::SubgraphQuery { redact, .. }
+            | SubgraphSelector::SubgraphQueryVariable { redact, .. }
+            | SubgraphSelector::SubgraphResponseBody { redact, .. }
+            | SubgraphSelector::SubgraphResponseData { redact, .. }
+            | SubgraphSelector::SubgraphResponseErrors { redact, .. }
+            | SubgraphSelector::SubgraphRequestHeader { redact, .. }
+            | SubgraphSelector::SubgraphResponseHeader { redact, .. }
+            | SubgraphSelector::SupergraphOperationName { redact, .. }
+            | SubgraphSelector::SupergraphQuery { redact, .. }
+            | SubgraphSelector::SupergraphQueryVariable { redact, .. }
+            | SubgraphSelector::SupergraphRequestHeader { redact, .. }
+            | SubgraphSelector::SupergraphRequestExtension { redact, .. }
+            | SubgraphSelector::RequestContext { redact, .. }
+            | SubgraphSelector::ResponseContext { redact, .. }
+            | SubgraphSelector::JwtClaim { redact, .. }
+            | SubgraphSelector::Baggage { redact, .. }
+            | SubgraphSelector::Env { redact, .. } => redact,
+            SubgraphSelector::SubgraphOperationKind { .. }
+            | SubgraphSelector::SubgraphResponseStatus { .. }
+            | SubgraphSelector::SupergraphOperationKind { .. }
+            | SubgraphSelector::Static(_) => return value,
+        };
+        match redact {
+            Some(redact) => value.map(|value| redact.apply(value)),
+            None => value,
+        }
+    }
+}
+
 impl Selector for RouterSelector {
     type Request = router::Request;
     type Response = router::Response;
 
     fn on_request(&self, request: &router::Request) -> Option<opentelemetry::Value> {
-        match self {
+        let value = match self {
             RouterSelector::RequestHeader {
                 request_header,
                 default,
@@ -475,11 +580,12 @@ impl Selector for RouterSelector {
             RouterSelector::Static(val) => Some(val.clone().into()),
             // Related to Response
             _ => None,
-        }
+        };
+        self.redact(value)
     }
 
     fn on_response(&self, response: &router::Response) -> Option<opentelemetry::Value> {
-        match self {
+        let value = match self {
             RouterSelector::ResponseHeader {
                 response_header,
                 default,
@@ -516,7 +622,8 @@ impl Selector for RouterSelector {
                 baggage, default, ..
             } => get_baggage(baggage).or_else(|| default.maybe_to_otel_value()),
             _ => None,
-        }
+        };
+        self.redact(value)
     }
 }
 
@@ -525,7 +632,7 @@ impl Selector for SupergraphSelector {
     type Response = supergraph::Response;
 
     fn on_request(&self, request: &supergraph::Request) -> Option<opentelemetry::Value> {
-        match self {
+        let value = match self {
             SupergraphSelector::OperationName {
                 operation_name,
                 default,
@@ -591,6 +698,15 @@ impl Selector for SupergraphSelector {
                 .as_ref()
                 .and_then(|v| v.maybe_to_otel_value())
                 .or_else(|| default.maybe_to_otel_value()),
+            SupergraphSelector::JwtClaim {
+                jwt_claim, default, ..
+            } => request
+                .context
+                .get_json_value(APOLLO_AUTHENTICATION_JWT_CLAIMS)
+                .as_ref()
+                .and_then(|claims| claims.as_object()?.get(jwt_claim.as_str()))
+                .and_then(|v| v.maybe_to_otel_value())
+                .or_else(|| default.maybe_to_otel_value()),
             SupergraphSelector::Baggage {
                 baggage, default, ..
             } => get_baggage(baggage).or_else(|| default.maybe_to_otel_value()),
@@ -602,11 +718,12 @@ impl Selector for SupergraphSelector {
             SupergraphSelector::Static(val) => Some(val.clone().into()),
             // For response
             _ => None,
-        }
+        };
+        self.redact(value)
     }
 
     fn on_response(&self, response: &supergraph::Response) -> Option<opentelemetry::Value> {
-        match self {
+        let value = match self {
             SupergraphSelector::ResponseHeader {
                 response_header,
                 default,
@@ -632,7 +749,8 @@ impl Selector for SupergraphSelector {
                 .or_else(|| default.maybe_to_otel_value()),
             // For request
             _ => None,
-        }
+        };
+        self.redact(value)
     }
 }
 
@@ -641,7 +759,7 @@ impl Selector for SubgraphSelector {
     type Response = subgraph::Response;
 
     fn on_request(&self, request: &subgraph::Request) -> Option<opentelemetry::Value> {
-        match self {
+        let value = match self {
             SubgraphSelector::SubgraphOperationName {
                 subgraph_operation_name,
                 default,
@@ -748,6 +866,17 @@ impl Selector for SubgraphSelector {
                 .and_then(|h| Some(h.to_str().ok()?.to_string()))
                 .or_else(|| default.clone())
                 .map(opentelemetry::Value::from),
+            SubgraphSelector::SupergraphRequestExtension {
+                supergraph_request_extension,
+                default,
+                ..
+            } => request
+                .supergraph_request
+                .body()
+                .extensions
+                .get(&ByteString::from(supergraph_request_extension.as_str()))
+                .and_then(|v| v.maybe_to_otel_value())
+                .or_else(|| default.maybe_to_otel_value()),
             SubgraphSelector::RequestContext {
                 request_context,
                 default,
@@ -760,6 +889,15 @@ impl Selector for SubgraphSelector {
                 .as_ref()
                 .and_then(|v| v.maybe_to_otel_value())
                 .or_else(|| default.maybe_to_otel_value()),
+            SubgraphSelector::JwtClaim {
+                jwt_claim, default, ..
+            } => request
+                .context
+                .get_json_value(APOLLO_AUTHENTICATION_JWT_CLAIMS)
+                .as_ref()
+                .and_then(|claims| claims.as_object()?.get(jwt_claim.as_str()))
+                .and_then(|v| v.maybe_to_otel_value())
+                .or_else(|| default.maybe_to_otel_value()),
             SubgraphSelector::Baggage {
                 baggage: baggage_name,
                 default,
@@ -774,11 +912,12 @@ impl Selector for SubgraphSelector {
 
             // For response
             _ => None,
-        }
+        };
+        self.redact(value)
     }
 
     fn on_response(&self, response: &subgraph::Response) -> Option<opentelemetry::Value> {
-        match self {
+        let value = match self {
             SubgraphSelector::SubgraphResponseHeader {
                 subgraph_response_header,
                 default,
@@ -870,7 +1009,8 @@ impl Selector for SubgraphSelector {
                 .or_else(|| default.maybe_to_otel_value()),
             // For request
             _ => None,
-        }
+        };
+        self.redact(value)
     }
 }
 
@@ -891,6 +1031,7 @@ mod test {
     use opentelemetry::Context;
     use opentelemetry::KeyValue;
     use opentelemetry_api::StringValue;
+    use regex::Regex;
     use serde_json::json;
     use tracing::span;
     use tracing::subscriber;
@@ -899,10 +1040,12 @@ mod test {
     use crate::context::OPERATION_KIND;
     use crate::context::OPERATION_NAME;
     use crate::graphql;
+    use crate::plugins::authentication::APOLLO_AUTHENTICATION_JWT_CLAIMS;
     use crate::plugins::telemetry::config::AttributeValue;
     use crate::plugins::telemetry::config_new::selectors::OperationKind;
     use crate::plugins::telemetry::config_new::selectors::OperationName;
     use crate::plugins::telemetry::config_new::selectors::Query;
+    use crate::plugins::telemetry::config_new::selectors::Redact;
     use crate::plugins::telemetry::config_new::selectors::ResponseStatus;
     use crate::plugins::telemetry::config_new::selectors::RouterSelector;
     use crate::plugins::telemetry::config_new::selectors::SubgraphSelector;
@@ -1335,6 +1478,42 @@ mod test {
         );
     }
 
+    #[test]
+    fn supergraph_jwt_claim() {
+        let selector = SupergraphSelector::JwtClaim {
+            jwt_claim: "role".to_string(),
+            redact: None,
+            default: Some("defaulted".into()),
+        };
+        let context = crate::context::Context::new();
+        let _ = context.insert(
+            APOLLO_AUTHENTICATION_JWT_CLAIMS,
+            json!({"sub": "user", "role": "admin"}),
+        );
+        assert_eq!(
+            selector
+                .on_request(
+                    &crate::services::SupergraphRequest::fake_builder()
+                        .context(context)
+                        .build()
+                        .unwrap()
+                )
+                .unwrap(),
+            "admin".into()
+        );
+
+        assert_eq!(
+            selector
+                .on_request(
+                    &crate::services::SupergraphRequest::fake_builder()
+                        .build()
+                        .unwrap()
+                )
+                .unwrap(),
+            "defaulted".into()
+        );
+    }
+
     #[test]
     fn supergraph_response_context() {
         let selector = SupergraphSelector::ResponseContext {
@@ -2212,6 +2391,67 @@ mod test {
         );
     }
 
+    #[test]
+    fn subgraph_supergraph_request_extension() {
+        let selector = SubgraphSelector::SupergraphRequestExtension {
+            supergraph_request_extension: "key".to_string(),
+            redact: None,
+            default: Some(AttributeValue::String("default".to_string())),
+        };
+        assert_eq!(
+            selector.on_request(
+                &crate::services::SubgraphRequest::fake_builder()
+                    .supergraph_request(Arc::new(
+                        http::Request::builder()
+                            .body(
+                                graphql::Request::fake_builder()
+                                    .extension("key", "value")
+                                    .build()
+                            )
+                            .unwrap()
+                    ))
+                    .build(),
+            ),
+            Some("value".into())
+        );
+
+        assert_eq!(
+            selector.on_request(&crate::services::SubgraphRequest::fake_builder().build(),),
+            Some("default".into())
+        );
+    }
+
+    #[test]
+    fn subgraph_jwt_claim() {
+        let selector = SubgraphSelector::JwtClaim {
+            jwt_claim: "role".to_string(),
+            redact: None,
+            default: Some("defaulted".into()),
+        };
+        let context = crate::context::Context::new();
+        let _ = context.insert(
+            APOLLO_AUTHENTICATION_JWT_CLAIMS,
+            json!({"sub": "user", "role": "admin"}),
+        );
+        assert_eq!(
+            selector
+                .on_request(
+                    &crate::services::SubgraphRequest::fake_builder()
+                        .context(context)
+                        .build()
+                )
+                .unwrap(),
+            "admin".into()
+        );
+
+        assert_eq!(
+            selector
+                .on_request(&crate::services::SubgraphRequest::fake_builder().build())
+                .unwrap(),
+            "defaulted".into()
+        );
+    }
+
     #[test]
     fn subgraph_subgraph_query_variable() {
         let selector = SubgraphSelector::SubgraphQueryVariable {
@@ -2241,4 +2481,95 @@ mod test {
             Some("default".into())
         );
     }
+
+    #[test]
+    fn router_request_header_redacted() {
+        let selector: RouterSelector = serde_json::from_value(json!({
+            "request_header": "authorization",
+            "redact": {
+                "pattern": "^(Bearer )\\S+$",
+                "replacement": "${1}***"
+            }
+        }))
+        .unwrap();
+        assert_eq!(
+            selector
+                .on_request(
+                    &crate::services::RouterRequest::fake_builder()
+                        .header("authorization", "Bearer secret_token")
+                        .build()
+                        .unwrap()
+                )
+                .unwrap(),
+            "Bearer ***".into()
+        );
+    }
+
+    #[test]
+    fn supergraph_request_context_redacted() {
+        let selector = SupergraphSelector::RequestContext {
+            request_context: "context_key".to_string(),
+            redact: Some(Redact {
+                pattern: Regex::new("[0-9]{4}").unwrap(),
+                replacement: "XXXX".to_string(),
+            }),
+            default: None,
+        };
+        let context = crate::context::Context::new();
+        let _ = context.insert(
+            "context_key".to_string(),
+            "jane@example.com-1234".to_string(),
+        );
+        assert_eq!(
+            selector
+                .on_request(
+                    &crate::services::SupergraphRequest::fake_builder()
+                        .context(context.clone())
+                        .build()
+                        .unwrap()
+                )
+                .unwrap(),
+            "jane@example.com-XXXX".into()
+        );
+
+        // numbers are redacted as strings
+        let _ = context.insert("context_key".to_string(), 12345678);
+        assert_eq!(
+            selector
+                .on_request(
+                    &crate::services::SupergraphRequest::fake_builder()
+                        .context(context)
+                        .build()
+                        .unwrap()
+                )
+                .unwrap(),
+            "XXXXXXXX".into()
+        );
+    }
+
+    #[test]
+    fn redact_defaults_to_redacted_placeholder() {
+        let selector: SubgraphSelector = serde_json::from_value(json!({
+            "subgraph_request_header": "x-user-email",
+            "redact": {
+                "pattern": ".+"
+            }
+        }))
+        .unwrap();
+        assert_eq!(
+            selector
+                .on_request(
+                    &crate::services::SubgraphRequest::fake_builder()
+                        .subgraph_request(
+                            http::Request::builder()
+                                .header("x-user-email", "jane@example.com")
+                                .body(graphql::Request::fake_builder().build())
+                                .unwrap()
+                        )
+                        .build()
+                )
+                .unwrap(),
+            "[REDACTED]".into()
+        );
+    }
 }
@@ -5,6 +5,7 @@ use opentelemetry::KeyValue;
 use tracing_opentelemetry::OtelData;
 use tracing_subscriber::layer::Context;
 use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::registry::SpanRef;
 use tracing_subscriber::Layer;
 use tracing_subscriber::Registry;
 
@@ -59,50 +60,33 @@ impl DynAttributeLayer {
 pub(crate) trait DynAttribute {
     fn set_dyn_attribute(&self, key: Key, value: opentelemetry::Value);
     fn set_dyn_attributes(&self, attributes: impl IntoIterator<Item = KeyValue>);
+    /// Sets the attribute on the closest span named one of `names`, starting from this span.
+    /// Nothing is set if there is no such span.
+    fn set_dyn_attribute_on_closest(&self, names: &[&str], key: Key, value: opentelemetry::Value);
 }
 
 impl DynAttribute for ::tracing::Span {
     fn set_dyn_attribute(&self, key: Key, value: opentelemetry::Value) {
+        self.with_subscriber(move |(id, dispatch)| {
+            if let Some(reg) = dispatch.downcast_ref::<Registry>() {
+                match reg.span(id) {
+                    None => eprintln!("no spanref, this is a bug"),
+                    Some(s) => insert_dyn_attribute(&s, key, value),
+                };
+            } else {
+                ::tracing::error!("no Registry, this is a bug");
+            }
+        });
+    }
+
+    fn set_dyn_attribute_on_closest(&self, names: &[&str], key: Key, value: opentelemetry::Value) {
         self.with_subscriber(move |(id, dispatch)| {
             if let Some(reg) = dispatch.downcast_ref::<Registry>() {
                 match reg.span(id) {
                     None => eprintln!("no spanref, this is a bug"),
                     Some(s) => {
-                        if s.is_sampled() {
-                            let mut extensions = s.extensions_mut();
-                            match extensions.get_mut::<OtelData>() {
-                                Some(otel_data) => {
-                                    if otel_data.builder.attributes.is_none() {
-                                        otel_data.builder.attributes =
-                                            Some([(key, value)].into_iter().collect());
-                                    } else {
-                                        otel_data
-                                            .builder
-                                            .attributes
-                                            .as_mut()
-                                            .expect("we checked the attributes value in the condition above")
-                                            .insert(key, value);
-                                    }
-                                }
-                                None => {
-                                    // Can't use ::tracing::error! because it could create deadlock on extensions
-                                    eprintln!("no OtelData, this is a bug");
-                                }
-                            }
-                        } else {
-                            if key.as_str().starts_with(APOLLO_PRIVATE_PREFIX) {
-                                return;
-                            }
-                            let mut extensions = s.extensions_mut();
-                            match extensions.get_mut::<LogAttributes>() {
-                                Some(attributes) => {
-                                    attributes.insert(KeyValue::new(key, value));
-                                }
-                                None => {
-                                    // Can't use ::tracing::error! because it could create deadlock on extensions
-                                    eprintln!("no LogAttributes, this is a bug");
-                                }
-                            }
+                        if let Some(s) = s.scope().find(|s| names.contains(&s.name())) {
+                            insert_dyn_attribute(&s, key, value);
                         }
                     }
                 };
@@ -168,3 +152,41 @@ impl DynAttribute for ::tracing::Span {
         });
     }
 }
+
+fn insert_dyn_attribute(s: &SpanRef<'_, Registry>, key: Key, value: opentelemetry::Value) {
+    if s.is_sampled() {
+        let mut extensions = s.extensions_mut();
+        match extensions.get_mut::<OtelData>() {
+            Some(otel_data) => {
+                if otel_data.builder.attributes.is_none() {
+                    otel_data.builder.attributes = Some([(key, value)].into_iter().collect());
+                } else {
+                    otel_data
+                        .builder
+                        .attributes
+                        .as_mut()
+                        .expect("we checked the attributes value in the condition above")
+                        .insert(key, value);
+                }
+            }
+            None => {
+                // Can't use ::tracing::error! because it could create deadlock on extensions
+                eprintln!("no OtelData, this is a bug");
+            }
+        }
+    } else {
+        if key.as_str().starts_with(APOLLO_PRIVATE_PREFIX) {
+            return;
+        }
+        let mut extensions = s.extensions_mut();
+        match extensions.get_mut::<LogAttributes>() {
+            Some(attributes) => {
+                attributes.insert(KeyValue::new(key, value));
+            }
+            None => {
+                // Can't use ::tracing::error! because it could create deadlock on extensions
+                eprintln!("no LogAttributes, this is a bug");
+            }
+        }
+    }
+}
@@ -4,6 +4,7 @@ use std::collections::HashSet;
 use std::io::IsTerminal;
 use std::marker::PhantomData;
 
+use opentelemetry::sdk::logs::LoggerProvider;
 use opentelemetry::Key;
 use opentelemetry::KeyValue;
 use tracing::field;
@@ -19,11 +20,17 @@ use super::config_new::ToOtelValue;
 use super::dynamic_attribute::LogAttributes;
 use super::formatters::EventFormatter;
 use super::formatters::EXCLUDED_ATTRIBUTES;
+use super::logging::otlp::OtlpLogLayer;
+use super::logging::rolling_file::NonBlocking;
 use super::reload::IsSampled;
 use crate::plugins::telemetry::config;
 use crate::plugins::telemetry::config_new::logging::Format;
+use crate::plugins::telemetry::config_new::logging::RateLimit;
 use crate::plugins::telemetry::config_new::logging::StdOut;
+use crate::plugins::telemetry::formatters::aws::Aws;
 use crate::plugins::telemetry::formatters::filter_metric_events;
+use crate::plugins::telemetry::formatters::gelf::Gelf;
+use crate::plugins::telemetry::formatters::google::Google;
 use crate::plugins::telemetry::formatters::json::Json;
 use crate::plugins::telemetry::formatters::text::Text;
 use crate::plugins::telemetry::formatters::FilteringFormatter;
@@ -32,8 +39,9 @@ use crate::plugins::telemetry::resource::ConfigResource;
 
 pub(crate) fn create_fmt_layer(
     config: &config::Conf,
+    logger_provider: Option<LoggerProvider>,
 ) -> Box<dyn Layer<LayeredTracer> + Send + Sync> {
-    match &config.exporters.logging.stdout {
+    let stdout = match &config.exporters.logging.stdout {
         StdOut {
             enabled,
             format,
@@ -47,33 +55,112 @@ pub(crate) fn create_fmt_layer(
             } else {
                 format
             };
-            match format {
-                Format::Json(format_config) => {
-                    let format = Json::new(
-                        config.exporters.logging.common.to_resource(),
-                        format_config.clone(),
-                    );
-                    FmtLayer::new(
-                        FilteringFormatter::new(format, filter_metric_events, rate_limit),
-                        std::io::stdout,
-                    )
-                    .boxed()
-                }
-
-                Format::Text(format_config) => {
-                    let format = Text::new(
-                        config.exporters.logging.common.to_resource(),
-                        format_config.clone(),
-                    );
-                    FmtLayer::new(
-                        FilteringFormatter::new(format, filter_metric_events, rate_limit),
-                        std::io::stdout,
-                    )
-                    .boxed()
-                }
+            Some(create_format_layer(
+                config,
+                format,
+                rate_limit,
+                std::io::stdout,
+            ))
+        }
+        _ => None,
+    };
+
+    let file = &config.exporters.logging.file;
+    let file = if file.enabled {
+        match NonBlocking::new(file) {
+            Ok(writer) => Some(create_format_layer(
+                config,
+                &file.format,
+                &file.rate_limit,
+                writer,
+            )),
+            Err(err) => {
+                tracing::error!("cannot log to the file {}: {err}", file.path);
+                None
             }
         }
-        _ => NoOpLayer.boxed(),
+    } else {
+        None
+    };
+
+    let otlp = logger_provider.map(|provider| OtlpLogLayer::new(provider).boxed());
+
+    [stdout, file, otlp]
+        .into_iter()
+        .flatten()
+        .reduce(|layers, layer| layers.and_then(layer).boxed())
+        .unwrap_or_else(|| NoOpLayer.boxed())
+}
+
+fn create_format_layer<W>(
+    config: &config::Conf,
+    format: &Format,
+    rate_limit: &RateLimit,
+    make_writer: W,
+) -> Box<dyn Layer<LayeredTracer> + Send + Sync>
+where
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    match format {
+        Format::Aws(format_config) => {
+            let format = Aws::new(
+                config.exporters.logging.common.to_resource(),
+                format_config.clone(),
+            );
+            FmtLayer::new(
+                FilteringFormatter::new(format, filter_metric_events, rate_limit),
+                make_writer,
+            )
+            .boxed()
+        }
+
+        Format::Gelf(format_config) => {
+            let format = Gelf::new(
+                config.exporters.logging.common.to_resource(),
+                format_config.clone(),
+            );
+            FmtLayer::new(
+                FilteringFormatter::new(format, filter_metric_events, rate_limit),
+                make_writer,
+            )
+            .boxed()
+        }
+
+        Format::Google(format_config) => {
+            let format = Google::new(
+                config.exporters.logging.common.to_resource(),
+                format_config.clone(),
+            );
+            FmtLayer::new(
+                FilteringFormatter::new(format, filter_metric_events, rate_limit),
+                make_writer,
+            )
+            .boxed()
+        }
+
+        Format::Json(format_config) => {
+            let format = Json::new(
+                config.exporters.logging.common.to_resource(),
+                format_config.clone(),
+            );
+            FmtLayer::new(
+                FilteringFormatter::new(format, filter_metric_events, rate_limit),
+                make_writer,
+            )
+            .boxed()
+        }
+
+        Format::Text(format_config) => {
+            let format = Text::new(
+                config.exporters.logging.common.to_resource(),
+                format_config.clone(),
+            );
+            FmtLayer::new(
+                FilteringFormatter::new(format, filter_metric_events, rate_limit),
+                make_writer,
+            )
+            .boxed()
+        }
     }
 }
 
@@ -344,6 +431,18 @@ mod tests {
         info!(event_attr = "foo", "Hello from test");
     }
 
+    fn generate_nested_spans_with_duplicated_attributes() {
+        let test_span = info_span!("test", first = "one", shadowed = "outer");
+        let _enter = test_span.enter();
+        {
+            let nested_test_span = info_span!("nested_test", shadowed = "inner");
+            let _enter = nested_test_span.enter();
+
+            error!(thread.name = "main", "Hello from nested test");
+        }
+        info!("Hello from test");
+    }
+
     #[tokio::test]
     async fn test_text_logging_attributes() {
         let buff = LogBuffer::default();
@@ -414,6 +513,78 @@ mod tests {
         insta::assert_display_snapshot!(buff.to_string());
     }
 
+    #[tokio::test]
+    async fn test_google_logging_attributes_nested_spans() {
+        let buff = LogBuffer::default();
+        let format = Google::default();
+        let fmt_layer = FmtLayer::new(
+            FilteringFormatter::new(format, filter_metric_events, &RateLimit::default()),
+            buff.clone(),
+        )
+        .boxed();
+
+        ::tracing::subscriber::with_default(
+            fmt::Subscriber::new().with(fmt_layer),
+            generate_nested_spans,
+        );
+
+        insta::assert_display_snapshot!(buff.to_string());
+    }
+
+    #[tokio::test]
+    async fn test_gelf_logging_attributes_nested_spans() {
+        let buff = LogBuffer::default();
+        let format = Gelf::default();
+        let fmt_layer = FmtLayer::new(
+            FilteringFormatter::new(format, filter_metric_events, &RateLimit::default()),
+            buff.clone(),
+        )
+        .boxed();
+
+        ::tracing::subscriber::with_default(
+            fmt::Subscriber::new().with(fmt_layer),
+            generate_nested_spans,
+        );
+
+        insta::assert_display_snapshot!(buff.to_string());
+    }
+
+    #[tokio::test]
+    async fn test_gelf_logging_duplicated_attributes_nested_spans() {
+        let buff = LogBuffer::default();
+        let format = Gelf::default();
+        let fmt_layer = FmtLayer::new(
+            FilteringFormatter::new(format, filter_metric_events, &RateLimit::default()),
+            buff.clone(),
+        )
+        .boxed();
+
+        ::tracing::subscriber::with_default(
+            fmt::Subscriber::new().with(fmt_layer),
+            generate_nested_spans_with_duplicated_attributes,
+        );
+
+        insta::assert_display_snapshot!(buff.to_string());
+    }
+
+    #[tokio::test]
+    async fn test_aws_logging_attributes_nested_spans() {
+        let buff = LogBuffer::default();
+        let format = Aws::default();
+        let fmt_layer = FmtLayer::new(
+            FilteringFormatter::new(format, filter_metric_events, &RateLimit::default()),
+            buff.clone(),
+        )
+        .boxed();
+
+        ::tracing::subscriber::with_default(
+            fmt::Subscriber::new().with(fmt_layer),
+            generate_nested_spans,
+        );
+
+        insta::assert_display_snapshot!(buff.to_string());
+    }
+
     #[tokio::test]
     async fn test_json_logging_without_span_list() {
         let buff = LogBuffer::default();
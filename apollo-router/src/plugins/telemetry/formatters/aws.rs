@@ -0,0 +1,168 @@
+use std::collections::HashSet;
+use std::collections::LinkedList;
+use std::fmt;
+
+use opentelemetry::sdk::Resource;
+use opentelemetry_api::trace::TraceId;
+use serde::ser::SerializeMap;
+use serde::ser::Serializer as _;
+use serde_json::Serializer;
+use tracing_core::Event;
+use tracing_core::Subscriber;
+use tracing_serde::AsSerde;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+
+use super::get_trace_and_span_id;
+use super::json::SerializableContext;
+use super::json::SerializableResources;
+use super::json::WriteAdaptor;
+use super::to_list;
+use super::EventFormatter;
+use super::EXCLUDED_ATTRIBUTES;
+use crate::plugins::telemetry::config_new::logging::AwsFormat;
+
+/// AWS CloudWatch structured logging, see https://docs.aws.amazon.com/AmazonCloudWatch/latest/logs/CWL_AnalyzeLogData-discoverable-fields.html
+///
+/// The field names follow the JSON log format of AWS Lambda, and the trace id is in the
+/// AWS X-Ray format so that CloudWatch can link log events to their trace.
+#[derive(Debug)]
+pub(crate) struct Aws {
+    config: AwsFormat,
+    resource: LinkedList<(String, serde_json::Value)>,
+    excluded_attributes: HashSet<&'static str>,
+}
+
+impl Aws {
+    pub(crate) fn new(resource: Resource, config: AwsFormat) -> Self {
+        Self {
+            resource: to_list(resource),
+            config,
+            excluded_attributes: EXCLUDED_ATTRIBUTES.into(),
+        }
+    }
+}
+
+impl Default for Aws {
+    fn default() -> Self {
+        Self {
+            config: Default::default(),
+            resource: Default::default(),
+            excluded_attributes: EXCLUDED_ATTRIBUTES.into(),
+        }
+    }
+}
+
+/// X-Ray trace ids are `1-<8 hex digits>-<24 hex digits>`
+fn xray_trace_id(trace_id: TraceId) -> String {
+    let trace_id = trace_id.to_string();
+    format!("1-{}-{}", &trace_id[..8], &trace_id[8..])
+}
+
+impl<S> EventFormatter<S> for Aws
+where
+    S: Subscriber + for<'lookup> LookupSpan<'lookup>,
+{
+    fn format_event<W>(
+        &self,
+        ctx: &Context<'_, S>,
+        writer: &mut W,
+        event: &Event<'_>,
+    ) -> fmt::Result
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+        W: std::fmt::Write,
+    {
+        let meta = event.metadata();
+
+        let mut visit = || {
+            let mut serializer = Serializer::new(WriteAdaptor::new(writer));
+
+            let mut serializer = serializer.serialize_map(None)?;
+
+            if self.config.display_timestamp {
+                #[cfg(test)]
+                {
+                    serializer.serialize_entry("timestamp", "[timestamp]")?;
+                }
+                #[cfg(not(test))]
+                {
+                    let timestamp = time::OffsetDateTime::now_utc()
+                        .format(&time::format_description::well_known::Rfc3339)
+                        .map_err(|e| serde::ser::Error::custom(e.to_string()))?;
+                    serializer.serialize_entry("timestamp", &timestamp)?;
+                }
+            }
+
+            serializer.serialize_entry("level", &meta.level().as_serde())?;
+
+            let current_span = event
+                .parent()
+                .and_then(|id| ctx.span(id))
+                .or_else(|| ctx.lookup_current());
+
+            if let Some(ref span) = current_span {
+                if let Some((trace_id, span_id)) = get_trace_and_span_id(span) {
+                    if self.config.display_trace_id {
+                        serializer.serialize_entry("traceId", &xray_trace_id(trace_id))?;
+                    }
+                    if self.config.display_span_id {
+                        serializer.serialize_entry("spanId", &span_id.to_string())?;
+                    }
+                }
+            }
+
+            let mut visitor = tracing_serde::SerdeMapVisitor::new(serializer);
+            event.record(&mut visitor);
+
+            serializer = visitor.take_serializer()?;
+
+            if self.config.display_target {
+                serializer.serialize_entry("target", meta.target())?;
+            }
+
+            if self.config.display_filename {
+                if let Some(filename) = meta.file() {
+                    serializer.serialize_entry("filename", filename)?;
+                }
+            }
+
+            if self.config.display_line_number {
+                if let Some(line_number) = meta.line() {
+                    serializer.serialize_entry("line_number", &line_number)?;
+                }
+            }
+
+            if self.config.display_span_list && current_span.is_some() {
+                serializer.serialize_entry(
+                    "spans",
+                    &SerializableContext(ctx.lookup_current(), &self.excluded_attributes),
+                )?;
+            }
+
+            if self.config.display_resource {
+                serializer.serialize_entry("resource", &SerializableResources(&self.resource))?;
+            }
+
+            serializer.end()
+        };
+
+        visit().map_err(|_| fmt::Error)?;
+        writeln!(writer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use opentelemetry_api::trace::TraceId;
+
+    use super::xray_trace_id;
+
+    #[test]
+    fn test_xray_trace_id() {
+        assert_eq!(
+            xray_trace_id(TraceId::from_hex("5759e988bd862e3fe1be46a994272793").unwrap()),
+            "1-5759e988-bd862e3fe1be46a994272793"
+        );
+    }
+}
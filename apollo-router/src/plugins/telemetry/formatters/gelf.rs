@@ -0,0 +1,287 @@
+use std::collections::HashSet;
+use std::collections::LinkedList;
+use std::fmt;
+
+use opentelemetry::sdk::Resource;
+use opentelemetry_semantic_conventions::resource::HOST_NAME;
+use serde::ser::SerializeMap;
+use serde::ser::Serializer as _;
+use serde::Serialize;
+use serde_json::Serializer;
+use tracing_core::field::Field;
+use tracing_core::field::Visit;
+use tracing_core::Event;
+use tracing_core::Level;
+use tracing_core::Subscriber;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+
+use super::get_trace_and_span_id;
+use super::json::serialize_span_attributes;
+use super::json::WriteAdaptor;
+use super::to_list;
+use super::EventFormatter;
+use super::EXCLUDED_ATTRIBUTES;
+use crate::plugins::telemetry::config_new::logging::GelfFormat;
+
+/// Prefix of the additional fields, which is mandatory in GELF
+const ADDITIONAL_FIELD_PREFIX: &str = "_";
+
+/// Graylog Extended Log Format, see https://go2docs.graylog.org/5-0/getting_in_log_data/gelf.html
+///
+/// GELF messages cannot be nested, so the attributes of the spans and the resource are
+/// flattened into additional fields.
+#[derive(Debug)]
+pub(crate) struct Gelf {
+    config: GelfFormat,
+    host: String,
+    resource: LinkedList<(String, serde_json::Value)>,
+    excluded_attributes: HashSet<&'static str>,
+}
+
+impl Gelf {
+    pub(crate) fn new(resource: Resource, config: GelfFormat) -> Self {
+        let host = config
+            .host
+            .clone()
+            .or_else(|| resource.get(HOST_NAME).map(|host| host.to_string()))
+            .or_else(|| sys_info::hostname().ok())
+            .unwrap_or_else(|| "unknown".to_string());
+        Self {
+            host,
+            resource: to_list(resource),
+            config,
+            excluded_attributes: EXCLUDED_ATTRIBUTES.into(),
+        }
+    }
+}
+
+impl Default for Gelf {
+    fn default() -> Self {
+        Self {
+            config: Default::default(),
+            host: "unknown".to_string(),
+            resource: Default::default(),
+            excluded_attributes: EXCLUDED_ATTRIBUTES.into(),
+        }
+    }
+}
+
+/// GELF levels are syslog severities
+fn syslog_level(level: &Level) -> u8 {
+    match *level {
+        Level::TRACE | Level::DEBUG => 7,
+        Level::INFO => 6,
+        Level::WARN => 4,
+        Level::ERROR => 3,
+    }
+}
+
+impl<S> EventFormatter<S> for Gelf
+where
+    S: Subscriber + for<'lookup> LookupSpan<'lookup>,
+{
+    fn format_event<W>(
+        &self,
+        ctx: &Context<'_, S>,
+        writer: &mut W,
+        event: &Event<'_>,
+    ) -> fmt::Result
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+        W: std::fmt::Write,
+    {
+        let meta = event.metadata();
+
+        let mut visit = || {
+            let mut serializer = Serializer::new(WriteAdaptor::new(writer));
+
+            let mut serializer = serializer.serialize_map(None)?;
+
+            serializer.serialize_entry("version", "1.1")?;
+            serializer.serialize_entry("host", &self.host)?;
+
+            #[cfg(test)]
+            {
+                serializer.serialize_entry("timestamp", "[timestamp]")?;
+            }
+            #[cfg(not(test))]
+            {
+                // seconds since the epoch, with the milliseconds as decimals
+                let timestamp = time::OffsetDateTime::now_utc().unix_timestamp_nanos() / 1_000_000;
+                serializer.serialize_entry("timestamp", &(timestamp as f64 / 1000.0))?;
+            }
+
+            serializer.serialize_entry("level", &syslog_level(meta.level()))?;
+
+            let mut fields = AdditionalFields::new(&mut serializer);
+
+            let mut visitor = GelfFieldsVisitor::new(&mut fields, &self.excluded_attributes);
+            event.record(&mut visitor);
+            let has_message = visitor.finish()?;
+            if !has_message {
+                // short_message is mandatory
+                fields
+                    .serializer
+                    .serialize_entry("short_message", meta.name())?;
+            }
+
+            let current_span = event
+                .parent()
+                .and_then(|id| ctx.span(id))
+                .or_else(|| ctx.lookup_current());
+
+            if let Some(ref span) = current_span {
+                if let Some((trace_id, span_id)) = get_trace_and_span_id(span) {
+                    if self.config.display_trace_id {
+                        fields.serialize("trace_id", &trace_id.to_string())?;
+                    }
+                    if self.config.display_span_id {
+                        fields.serialize("span_id", &span_id.to_string())?;
+                    }
+                }
+            }
+
+            if self.config.display_target {
+                fields.serialize("target", meta.target())?;
+            }
+
+            if self.config.display_filename {
+                if let Some(filename) = meta.file() {
+                    fields.serialize("file", filename)?;
+                }
+            }
+
+            if self.config.display_line_number {
+                if let Some(line_number) = meta.line() {
+                    fields.serialize("line", &line_number)?;
+                }
+            }
+
+            if self.config.display_span_attributes {
+                if let Some(leaf_span) = ctx.lookup_current() {
+                    // the spans are visited from the root, so an attribute of an inner span
+                    // replaces the attribute of the same name of its parents
+                    let mut attributes = serde_json::value::Serializer.serialize_map(None)?;
+                    for span in leaf_span.scope().from_root() {
+                        serialize_span_attributes(
+                            &span,
+                            &self.excluded_attributes,
+                            "",
+                            &mut attributes,
+                        )?;
+                    }
+                    if let serde_json::Value::Object(attributes) = attributes.end()? {
+                        for (key, value) in &attributes {
+                            fields.serialize(key, value)?;
+                        }
+                    }
+                }
+            }
+
+            if self.config.display_resource {
+                for (key, value) in &self.resource {
+                    fields.serialize(key, value)?;
+                }
+            }
+
+            serializer.end()
+        };
+
+        visit().map_err(|_| fmt::Error)?;
+        writeln!(writer)
+    }
+}
+
+/// Additional fields of a message, which must be unique: the first value of a field is kept
+struct AdditionalFields<'a, M: SerializeMap> {
+    serializer: &'a mut M,
+    names: HashSet<String>,
+}
+
+impl<'a, M: SerializeMap> AdditionalFields<'a, M> {
+    fn new(serializer: &'a mut M) -> Self {
+        Self {
+            serializer,
+            names: HashSet::new(),
+        }
+    }
+
+    fn serialize<V: Serialize + ?Sized>(&mut self, name: &str, value: &V) -> Result<(), M::Error> {
+        let name = format!("{ADDITIONAL_FIELD_PREFIX}{name}");
+        if self.names.contains(&name) {
+            return Ok(());
+        }
+        self.serializer.serialize_entry(&name, value)?;
+        self.names.insert(name);
+        Ok(())
+    }
+}
+
+/// Serializes the message of an event as `short_message` and its other fields as additional fields
+struct GelfFieldsVisitor<'a, 'b, M: SerializeMap> {
+    fields: &'a mut AdditionalFields<'b, M>,
+    excluded_attributes: &'a HashSet<&'static str>,
+    has_message: bool,
+    state: Result<(), M::Error>,
+}
+
+impl<'a, 'b, M: SerializeMap> GelfFieldsVisitor<'a, 'b, M> {
+    fn new(
+        fields: &'a mut AdditionalFields<'b, M>,
+        excluded_attributes: &'a HashSet<&'static str>,
+    ) -> Self {
+        Self {
+            fields,
+            excluded_attributes,
+            has_message: false,
+            state: Ok(()),
+        }
+    }
+
+    /// Returns whether the event had a message
+    fn finish(self) -> Result<bool, M::Error> {
+        let has_message = self.has_message;
+        self.state.map(|_| has_message)
+    }
+
+    fn serialize<V: Serialize + ?Sized>(&mut self, field: &Field, value: &V) {
+        if self.state.is_err() || self.excluded_attributes.contains(field.name()) {
+            return;
+        }
+        self.state = if field.name() == "message" {
+            self.has_message = true;
+            self.fields
+                .serializer
+                .serialize_entry("short_message", value)
+        } else {
+            self.fields.serialize(field.name(), value)
+        };
+    }
+}
+
+impl<'a, 'b, M: SerializeMap> Visit for GelfFieldsVisitor<'a, 'b, M> {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.serialize(field, &value);
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.serialize(field, &value);
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.serialize(field, &value);
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.serialize(field, &value);
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.serialize(field, value);
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.serialize(field, &format!("{value:?}"));
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use opentelemetry::sdk::Resource;
+use serde::ser::SerializeMap;
+use serde::ser::Serializer as _;
+use serde_json::Serializer;
+use tracing_core::Event;
+use tracing_core::Level;
+use tracing_core::Subscriber;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+
+use super::get_trace_and_span_id;
+use super::json::SerializableContext;
+use super::json::WriteAdaptor;
+use super::to_list;
+use super::EventFormatter;
+use super::EXCLUDED_ATTRIBUTES;
+use crate::plugins::telemetry::config_new::logging::GoogleFormat;
+
+/// Google Cloud structured logging, see https://cloud.google.com/logging/docs/structured-logging
+///
+/// The fields with a special meaning for Cloud Logging are prefixed with `logging.googleapis.com/`,
+/// the other fields end up in the `jsonPayload` of the log entry.
+#[derive(Debug)]
+pub(crate) struct Google {
+    config: GoogleFormat,
+    /// The resource as log entry labels, which can only be strings
+    labels: Vec<(String, String)>,
+    excluded_attributes: HashSet<&'static str>,
+}
+
+impl Google {
+    pub(crate) fn new(resource: Resource, config: GoogleFormat) -> Self {
+        Self {
+            labels: to_list(resource)
+                .into_iter()
+                .map(|(key, value)| match value {
+                    serde_json::Value::String(value) => (key, value),
+                    value => (key, value.to_string()),
+                })
+                .collect(),
+            config,
+            excluded_attributes: EXCLUDED_ATTRIBUTES.into(),
+        }
+    }
+}
+
+impl Default for Google {
+    fn default() -> Self {
+        Self {
+            config: Default::default(),
+            labels: Default::default(),
+            excluded_attributes: EXCLUDED_ATTRIBUTES.into(),
+        }
+    }
+}
+
+/// https://cloud.google.com/logging/docs/reference/v2/rest/v2/LogEntry#logseverity
+fn severity(level: &Level) -> &'static str {
+    match *level {
+        Level::TRACE | Level::DEBUG => "DEBUG",
+        Level::INFO => "INFO",
+        Level::WARN => "WARNING",
+        Level::ERROR => "ERROR",
+    }
+}
+
+impl<S> EventFormatter<S> for Google
+where
+    S: Subscriber + for<'lookup> LookupSpan<'lookup>,
+{
+    fn format_event<W>(
+        &self,
+        ctx: &Context<'_, S>,
+        writer: &mut W,
+        event: &Event<'_>,
+    ) -> fmt::Result
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+        W: std::fmt::Write,
+    {
+        let meta = event.metadata();
+
+        let mut visit = || {
+            let mut serializer = Serializer::new(WriteAdaptor::new(writer));
+
+            let mut serializer = serializer.serialize_map(None)?;
+
+            if self.config.display_timestamp {
+                #[cfg(test)]
+                {
+                    serializer.serialize_entry("time", "[timestamp]")?;
+                }
+                #[cfg(not(test))]
+                {
+                    let timestamp = time::OffsetDateTime::now_utc()
+                        .format(&time::format_description::well_known::Rfc3339)
+                        .map_err(|e| serde::ser::Error::custom(e.to_string()))?;
+                    serializer.serialize_entry("time", &timestamp)?;
+                }
+            }
+
+            serializer.serialize_entry("severity", severity(meta.level()))?;
+
+            let current_span = event
+                .parent()
+                .and_then(|id| ctx.span(id))
+                .or_else(|| ctx.lookup_current());
+
+            if let Some(ref span) = current_span {
+                if let Some((trace_id, span_id)) = get_trace_and_span_id(span) {
+                    if self.config.display_trace_id {
+                        let trace = match &self.config.project_id {
+                            Some(project_id) => format!("projects/{project_id}/traces/{trace_id}"),
+                            None => trace_id.to_string(),
+                        };
+                        serializer.serialize_entry("logging.googleapis.com/trace", &trace)?;
+                    }
+                    if self.config.display_span_id {
+                        serializer.serialize_entry(
+                            "logging.googleapis.com/spanId",
+                            &span_id.to_string(),
+                        )?;
+                    }
+                }
+            }
+
+            let mut visitor = tracing_serde::SerdeMapVisitor::new(serializer);
+            event.record(&mut visitor);
+
+            serializer = visitor.take_serializer()?;
+
+            if self.config.display_target {
+                serializer.serialize_entry("target", meta.target())?;
+            }
+
+            if self.config.display_source_location {
+                serializer.serialize_entry(
+                    "logging.googleapis.com/sourceLocation",
+                    &SourceLocation {
+                        file: meta.file(),
+                        line: meta.line().map(|line| line.to_string()),
+                        function: meta.module_path(),
+                    },
+                )?;
+            }
+
+            if self.config.display_span_list && current_span.is_some() {
+                serializer.serialize_entry(
+                    "spans",
+                    &SerializableContext(ctx.lookup_current(), &self.excluded_attributes),
+                )?;
+            }
+
+            if self.config.display_resource && !self.labels.is_empty() {
+                serializer
+                    .serialize_entry("logging.googleapis.com/labels", &Labels(&self.labels))?;
+            }
+
+            serializer.end()
+        };
+
+        visit().map_err(|_| fmt::Error)?;
+        writeln!(writer)
+    }
+}
+
+/// https://cloud.google.com/logging/docs/reference/v2/rest/v2/LogEntry#logentrysourcelocation
+#[derive(serde::Serialize)]
+struct SourceLocation<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file: Option<&'a str>,
+    /// The line is an int64, which is represented as a string in JSON
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    function: Option<&'a str>,
+}
+
+struct Labels<'a>(&'a [(String, String)]);
+
+impl<'a> serde::ser::Serialize for Labels<'a> {
+    fn serialize<Ser>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error>
+    where
+        Ser: serde::ser::Serializer,
+    {
+        let mut serializer = serializer.serialize_map(Some(self.0.len()))?;
+        for (key, value) in self.0 {
+            serializer.serialize_entry(key, value)?;
+        }
+        serializer.end()
+    }
+}
@@ -1,3 +1,4 @@
+use std::borrow::Cow;
 use std::collections::HashSet;
 use std::collections::LinkedList;
 use std::fmt;
@@ -52,7 +53,7 @@ impl Default for Json {
     }
 }
 
-struct SerializableResources<'a>(&'a LinkedList<(String, serde_json::Value)>);
+pub(super) struct SerializableResources<'a>(pub(super) &'a LinkedList<(String, serde_json::Value)>);
 
 impl<'a> serde::ser::Serialize for SerializableResources<'a> {
     fn serialize<Ser>(&self, serializer_o: Ser) -> Result<Ser::Ok, Ser::Error>
@@ -69,7 +70,10 @@ impl<'a> serde::ser::Serialize for SerializableResources<'a> {
     }
 }
 
-struct SerializableContext<'a, 'b, Span>(Option<SpanRef<'a, Span>>, &'b HashSet<&'static str>)
+pub(super) struct SerializableContext<'a, 'b, Span>(
+    pub(super) Option<SpanRef<'a, Span>>,
+    pub(super) &'b HashSet<&'static str>,
+)
 where
     Span: Subscriber + for<'lookup> tracing_subscriber::registry::LookupSpan<'lookup>;
 
@@ -111,71 +115,96 @@ where
         Ser: serde::ser::Serializer,
     {
         let mut serializer = serializer.serialize_map(None)?;
+        serialize_span_attributes(self.0, self.1, "", &mut serializer)?;
+        serializer.serialize_entry("name", self.0.metadata().name())?;
+        serializer.end()
+    }
+}
 
-        let ext = self.0.extensions();
-
-        // Get otel attributes
-        {
-            let otel_attributes = ext
-                .get::<OtelData>()
-                .and_then(|otel_data| otel_data.builder.attributes.as_ref());
-            if let Some(otel_attributes) = otel_attributes {
-                for (key, value) in otel_attributes.iter().filter(|(key, _)| {
-                    let key_name = key.as_str();
-                    !key_name.starts_with(APOLLO_PRIVATE_PREFIX) && !self.1.contains(&key_name)
-                }) {
-                    serializer.serialize_entry(key.as_str(), &value.as_str())?;
-                }
+/// Serializes the attributes of a span as map entries, with their keys prefixed by `key_prefix`
+pub(super) fn serialize_span_attributes<Span, M>(
+    span: &SpanRef<'_, Span>,
+    excluded_attributes: &HashSet<&'static str>,
+    key_prefix: &str,
+    serializer: &mut M,
+) -> Result<(), M::Error>
+where
+    Span: for<'lookup> tracing_subscriber::registry::LookupSpan<'lookup>,
+    M: SerializeMap,
+{
+    let ext = span.extensions();
+
+    // Get otel attributes
+    {
+        let otel_attributes = ext
+            .get::<OtelData>()
+            .and_then(|otel_data| otel_data.builder.attributes.as_ref());
+        if let Some(otel_attributes) = otel_attributes {
+            for (key, value) in otel_attributes.iter().filter(|(key, _)| {
+                let key_name = key.as_str();
+                !key_name.starts_with(APOLLO_PRIVATE_PREFIX)
+                    && !excluded_attributes.contains(&key_name)
+            }) {
+                serializer.serialize_entry(&prefixed(key_prefix, key.as_str()), &value.as_str())?;
             }
         }
-        // Get custom dynamic attributes
-        {
-            let custom_attributes = ext.get::<LogAttributes>().map(|attrs| attrs.attributes());
-            if let Some(custom_attributes) = custom_attributes {
-                #[cfg(test)]
-                let custom_attributes: Vec<&opentelemetry::KeyValue> = {
-                    let mut my_custom_attributes: Vec<&opentelemetry::KeyValue> =
-                        custom_attributes.iter().collect();
-                    my_custom_attributes.sort_by_key(|kv| &kv.key);
-                    my_custom_attributes
-                };
-                for kv in custom_attributes.iter().filter(|kv| {
-                    let key_name = kv.key.as_str();
-                    !key_name.starts_with(APOLLO_PRIVATE_PREFIX) && !self.1.contains(&key_name)
-                }) {
-                    match &kv.value {
-                        Value::Bool(value) => {
-                            serializer.serialize_entry(kv.key.as_str(), value)?;
-                        }
-                        Value::I64(value) => {
-                            serializer.serialize_entry(kv.key.as_str(), value)?;
-                        }
-                        Value::F64(value) => {
-                            serializer.serialize_entry(kv.key.as_str(), value)?;
-                        }
-                        Value::String(value) => {
-                            serializer.serialize_entry(kv.key.as_str(), value.as_str())?;
-                        }
-                        Value::Array(Array::Bool(array)) => {
-                            serializer.serialize_entry(kv.key.as_str(), array)?;
-                        }
-                        Value::Array(Array::I64(array)) => {
-                            serializer.serialize_entry(kv.key.as_str(), array)?;
-                        }
-                        Value::Array(Array::F64(array)) => {
-                            serializer.serialize_entry(kv.key.as_str(), array)?;
-                        }
-                        Value::Array(Array::String(array)) => {
-                            let array = array.iter().map(|a| a.as_str()).collect::<Vec<_>>();
-                            serializer.serialize_entry(kv.key.as_str(), &array)?;
-                        }
+    }
+    // Get custom dynamic attributes
+    {
+        let custom_attributes = ext.get::<LogAttributes>().map(|attrs| attrs.attributes());
+        if let Some(custom_attributes) = custom_attributes {
+            #[cfg(test)]
+            let custom_attributes: Vec<&opentelemetry::KeyValue> = {
+                let mut my_custom_attributes: Vec<&opentelemetry::KeyValue> =
+                    custom_attributes.iter().collect();
+                my_custom_attributes.sort_by_key(|kv| &kv.key);
+                my_custom_attributes
+            };
+            for kv in custom_attributes.iter().filter(|kv| {
+                let key_name = kv.key.as_str();
+                !key_name.starts_with(APOLLO_PRIVATE_PREFIX)
+                    && !excluded_attributes.contains(&key_name)
+            }) {
+                let key = prefixed(key_prefix, kv.key.as_str());
+                match &kv.value {
+                    Value::Bool(value) => {
+                        serializer.serialize_entry(&key, value)?;
+                    }
+                    Value::I64(value) => {
+                        serializer.serialize_entry(&key, value)?;
+                    }
+                    Value::F64(value) => {
+                        serializer.serialize_entry(&key, value)?;
+                    }
+                    Value::String(value) => {
+                        serializer.serialize_entry(&key, value.as_str())?;
+                    }
+                    Value::Array(Array::Bool(array)) => {
+                        serializer.serialize_entry(&key, array)?;
+                    }
+                    Value::Array(Array::I64(array)) => {
+                        serializer.serialize_entry(&key, array)?;
+                    }
+                    Value::Array(Array::F64(array)) => {
+                        serializer.serialize_entry(&key, array)?;
+                    }
+                    Value::Array(Array::String(array)) => {
+                        let array = array.iter().map(|a| a.as_str()).collect::<Vec<_>>();
+                        serializer.serialize_entry(&key, &array)?;
                     }
                 }
             }
         }
+    }
 
-        serializer.serialize_entry("name", self.0.metadata().name())?;
-        serializer.end()
+    Ok(())
+}
+
+fn prefixed<'a>(prefix: &str, key: &'a str) -> Cow<'a, str> {
+    if prefix.is_empty() {
+        Cow::Borrowed(key)
+    } else {
+        Cow::Owned(format!("{prefix}{key}"))
     }
 }
 
@@ -325,12 +354,12 @@ fn extract_dd_trace_id<'a, 'b, T: LookupSpan<'a>>(span: &SpanRef<'a, T>) -> Opti
     dd_trace_id
 }
 
-struct WriteAdaptor<'a> {
+pub(super) struct WriteAdaptor<'a> {
     fmt_write: &'a mut dyn fmt::Write,
 }
 
 impl<'a> WriteAdaptor<'a> {
-    fn new(fmt_write: &'a mut dyn fmt::Write) -> Self {
+    pub(super) fn new(fmt_write: &'a mut dyn fmt::Write) -> Self {
         Self { fmt_write }
     }
 }
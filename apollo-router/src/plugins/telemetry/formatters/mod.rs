@@ -1,4 +1,7 @@
 //! Our formatters and visitors used for logging
+pub(crate) mod aws;
+pub(crate) mod gelf;
+pub(crate) mod google;
 pub(crate) mod json;
 pub(crate) mod text;
 
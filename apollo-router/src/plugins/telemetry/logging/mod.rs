@@ -1,4 +1,7 @@
 //TODO move telemetry logging functionality to this file
+pub(crate) mod otlp;
+pub(crate) mod rolling_file;
+
 #[cfg(test)]
 mod test {
     use std::any::TypeId;
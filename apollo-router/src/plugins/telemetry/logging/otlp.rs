@@ -0,0 +1,264 @@
+//! Export of log events as OTLP log records
+use std::time::SystemTime;
+
+use opentelemetry::logs::AnyValue;
+use opentelemetry::logs::LogRecord;
+use opentelemetry::logs::Logger as _;
+use opentelemetry::logs::LoggerProvider as _;
+use opentelemetry::logs::Severity;
+use opentelemetry::sdk::logs::Config;
+use opentelemetry::sdk::logs::Logger;
+use opentelemetry::sdk::logs::LoggerProvider;
+use opentelemetry::trace::SpanContext;
+use opentelemetry::trace::TraceFlags;
+use opentelemetry::trace::TraceState;
+use opentelemetry::Array;
+use opentelemetry::Key;
+use opentelemetry::Value;
+use opentelemetry_otlp::LogExporterBuilder;
+use tower::BoxError;
+use tracing_core::field::Field;
+use tracing_core::field::Visit;
+use tracing_core::Event;
+use tracing_core::Level;
+use tracing_core::Subscriber;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+use crate::plugins::telemetry::config;
+use crate::plugins::telemetry::dynamic_attribute::LogAttributes;
+use crate::plugins::telemetry::formatters::filter_metric_events;
+use crate::plugins::telemetry::formatters::get_trace_and_span_id;
+use crate::plugins::telemetry::formatters::EXCLUDED_ATTRIBUTES;
+use crate::plugins::telemetry::resource::ConfigResource;
+use crate::plugins::telemetry::Telemetry;
+
+/// Creates the logger provider exporting log records to `telemetry.exporters.logging.otlp`, if enabled
+pub(crate) fn create_logger_provider(
+    config: &config::Conf,
+) -> Result<Option<LoggerProvider>, BoxError> {
+    let logging = &config.exporters.logging;
+    if !logging.otlp.enabled {
+        return Ok(None);
+    }
+
+    tracing::info!("Configuring Otlp logging: {}", logging.otlp.batch_processor);
+    let exporter: LogExporterBuilder = logging.otlp.exporter()?;
+    Ok(Some(
+        LoggerProvider::builder()
+            .with_config(Config::default().with_resource(logging.common.to_resource()))
+            .with_batch_exporter(
+                exporter.build_log_exporter()?,
+                opentelemetry::runtime::Tokio,
+            )
+            .build(),
+    ))
+}
+
+/// Sends log events as OTLP log records, with the trace and span ids of the current span
+pub(crate) struct OtlpLogLayer {
+    logger: Logger,
+    // Loggers only keep a weak reference to their provider, so it has to outlive them
+    provider: Option<LoggerProvider>,
+}
+
+impl OtlpLogLayer {
+    pub(crate) fn new(provider: LoggerProvider) -> Self {
+        Self {
+            logger: provider.logger("apollo-router"),
+            provider: Some(provider),
+        }
+    }
+}
+
+impl Drop for OtlpLogLayer {
+    fn drop(&mut self) {
+        // Dropping the provider flushes the pending log records, which blocks
+        if let Some(provider) = self.provider.take() {
+            Telemetry::checked_spawn_task(Box::new(move || drop(provider)));
+        }
+    }
+}
+
+impl<S> Layer<S> for OtlpLogLayer
+where
+    S: Subscriber + for<'lookup> LookupSpan<'lookup>,
+{
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        if !filter_metric_events(event) {
+            return;
+        }
+
+        let metadata = event.metadata();
+        let mut visitor = LogRecordVisitor::default();
+        event.record(&mut visitor);
+
+        let now = SystemTime::now();
+        let mut record = LogRecord::builder()
+            .with_timestamp(now)
+            .with_observed_timestamp(now)
+            .with_severity_number(severity(metadata.level()))
+            .with_severity_text(metadata.level().as_str())
+            .with_attribute(
+                Key::from_static_str("target"),
+                AnyValue::String(metadata.target().into()),
+            );
+        if let Some(body) = visitor.body {
+            record = record.with_body(body);
+        }
+        for (key, value) in visitor.attributes {
+            record = record.with_attribute(key, value);
+        }
+
+        let current_span = event
+            .parent()
+            .and_then(|id| ctx.span(id))
+            .or_else(|| ctx.lookup_current());
+        if let Some(span) = current_span {
+            if let Some((trace_id, span_id)) = get_trace_and_span_id(&span) {
+                record = record.with_span_context(&SpanContext::new(
+                    trace_id,
+                    span_id,
+                    TraceFlags::default(),
+                    false,
+                    TraceState::default(),
+                ));
+            }
+            if let Some(attributes) = span.extensions().get::<LogAttributes>() {
+                for attribute in attributes.attributes() {
+                    record = record.with_attribute(
+                        attribute.key.clone(),
+                        to_any_value(attribute.value.clone()),
+                    );
+                }
+            }
+        }
+
+        self.logger.emit(record.build());
+    }
+}
+
+fn severity(level: &Level) -> Severity {
+    match *level {
+        Level::TRACE => Severity::Trace,
+        Level::DEBUG => Severity::Debug,
+        Level::INFO => Severity::Info,
+        Level::WARN => Severity::Warn,
+        Level::ERROR => Severity::Error,
+    }
+}
+
+fn to_any_value(value: Value) -> AnyValue {
+    match value {
+        Value::Bool(value) => AnyValue::Boolean(value),
+        Value::I64(value) => AnyValue::Int(value),
+        Value::F64(value) => AnyValue::Double(value),
+        Value::String(value) => AnyValue::String(value),
+        Value::Array(Array::Bool(values)) => {
+            AnyValue::ListAny(values.into_iter().map(AnyValue::Boolean).collect())
+        }
+        Value::Array(Array::I64(values)) => {
+            AnyValue::ListAny(values.into_iter().map(AnyValue::Int).collect())
+        }
+        Value::Array(Array::F64(values)) => {
+            AnyValue::ListAny(values.into_iter().map(AnyValue::Double).collect())
+        }
+        Value::Array(Array::String(values)) => {
+            AnyValue::ListAny(values.into_iter().map(AnyValue::String).collect())
+        }
+    }
+}
+
+/// Collects the message of an event as log record body, and its other fields as attributes
+#[derive(Default)]
+struct LogRecordVisitor {
+    body: Option<AnyValue>,
+    attributes: Vec<(Key, AnyValue)>,
+}
+
+impl LogRecordVisitor {
+    fn record(&mut self, field: &Field, value: AnyValue) {
+        if field.name() == "message" {
+            self.body = Some(value);
+        } else if !EXCLUDED_ATTRIBUTES.contains(&field.name()) {
+            self.attributes
+                .push((Key::from_static_str(field.name()), value));
+        }
+    }
+}
+
+impl Visit for LogRecordVisitor {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.record(field, AnyValue::Double(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.record(field, AnyValue::Int(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        match i64::try_from(value) {
+            Ok(value) => self.record(field, AnyValue::Int(value)),
+            Err(_) => self.record(field, AnyValue::String(value.to_string().into())),
+        }
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.record(field, AnyValue::Boolean(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.record(field, AnyValue::String(value.to_string().into()));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.record(field, AnyValue::String(format!("{value:?}").into()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::Mutex;
+
+    use opentelemetry::logs::LogResult;
+    use opentelemetry::sdk::export::logs::LogData;
+    use opentelemetry::sdk::export::logs::LogExporter;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::*;
+
+    #[derive(Debug, Clone, Default)]
+    struct TestExporter(Arc<Mutex<Vec<LogData>>>);
+
+    #[async_trait::async_trait]
+    impl LogExporter for TestExporter {
+        async fn export(&mut self, batch: Vec<LogData>) -> LogResult<()> {
+            self.0.lock().unwrap().extend(batch);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn exports_events_as_log_records() {
+        let exporter = TestExporter::default();
+        let provider = LoggerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let subscriber = tracing_subscriber::registry().with(OtlpLogLayer::new(provider));
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::warn!(attr = "value", "hello");
+            // metrics are not logs
+            tracing::info!(monotonic_counter.test = 1u64);
+        });
+
+        let logs = exporter.0.lock().unwrap();
+        assert_eq!(logs.len(), 1);
+        let record = &logs[0].record;
+        assert_eq!(record.body, Some(AnyValue::String("hello".into())));
+        assert_eq!(record.severity_number, Some(Severity::Warn));
+        assert_eq!(record.severity_text.as_deref(), Some("WARN"));
+    }
+}
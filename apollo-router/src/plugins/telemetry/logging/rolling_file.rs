@@ -0,0 +1,408 @@
+//! Log file writer with time and size based rollover
+use std::fs;
+use std::io;
+use std::io::BufWriter;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::SyncSender;
+use std::sync::mpsc::TrySendError;
+
+use time::OffsetDateTime;
+use time::Time;
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::plugins::telemetry::config_new::logging::File;
+use crate::plugins::telemetry::config_new::logging::Rollover;
+
+/// Maximum number of log lines waiting to be written, after which new lines are dropped
+const BUFFERED_LINES_LIMIT: usize = 128_000;
+
+/// Writes to a log file, rolling it over when the configured period elapses or when it gets
+/// too large.
+///
+/// The current logs are always written to the configured path. On rollover, the file is
+/// renamed with the rollover time as suffix, then the oldest rolled over files are deleted
+/// so that at most `max_files` are kept.
+pub(crate) struct RollingFile {
+    path: PathBuf,
+    rollover: Rollover,
+    max_size: Option<u64>,
+    max_files: Option<usize>,
+    file: BufWriter<fs::File>,
+    size: u64,
+    next_rollover: Option<OffsetDateTime>,
+}
+
+impl RollingFile {
+    pub(crate) fn new(
+        path: impl Into<PathBuf>,
+        rollover: Rollover,
+        max_size: Option<u64>,
+        max_files: Option<usize>,
+    ) -> io::Result<Self> {
+        let path = path.into();
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent)?;
+        }
+        let file = open(&path)?;
+        let size = file.metadata()?.len();
+
+        Ok(Self {
+            next_rollover: next_rollover(&rollover, OffsetDateTime::now_utc()),
+            path,
+            rollover,
+            max_size,
+            max_files,
+            file: BufWriter::new(file),
+            size,
+        })
+    }
+
+    fn write_line(&mut self, line: &[u8]) -> io::Result<()> {
+        let now = OffsetDateTime::now_utc();
+        let period_elapsed = self.next_rollover.map_or(false, |next| now >= next);
+        let too_large = self.max_size.map_or(false, |max_size| {
+            self.size > 0 && self.size + line.len() as u64 > max_size
+        });
+        if period_elapsed || too_large {
+            self.roll_over(now)?;
+        }
+
+        self.file.write_all(line)?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+
+    fn roll_over(&mut self, now: OffsetDateTime) -> io::Result<()> {
+        self.file.flush()?;
+        fs::rename(&self.path, self.rolled_over_path(now))?;
+        self.file = BufWriter::new(open(&self.path)?);
+        self.size = 0;
+        self.next_rollover = next_rollover(&self.rollover, now);
+        if let Some(max_files) = self.max_files {
+            self.delete_oldest_files(max_files)?;
+        }
+        Ok(())
+    }
+
+    /// `<path>.<date>T<time>`, with an index if several files are rolled over in the same second
+    fn rolled_over_path(&self, now: OffsetDateTime) -> PathBuf {
+        let prefix = format!(
+            "{}.{:04}-{:02}-{:02}T{:02}-{:02}-{:02}",
+            self.path.display(),
+            now.year(),
+            now.month() as u8,
+            now.day(),
+            now.hour(),
+            now.minute(),
+            now.second()
+        );
+        let mut path = PathBuf::from(&prefix);
+        let mut index = 1;
+        while path.exists() {
+            path = PathBuf::from(format!("{prefix}.{index}"));
+            index += 1;
+        }
+        path
+    }
+
+    fn delete_oldest_files(&self, max_files: usize) -> io::Result<()> {
+        let mut rolled_over = self.rolled_over_files()?;
+        if rolled_over.len() > max_files {
+            rolled_over.sort();
+            for (_, path) in &rolled_over[..rolled_over.len() - max_files] {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The files renamed by [`Self::rolled_over_path`], with their rollover time and index.
+    /// Other files sharing the log file name as prefix are left alone.
+    fn rolled_over_files(&self) -> io::Result<Vec<((String, u64), PathBuf)>> {
+        let directory = match self.path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let prefix = match self.path.file_name() {
+            Some(file_name) => format!("{}.", file_name.to_string_lossy()),
+            None => return Ok(Vec::new()),
+        };
+        let mut files = Vec::new();
+        for entry in fs::read_dir(directory)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_file() {
+                continue;
+            }
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            if let Some(suffix) = file_name
+                .strip_prefix(&prefix)
+                .and_then(parse_rolled_over_suffix)
+            {
+                files.push((suffix, entry.path()));
+            }
+        }
+        Ok(files)
+    }
+}
+
+/// Parses the `<date>T<time>` suffix of a rolled over file, with its optional `.<index>`
+fn parse_rolled_over_suffix(suffix: &str) -> Option<(String, u64)> {
+    let (timestamp, index) = match suffix.split_once('.') {
+        Some((timestamp, index)) if is_digits(index) => (timestamp, index.parse().ok()?),
+        Some(_) => return None,
+        None => (suffix, 0),
+    };
+    // YYYY-MM-DDTHH-MM-SS
+    let is_timestamp = timestamp.len() == 19
+        && timestamp
+            .char_indices()
+            .all(|(position, c)| match position {
+                4 | 7 | 13 | 16 => c == '-',
+                10 => c == 'T',
+                _ => c.is_ascii_digit(),
+            });
+    is_timestamp.then(|| (timestamp.to_string(), index))
+}
+
+fn is_digits(value: &str) -> bool {
+    !value.is_empty() && value.bytes().all(|byte| byte.is_ascii_digit())
+}
+
+fn open(path: &Path) -> io::Result<fs::File> {
+    fs::OpenOptions::new().create(true).append(true).open(path)
+}
+
+fn next_rollover(rollover: &Rollover, now: OffsetDateTime) -> Option<OffsetDateTime> {
+    match rollover {
+        Rollover::Hourly => Some(
+            now.replace_time(Time::from_hms(now.hour(), 0, 0).expect("valid time"))
+                + time::Duration::HOUR,
+        ),
+        Rollover::Daily => Some(now.replace_time(Time::MIDNIGHT) + time::Duration::DAY),
+        Rollover::Never => None,
+    }
+}
+
+/// Sends log lines to a thread writing them to a [`RollingFile`], so that logging never blocks
+/// on the file system. If the thread cannot keep up, new lines are dropped.
+///
+/// The thread stops once every clone of the writer is dropped, after writing the remaining lines.
+#[derive(Clone)]
+pub(crate) struct NonBlocking {
+    sender: SyncSender<Vec<u8>>,
+}
+
+impl NonBlocking {
+    pub(crate) fn new(config: &File) -> io::Result<Self> {
+        let file = RollingFile::new(
+            &config.path,
+            config.rollover.clone(),
+            config.max_size.map(|max_size| max_size.as_u64()),
+            config.max_files,
+        )?;
+        let (sender, receiver) = mpsc::sync_channel(BUFFERED_LINES_LIMIT);
+        std::thread::Builder::new()
+            .name("router-log-file".to_string())
+            .spawn(move || write_lines(file, receiver))?;
+        Ok(Self { sender })
+    }
+}
+
+fn write_lines(mut file: RollingFile, receiver: Receiver<Vec<u8>>) {
+    while let Ok(line) = receiver.recv() {
+        if let Err(err) = file.write_line(&line) {
+            eprintln!("cannot write to the log file: {err}");
+        }
+        // write everything that is waiting before flushing
+        while let Ok(line) = receiver.try_recv() {
+            if let Err(err) = file.write_line(&line) {
+                eprintln!("cannot write to the log file: {err}");
+            }
+        }
+        if let Err(err) = file.flush() {
+            eprintln!("cannot flush the log file: {err}");
+        }
+    }
+}
+
+impl Write for NonBlocking {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.sender.try_send(buf.to_vec()) {
+            Ok(()) | Err(TrySendError::Full(_)) => Ok(buf.len()),
+            Err(TrySendError::Disconnected(_)) => Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "the log file writer stopped",
+            )),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for NonBlocking {
+    type Writer = NonBlocking;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log_files(directory: &Path) -> Vec<String> {
+        let mut files: Vec<String> = fs::read_dir(directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn rolls_over_when_max_size_is_reached() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("router.log");
+        let mut file = RollingFile::new(&path, Rollover::Never, Some(10), Some(2)).unwrap();
+
+        for line in ["first\n", "second\n", "third\n", "fourth\n"] {
+            file.write_line(line.as_bytes()).unwrap();
+        }
+        file.flush().unwrap();
+
+        let files = log_files(directory.path());
+        // the first rolled over file was deleted
+        assert_eq!(files.len(), 3);
+        assert_eq!(files[0], "router.log");
+        assert!(files[1].starts_with("router.log."));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "fourth\n");
+        assert_eq!(
+            fs::read_to_string(directory.path().join(&files[1])).unwrap(),
+            "second\n"
+        );
+        assert_eq!(
+            fs::read_to_string(directory.path().join(&files[2])).unwrap(),
+            "third\n"
+        );
+    }
+
+    #[test]
+    fn only_deletes_rolled_over_files() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("router.log");
+        for sibling in [
+            "router.log.bak",
+            "router.log.gz",
+            "router.log.2020-01-01T00-00-00.old",
+        ] {
+            fs::write(directory.path().join(sibling), "keep\n").unwrap();
+        }
+        fs::write(
+            directory.path().join("router.log.2020-01-01T00-00-00"),
+            "old\n",
+        )
+        .unwrap();
+        let mut file = RollingFile::new(&path, Rollover::Never, Some(10), Some(1)).unwrap();
+
+        for line in ["first\n", "second\n"] {
+            file.write_line(line.as_bytes()).unwrap();
+        }
+        file.flush().unwrap();
+
+        let files = log_files(directory.path());
+        // the oldest rolled over file was deleted, the other files are kept
+        assert_eq!(files.len(), 5);
+        assert!(!files.contains(&"router.log.2020-01-01T00-00-00".to_string()));
+        assert!(files.contains(&"router.log.bak".to_string()));
+        assert!(files.contains(&"router.log.gz".to_string()));
+        assert!(files.contains(&"router.log.2020-01-01T00-00-00.old".to_string()));
+    }
+
+    #[test]
+    fn parses_rolled_over_suffixes() {
+        assert_eq!(
+            parse_rolled_over_suffix("2023-11-14T22-13-20"),
+            Some(("2023-11-14T22-13-20".to_string(), 0))
+        );
+        assert_eq!(
+            parse_rolled_over_suffix("2023-11-14T22-13-20.10"),
+            Some(("2023-11-14T22-13-20".to_string(), 10))
+        );
+        assert_eq!(parse_rolled_over_suffix("bak"), None);
+        assert_eq!(parse_rolled_over_suffix("2023-11-14T22-13-20.gz"), None);
+        assert_eq!(parse_rolled_over_suffix("2023-11-14T22-13-20."), None);
+        assert_eq!(parse_rolled_over_suffix("2023-11-14 22:13:20"), None);
+    }
+
+    #[test]
+    fn rolls_over_when_period_elapses() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("router.log");
+        let mut file = RollingFile::new(&path, Rollover::Hourly, None, None).unwrap();
+
+        file.write_line(b"first\n").unwrap();
+        file.next_rollover = Some(OffsetDateTime::now_utc() - time::Duration::SECOND);
+        file.write_line(b"second\n").unwrap();
+        file.flush().unwrap();
+
+        let files = log_files(directory.path());
+        assert_eq!(files.len(), 2);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second\n");
+        assert_eq!(
+            fs::read_to_string(directory.path().join(&files[1])).unwrap(),
+            "first\n"
+        );
+        assert!(file.next_rollover.unwrap() > OffsetDateTime::now_utc());
+    }
+
+    #[test]
+    fn next_rollover_is_at_the_start_of_the_next_period() {
+        let now = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        // 2023-11-14T22:13:20Z
+        assert_eq!(
+            next_rollover(&Rollover::Hourly, now),
+            Some(OffsetDateTime::from_unix_timestamp(1_700_002_800).unwrap())
+        );
+        assert_eq!(
+            next_rollover(&Rollover::Daily, now),
+            Some(OffsetDateTime::from_unix_timestamp(1_700_006_400).unwrap())
+        );
+        assert_eq!(next_rollover(&Rollover::Never, now), None);
+    }
+
+    #[test]
+    fn non_blocking_writes_in_background() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("logs").join("router.log");
+        let config = File {
+            enabled: true,
+            path: path.to_string_lossy().to_string(),
+            ..Default::default()
+        };
+        let mut writer = NonBlocking::new(&config).unwrap();
+        writer.write_all(b"line\n").unwrap();
+        drop(writer);
+
+        for _ in 0..50 {
+            if fs::read_to_string(&path).unwrap_or_default() == "line\n" {
+                return;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        panic!("the log line was not written");
+    }
+}
@@ -0,0 +1,565 @@
+//! Usage of the fields of the schema, as counters exported with the other router metrics.
+//!
+//! Apollo Studio computes field usage from the usage reports, this gives the same information to
+//! teams using Prometheus or an OTLP backend, for example to find the clients still using
+//! deprecated fields.
+
+use std::collections::BTreeSet;
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+use apollo_compiler::schema::ExtendedType;
+use opentelemetry::KeyValue;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json_bytes::Value;
+use tower::BoxError;
+
+use crate::graphql;
+use crate::spec::Query;
+use crate::spec::Schema;
+use crate::spec::Selection;
+use crate::Configuration;
+
+/// Value of the attributes that went over the cardinality limits
+const OTHER: &str = "__other__";
+
+/// Field usage metrics configuration
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields, default)]
+pub(crate) struct Config {
+    /// Set to true to count the fields requested by operations and returned in responses
+    pub(crate) enabled: bool,
+    /// Only count the fields marked as `@deprecated` in the schema
+    pub(crate) deprecated_only: bool,
+    /// Add the client name as the `client.name` attribute
+    pub(crate) client_name: bool,
+    /// Maximum number of distinct fields in the metrics, the other fields are reported as `__other__`
+    pub(crate) max_fields: usize,
+    /// Maximum number of distinct client names in the metrics, the other clients are reported as `__other__`
+    pub(crate) max_client_names: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            deprecated_only: false,
+            client_name: true,
+            max_fields: 1000,
+            max_client_names: 100,
+        }
+    }
+}
+
+pub(crate) struct FieldUsage {
+    config: Config,
+    /// The supergraph schema, to find the fragments that apply to the returned objects
+    schema: Schema,
+    /// Coordinates (`Type.field`) of the deprecated fields of the schema
+    deprecated_fields: HashSet<String>,
+    /// Attribute values already used, to bound the cardinality of the metrics
+    seen: Mutex<Seen>,
+}
+
+#[derive(Default)]
+struct Seen {
+    fields: HashSet<String>,
+    client_names: HashSet<String>,
+}
+
+impl FieldUsage {
+    pub(crate) fn new(config: &Config, supergraph_sdl: &str) -> Result<Self, BoxError> {
+        let schema = Schema::parse(supergraph_sdl, &Configuration::default())?;
+        let deprecated_fields = schema
+            .definitions
+            .types
+            .iter()
+            .flat_map(|(type_name, ty)| {
+                let fields = match ty {
+                    ExtendedType::Object(object) => Some(&object.fields),
+                    ExtendedType::Interface(interface) => Some(&interface.fields),
+                    _ => None,
+                };
+                fields
+                    .into_iter()
+                    .flatten()
+                    .filter(|(_, field)| field.directives.has("deprecated"))
+                    .map(move |(field_name, _)| format!("{type_name}.{field_name}"))
+            })
+            .collect();
+
+        Ok(Self {
+            config: config.clone(),
+            schema,
+            deprecated_fields,
+            seen: Default::default(),
+        })
+    }
+
+    /// Counts the fields selected by an operation
+    pub(crate) fn record_requested(
+        &self,
+        query: &Query,
+        operation_name: Option<&str>,
+        client_name: Option<&str>,
+    ) {
+        for attributes in self.attributes(requested_fields(query, operation_name), client_name) {
+            u64_counter!(
+                "apollo.router.operations.fields.requested",
+                "Number of operations requesting a field",
+                1,
+                attributes
+            );
+        }
+    }
+
+    /// Counts the fields with a value in a response
+    pub(crate) fn record_returned(
+        &self,
+        query: &Query,
+        operation_name: Option<&str>,
+        response: &graphql::Response,
+        client_name: Option<&str>,
+    ) {
+        let fields = returned_fields(&self.schema, query, operation_name, response);
+        for attributes in self.attributes(fields, client_name) {
+            u64_counter!(
+                "apollo.router.operations.fields.returned",
+                "Number of responses returning a field",
+                1,
+                attributes
+            );
+        }
+    }
+
+    fn attributes(
+        &self,
+        fields: BTreeSet<String>,
+        client_name: Option<&str>,
+    ) -> Vec<Vec<KeyValue>> {
+        let mut seen = self.seen.lock().expect("lock poisoned");
+        let client_name = self.config.client_name.then(|| {
+            let client_name = client_name.unwrap_or_default();
+            bounded(
+                &mut seen.client_names,
+                self.config.max_client_names,
+                client_name,
+            )
+        });
+
+        fields
+            .into_iter()
+            .filter_map(|field| {
+                let deprecated = self.deprecated_fields.contains(&field);
+                if self.config.deprecated_only && !deprecated {
+                    return None;
+                }
+                let mut attributes = vec![
+                    KeyValue::new(
+                        "graphql.field.coordinate",
+                        bounded(&mut seen.fields, self.config.max_fields, &field),
+                    ),
+                    KeyValue::new("graphql.field.deprecated", deprecated),
+                ];
+                if let Some(client_name) = &client_name {
+                    attributes.push(KeyValue::new("client.name", client_name.clone()));
+                }
+                Some(attributes)
+            })
+            .collect()
+    }
+}
+
+/// Returns the value if it was seen before or if there is room for it, `__other__` otherwise
+fn bounded(seen: &mut HashSet<String>, max: usize, value: &str) -> String {
+    if seen.contains(value) {
+        value.to_string()
+    } else if seen.len() < max {
+        seen.insert(value.to_string());
+        value.to_string()
+    } else {
+        OTHER.to_string()
+    }
+}
+
+/// Coordinates (`Type.field`) of the fields selected by an operation.
+///
+/// The type is the one the field is selected on, which is an interface for the fields selected
+/// on an interface.
+pub(crate) fn requested_fields(query: &Query, operation_name: Option<&str>) -> BTreeSet<String> {
+    let mut fields = BTreeSet::new();
+    if let Some(operation) = query.operation(operation_name) {
+        collect_requested(
+            query,
+            &operation.type_name,
+            &operation.selection_set,
+            &mut fields,
+        );
+    }
+    fields
+}
+
+fn collect_requested(
+    query: &Query,
+    parent_type: &str,
+    selection_set: &[Selection],
+    fields: &mut BTreeSet<String>,
+) {
+    for selection in selection_set {
+        match selection {
+            Selection::Field {
+                name,
+                selection_set,
+                field_type,
+                ..
+            } => {
+                // introspection fields are not part of the schema
+                if name.as_str().starts_with("__") {
+                    continue;
+                }
+                fields.insert(format!("{parent_type}.{}", name.as_str()));
+                if let Some(selection_set) = selection_set {
+                    collect_requested(
+                        query,
+                        field_type.0.inner_named_type().as_str(),
+                        selection_set,
+                        fields,
+                    );
+                }
+            }
+            Selection::InlineFragment {
+                type_condition,
+                selection_set,
+                ..
+            } => collect_requested(query, type_condition, selection_set, fields),
+            Selection::FragmentSpread { name, .. } => {
+                if let Some(fragment) = query.fragments.get(name) {
+                    collect_requested(
+                        query,
+                        &fragment.type_condition,
+                        &fragment.selection_set,
+                        fields,
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Coordinates (`Type.field`) of the fields with a non null value in a response.
+///
+/// Only the primary response is traversed: the fields of deferred fragments are counted as
+/// requested, but not as returned.
+pub(crate) fn returned_fields(
+    schema: &Schema,
+    query: &Query,
+    operation_name: Option<&str>,
+    response: &graphql::Response,
+) -> BTreeSet<String> {
+    let mut fields = BTreeSet::new();
+    if let (Some(operation), Some(data), None) = (
+        query.operation(operation_name),
+        response.data.as_ref(),
+        response.path.as_ref(),
+    ) {
+        collect_returned(
+            schema,
+            query,
+            &operation.type_name,
+            &operation.selection_set,
+            data,
+            &mut fields,
+        );
+    }
+    fields
+}
+
+fn collect_returned(
+    schema: &Schema,
+    query: &Query,
+    parent_type: &str,
+    selection_set: &[Selection],
+    data: &Value,
+    fields: &mut BTreeSet<String>,
+) {
+    let object = match data {
+        Value::Object(object) => object,
+        Value::Array(items) => {
+            for item in items {
+                collect_returned(schema, query, parent_type, selection_set, item, fields);
+            }
+            return;
+        }
+        _ => return,
+    };
+    // Without `__typename`, the fragments are assumed to apply
+    let applies = |type_condition: &str| match object.get("__typename").and_then(Value::as_str) {
+        Some(typename) => typename == type_condition || schema.is_subtype(type_condition, typename),
+        None => true,
+    };
+
+    for selection in selection_set {
+        match selection {
+            Selection::Field {
+                name,
+                alias,
+                selection_set,
+                field_type,
+                ..
+            } => {
+                if name.as_str().starts_with("__") {
+                    continue;
+                }
+                let value = match object.get(alias.as_ref().unwrap_or(name).as_str()) {
+                    Some(value) if !value.is_null() => value,
+                    _ => continue,
+                };
+                fields.insert(format!("{parent_type}.{}", name.as_str()));
+                if let Some(selection_set) = selection_set {
+                    collect_returned(
+                        schema,
+                        query,
+                        field_type.0.inner_named_type().as_str(),
+                        selection_set,
+                        value,
+                        fields,
+                    );
+                }
+            }
+            Selection::InlineFragment {
+                type_condition,
+                selection_set,
+                ..
+            } => {
+                if applies(type_condition) {
+                    collect_returned(schema, query, type_condition, selection_set, data, fields)
+                }
+            }
+            Selection::FragmentSpread { name, .. } => {
+                if let Some(fragment) = query
+                    .fragments
+                    .get(name)
+                    .filter(|fragment| applies(&fragment.type_condition))
+                {
+                    collect_returned(
+                        schema,
+                        query,
+                        &fragment.type_condition,
+                        &fragment.selection_set,
+                        data,
+                        fields,
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json_bytes::json;
+
+    use super::requested_fields;
+    use super::returned_fields;
+    use super::Config;
+    use super::FieldUsage;
+    use crate::graphql;
+    use crate::metrics::FutureMetricsExt;
+    use crate::spec::Query;
+    use crate::spec::Schema;
+
+    const SCHEMA: &str = r#"
+    schema
+        @core(feature: "https://specs.apollo.dev/core/v0.1")
+        @core(feature: "https://specs.apollo.dev/join/v0.1")
+    {
+        query: Query
+    }
+    directive @core(feature: String!) repeatable on SCHEMA
+    directive @join__graph(name: String!, url: String!) on ENUM_VALUE
+    enum join__Graph {
+        TEST @join__graph(name: "test", url: "http://localhost:4001/graphql")
+    }
+
+    type Query {
+        topProducts: [Product]
+        me: User
+    }
+
+    interface Product {
+        upc: String!
+        name: String @deprecated(reason: "use title")
+    }
+
+    type Book implements Product {
+        upc: String!
+        name: String @deprecated(reason: "use title")
+        title: String
+    }
+
+    type Movie implements Product {
+        upc: String!
+        name: String @deprecated(reason: "use title")
+        title: String
+    }
+
+    type User {
+        id: ID!
+        username: String
+    }
+    "#;
+
+    #[test]
+    fn test_requested_and_returned_fields() {
+        let schema = Schema::parse_test(SCHEMA, &Default::default()).unwrap();
+        let query = Query::parse(
+            "query Top { topProducts { __typename upc name ...BookFields } me { id username } }
+            fragment BookFields on Book { title }",
+            &schema,
+            &Default::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            requested_fields(&query, Some("Top"))
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec![
+                "Book.title",
+                "Product.name",
+                "Product.upc",
+                "Query.me",
+                "Query.topProducts",
+                "User.id",
+                "User.username",
+            ]
+        );
+
+        let response = graphql::Response::builder()
+            .data(json!({
+                "topProducts": [
+                    { "__typename": "Book", "upc": "1", "name": null, "title": "Dune" },
+                    { "__typename": "Book", "upc": "2", "name": null, "title": null },
+                ],
+                "me": null
+            }))
+            .build();
+        assert_eq!(
+            returned_fields(&schema, &query, Some("Top"), &response)
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec!["Book.title", "Product.upc", "Query.topProducts"]
+        );
+
+        // Only the fragments matching the type of each object are counted as returned
+        let query = Query::parse(
+            "query Titles { topProducts { __typename ... on Book { title } ... on Movie { title } } }",
+            &schema,
+            &Default::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            requested_fields(&query, Some("Titles"))
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec!["Book.title", "Movie.title", "Query.topProducts"]
+        );
+        let response = graphql::Response::builder()
+            .data(json!({
+                "topProducts": [{ "__typename": "Book", "title": "Dune" }]
+            }))
+            .build();
+        assert_eq!(
+            returned_fields(&schema, &query, Some("Titles"), &response)
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec!["Book.title", "Query.topProducts"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_field_usage_metrics() {
+        async {
+            let field_usage = FieldUsage::new(
+                &Config {
+                    enabled: true,
+                    max_fields: 2,
+                    max_client_names: 1,
+                    ..Default::default()
+                },
+                SCHEMA,
+            )
+            .unwrap();
+            let schema = Schema::parse_test(SCHEMA, &Default::default()).unwrap();
+            let query = Query::parse(
+                "query Me { me { id username } }",
+                &schema,
+                &Default::default(),
+            )
+            .unwrap();
+            let response = graphql::Response::builder()
+                .data(json!({ "me": { "id": "1", "username": null } }))
+                .build();
+
+            field_usage.record_requested(&query, Some("Me"), Some("web"));
+            field_usage.record_returned(&query, Some("Me"), &response, Some("web"));
+            field_usage.record_requested(&query, Some("Me"), Some("ios"));
+
+            assert_counter!(
+                "apollo.router.operations.fields.requested",
+                1,
+                "graphql.field.coordinate" = "Query.me",
+                "graphql.field.deprecated" = false,
+                "client.name" = "web"
+            );
+            // over max_client_names
+            assert_counter!(
+                "apollo.router.operations.fields.requested",
+                1,
+                "graphql.field.coordinate" = "Query.me",
+                "graphql.field.deprecated" = false,
+                "client.name" = "__other__"
+            );
+            // over max_fields
+            assert_counter!(
+                "apollo.router.operations.fields.requested",
+                1,
+                "graphql.field.coordinate" = "__other__",
+                "graphql.field.deprecated" = false,
+                "client.name" = "web"
+            );
+            assert_counter!(
+                "apollo.router.operations.fields.returned",
+                1,
+                "graphql.field.coordinate" = "User.id",
+                "graphql.field.deprecated" = false,
+                "client.name" = "web"
+            );
+        }
+        .with_metrics()
+        .await;
+    }
+
+    #[test]
+    fn test_field_usage_deprecated_fields() {
+        let field_usage = super::FieldUsage::new(
+            &super::Config {
+                enabled: true,
+                ..Default::default()
+            },
+            SCHEMA,
+        )
+        .unwrap();
+        let mut deprecated_fields = field_usage
+            .deprecated_fields
+            .iter()
+            .cloned()
+            .collect::<Vec<_>>();
+        deprecated_fields.sort();
+        assert_eq!(
+            deprecated_fields,
+            vec!["Book.name", "Movie.name", "Product.name"]
+        );
+    }
+}
@@ -61,8 +61,10 @@ use self::config::Conf;
 use self::config::Sampler;
 use self::config::SamplerOption;
 use self::config::TraceIdFormat;
+use self::config_new::instruments::SupergraphRequestInstruments;
 use self::config_new::spans::Spans;
 use self::metrics::apollo::studio::SingleTypeStat;
+use self::metrics::field_usage::FieldUsage;
 use self::metrics::AttributesForwardConf;
 use self::reload::reload_fmt;
 use self::reload::SamplingFilter;
@@ -88,6 +90,7 @@ use crate::plugins::telemetry::config::MetricsCommon;
 use crate::plugins::telemetry::config::TracingCommon;
 use crate::plugins::telemetry::dynamic_attribute::DynAttribute;
 use crate::plugins::telemetry::fmt_layer::create_fmt_layer;
+use crate::plugins::telemetry::logging::otlp::create_logger_provider;
 use crate::plugins::telemetry::metrics::apollo::studio::SingleContextualizedStats;
 use crate::plugins::telemetry::metrics::apollo::studio::SinglePathErrorStats;
 use crate::plugins::telemetry::metrics::apollo::studio::SingleQueryLatencyStats;
@@ -112,6 +115,7 @@ use crate::services::subgraph::Request;
 use crate::services::subgraph::Response;
 use crate::services::supergraph;
 use crate::services::ExecutionRequest;
+use crate::services::ExecutionResponse;
 use crate::services::SubgraphRequest;
 use crate::services::SubgraphResponse;
 use crate::services::SupergraphRequest;
@@ -123,7 +127,7 @@ use crate::ListenAddr;
 pub(crate) mod apollo;
 pub(crate) mod apollo_exporter;
 pub(crate) mod config;
-mod config_new;
+pub(crate) mod config_new;
 pub(crate) mod dynamic_attribute;
 mod endpoint;
 mod fmt_layer;
@@ -168,12 +172,14 @@ pub(crate) struct Telemetry {
     apollo_metrics_sender: apollo_exporter::Sender,
     field_level_instrumentation_ratio: f64,
     sampling_filter_ratio: SamplerOption,
+    field_usage: Option<Arc<FieldUsage>>,
 
     activation: Mutex<TelemetryActivation>,
 }
 
 struct TelemetryActivation {
     tracer_provider: Option<opentelemetry::sdk::trace::TracerProvider>,
+    logger_provider: Option<opentelemetry::sdk::logs::LoggerProvider>,
     // We have to have separate meter providers for prometheus metrics so that they don't get zapped on router reload.
     public_meter_provider: Option<FilterMeterProvider>,
     public_prometheus_meter_provider: Option<FilterMeterProvider>,
@@ -224,9 +230,14 @@ impl Drop for Telemetry {
             activation.public_prometheus_meter_provider.take(),
         ];
         let tracer_provider = activation.tracer_provider.take();
+        let logger_provider = activation.logger_provider.take();
         drop(activation);
         TelemetryActivation::checked_meter_shutdown(metrics_providers);
 
+        if let Some(logger_provider) = logger_provider {
+            Self::checked_spawn_task(Box::new(move || drop(logger_provider)));
+        }
+
         if let Some(tracer_provider) = tracer_provider {
             Self::checked_tracer_shutdown(tracer_provider);
         }
@@ -243,13 +254,23 @@ impl Plugin for Telemetry {
 
         let mut config = init.config;
         config.instrumentation.spans.update_defaults();
+        config.instrumentation.instruments.update_defaults();
         config.exporters.logging.validate()?;
+        config.instrumentation.instruments.validate()?;
 
         let field_level_instrumentation_ratio =
             config.calculate_field_level_instrumentation_ratio()?;
         let metrics_builder = Self::create_metrics_builder(&config)?;
 
         let (sampling_filter_ratio, tracer_provider) = Self::create_tracer_provider(&config)?;
+        let logger_provider = create_logger_provider(&config)?;
+        let field_usage = config
+            .metrics
+            .field_usage
+            .enabled
+            .then(|| FieldUsage::new(&config.metrics.field_usage, &init.supergraph_sdl))
+            .transpose()?
+            .map(Arc::new);
 
         if config.instrumentation.spans.mode == SpanMode::Deprecated {
             ::tracing::warn!("telemetry.instrumentation.spans.mode is currently set to 'deprecated', either explicitly or via defaulting. Set telemetry.instrumentation.spans.mode explicitly in your router.yaml to 'spec_compliant' for log and span attributes that follow OpenTelemetry semantic conventions. This option will be defaulted to 'spec_compliant' in a future release and eventually removed altogether");
@@ -261,6 +282,7 @@ impl Plugin for Telemetry {
             field_level_instrumentation_ratio,
             activation: Mutex::new(TelemetryActivation {
                 tracer_provider: Some(tracer_provider),
+                logger_provider,
                 public_meter_provider: Some(FilterMeterProvider::public(
                     metrics_builder.public_meter_provider_builder.build(),
                 )),
@@ -273,6 +295,7 @@ impl Plugin for Telemetry {
                 is_active: false,
             }),
             sampling_filter_ratio,
+            field_usage,
             config: Arc::new(config),
         })
     }
@@ -500,10 +523,11 @@ impl Plugin for Telemetry {
             .map_future_with_request_data(
                 move |req: &SupergraphRequest| {
                     let custom_attributes = config.instrumentation.spans.supergraph.attributes.on_request(req);
+                    let instruments = config.instrumentation.instruments.on_supergraph_request(req);
                     Self::populate_context(config.clone(), field_level_instrumentation_ratio, req);
-                    (req.context.clone(), custom_attributes)
+                    (req.context.clone(), custom_attributes, instruments)
                 },
-                move |(ctx, custom_attributes): (Context, LinkedList<KeyValue>), fut| {
+                move |(ctx, custom_attributes, instruments): (Context, LinkedList<KeyValue>, SupergraphRequestInstruments), fut| {
                     let config = config_map_res.clone();
                     let sender = metrics_sender.clone();
                     let start = Instant::now();
@@ -516,6 +540,13 @@ impl Plugin for Telemetry {
                             Ok(resp) => span.set_dyn_attributes(config.instrumentation.spans.supergraph.attributes.on_response(resp)),
                             Err(err) => span.set_dyn_attributes(config.instrumentation.spans.supergraph.attributes.on_error(err)),
                         }
+                        let graphql_instruments = config.instrumentation.instruments.on_supergraph_response(instruments, result.as_ref());
+                        result = result.map(|resp| {
+                            resp.map_stream(move |gql_response| {
+                                graphql_instruments.on_graphql_response(&gql_response);
+                                gql_response
+                            })
+                        });
                         result = Self::update_otel_metrics(
                             config.clone(),
                             ctx.clone(),
@@ -534,6 +565,7 @@ impl Plugin for Telemetry {
     }
 
     fn execution_service(&self, service: execution::BoxService) -> execution::BoxService {
+        let field_usage = self.field_usage.clone();
         ServiceBuilder::new()
             .instrument(move |req: &ExecutionRequest| {
                 let operation_kind = req
@@ -555,6 +587,36 @@ impl Plugin for Telemetry {
                     }
                 }
             })
+            .map_future_with_request_data(
+                move |req: &ExecutionRequest| {
+                    let field_usage = field_usage.clone()?;
+                    let query = req.query_plan.query.clone();
+                    let operation_name = req.supergraph_request.body().operation_name.clone();
+                    let client_name: Option<String> = req.context.get(CLIENT_NAME).ok().flatten();
+                    field_usage.record_requested(
+                        &query,
+                        operation_name.as_deref(),
+                        client_name.as_deref(),
+                    );
+                    Some((field_usage, query, operation_name, client_name))
+                },
+                move |data, fut| async move {
+                    let response: ExecutionResponse = fut.await?;
+                    Ok::<_, BoxError>(match data {
+                        Some((field_usage, query, operation_name, client_name)) => response
+                            .map_stream(move |response| {
+                                field_usage.record_returned(
+                                    &query,
+                                    operation_name.as_deref(),
+                                    &response,
+                                    client_name.as_deref(),
+                                );
+                                response
+                            }),
+                        None => response,
+                    })
+                },
+            )
             .service(service)
             .boxed()
     }
@@ -682,7 +744,10 @@ impl Telemetry {
 
         activation.reload_metrics();
 
-        reload_fmt(create_fmt_layer(&self.config));
+        reload_fmt(create_fmt_layer(
+            &self.config,
+            activation.logger_provider.take(),
+        ));
         activation.is_active = true;
     }
 
@@ -1982,6 +2047,83 @@ mod tests {
         .await;
     }
 
+    #[tokio::test]
+    async fn test_supergraph_instruments() {
+        async {
+            let plugin = create_plugin_with_config(include_str!(
+                "testdata/supergraph_instruments.router.yaml"
+            ))
+            .await;
+
+            let mut mock_service = MockSupergraphService::new();
+            mock_service
+                .expect_call()
+                .times(2)
+                .returning(move |req: SupergraphRequest| {
+                    let _ = req.context.insert("acme.cost", 12).unwrap();
+                    Ok(SupergraphResponse::fake_builder()
+                        .context(req.context)
+                        .data(json!({"topProducts": [{"name": "Table"}, {"name": "Chair"}]}))
+                        .error(
+                            Error::builder()
+                                .message("one")
+                                .extension_code("ONE")
+                                .build(),
+                        )
+                        .error(
+                            Error::builder()
+                                .message("two")
+                                .extension_code("ONE")
+                                .build(),
+                        )
+                        .error(
+                            Error::builder()
+                                .message("three")
+                                .extension_code("TWO")
+                                .build(),
+                        )
+                        .build()
+                        .unwrap())
+                });
+            let mut supergraph_service = plugin.supergraph_service(BoxService::new(mock_service));
+            for client in ["admin", "web"] {
+                let router_req = SupergraphRequest::fake_builder().header("x-client", client);
+                let _router_response = supergraph_service
+                    .ready()
+                    .await
+                    .unwrap()
+                    .call(router_req.build().unwrap())
+                    .await
+                    .unwrap()
+                    .next_response()
+                    .await
+                    .unwrap();
+            }
+
+            assert_histogram!("graphql.operation.duration", 1, "client" = "admin");
+            assert_histogram!("graphql.operation.duration", 1, "client" = "web");
+            assert_counter!(
+                "graphql.errors",
+                2,
+                "client" = "admin",
+                "graphql.error.code" = "ONE"
+            );
+            assert_counter!(
+                "graphql.errors",
+                1,
+                "client" = "web",
+                "graphql.error.code" = "TWO"
+            );
+            assert_histogram!("acme.graphql.fields", 2);
+            // The cost of both requests
+            assert_counter!("acme.graphql.cost", 24);
+            // The condition only matches the admin client
+            assert_counter!("acme.graphql.admin_requests", 1);
+        }
+        .with_metrics()
+        .await;
+    }
+
     #[tokio::test]
     async fn test_supergraph_metrics_bad_request() {
         async {
@@ -19,6 +19,7 @@ use crate::graphql::Request;
 use crate::http_ext;
 use crate::plugins::authorization::CacheKeyMetadata;
 use crate::query_planner::fetch::OperationKind;
+use crate::services::query_batching::BatchQuery;
 use crate::services::SubgraphRequest;
 use crate::services::SubgraphResponse;
 
@@ -168,7 +169,15 @@ where
     fn call(&mut self, request: SubgraphRequest) -> Self::Future {
         let service = self.service.clone();
 
-        if request.operation_kind == OperationKind::Query {
+        // batched fetches must not wait on each other, they could be in the same wave
+        let batched = request
+            .subgraph_name
+            .as_deref()
+            .map_or(false, |subgraph_name| {
+                BatchQuery::for_subgraph(&request.context, request.operation_kind, subgraph_name)
+                    .is_some()
+            });
+        if request.operation_kind == OperationKind::Query && !batched {
             let wait_map = self.wait_map.clone();
 
             Box::pin(async move { Self::dedup(service, wait_map, request).await })
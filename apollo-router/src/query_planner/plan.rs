@@ -323,6 +323,36 @@ impl PlanNode {
         }
     }
 
+    /// Retrieves the fetch nodes of the plan in plan order, the deferred fetches coming
+    /// after the primary ones.
+    pub(crate) fn fetch_nodes<'a>(&'a self) -> Box<dyn Iterator<Item = &'a fetch::FetchNode> + 'a> {
+        match self {
+            Self::Sequence { nodes } | Self::Parallel { nodes } => {
+                Box::new(nodes.iter().flat_map(|x| x.fetch_nodes()))
+            }
+            Self::Fetch(fetch) => Box::new(Some(fetch).into_iter()),
+            Self::Subscription { rest, .. } => Box::new(rest.iter().flat_map(|x| x.fetch_nodes())),
+            Self::Flatten(flatten) => flatten.node.fetch_nodes(),
+            Self::Defer { primary, deferred } => Box::new(
+                primary.node.iter().flat_map(|x| x.fetch_nodes()).chain(
+                    deferred
+                        .iter()
+                        .flat_map(|d| d.node.iter().flat_map(|x| x.fetch_nodes())),
+                ),
+            ),
+            Self::Condition {
+                if_clause,
+                else_clause,
+                ..
+            } => Box::new(
+                if_clause
+                    .iter()
+                    .chain(else_clause.iter())
+                    .flat_map(|x| x.fetch_nodes()),
+            ),
+        }
+    }
+
     pub(crate) fn extract_authorization_metadata(
         &mut self,
         schema: &apollo_compiler::Schema,
@@ -23,6 +23,8 @@ use crate::configuration::APOLLO_PLUGIN_PREFIX;
 use crate::plugin::DynPlugin;
 use crate::plugin::Handler;
 use crate::plugin::PluginFactory;
+use crate::plugins::rhai::Rhai;
+use crate::plugins::rhai::APOLLO_RHAI;
 use crate::plugins::subscription::Subscription;
 use crate::plugins::subscription::APOLLO_SUBSCRIPTION_PLUGIN;
 use crate::plugins::telemetry::reload::apollo_opentelemetry_initialized;
@@ -40,6 +42,7 @@ use crate::services::router::service::RouterCreator;
 use crate::services::subgraph;
 use crate::services::transport;
 use crate::services::HasConfig;
+use crate::services::HasPlugins;
 use crate::services::HasSchema;
 use crate::services::PluggableSupergraphServiceBuilder;
 use crate::services::Plugins;
@@ -235,6 +238,13 @@ impl YamlRouterFactory {
         let persisted_query_layer = Arc::new(PersistedQueryLayer::new(&configuration).await?);
 
         if let Some(previous_router) = previous_router {
+            persisted_query_layer
+                .carry_over_unknown_operations(&previous_router.persisted_query_layer);
+            carry_over_rhai_store(
+                &supergraph_creator.plugins(),
+                &previous_router.supergraph_creator.plugins(),
+            );
+
             let cache_keys = previous_router
                 .cache_keys(configuration.supergraph.query_planning.warmed_up_queries)
                 .await;
@@ -665,6 +675,18 @@ pub(crate) async fn create_plugins(
     }
 }
 
+/// Keeps the entries of the Rhai store across reloads
+fn carry_over_rhai_store(plugins: &Plugins, previous_plugins: &Plugins) {
+    fn rhai(plugins: &Plugins) -> Option<&Rhai> {
+        plugins
+            .get(APOLLO_RHAI)
+            .and_then(|plugin| plugin.as_any().downcast_ref::<Rhai>())
+    }
+    if let (Some(rhai), Some(previous)) = (rhai(plugins), rhai(previous_plugins)) {
+        rhai.carry_over_store(previous);
+    }
+}
+
 fn inject_schema_id(schema_id: Option<&str>, configuration: &mut Value) {
     if configuration.get("apollo").is_none() {
         // Warning: this must be done here, otherwise studio reporting will not work
@@ -2,17 +2,25 @@
 
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::path::Path;
 use std::sync::Arc;
 use std::sync::RwLock;
+use std::time::Duration;
 
 use apollo_compiler::ast;
 use futures::prelude::*;
+use futures::stream::BoxStream;
+use reqwest::header::ETAG;
+use reqwest::header::IF_NONE_MATCH;
 use reqwest::Client;
+use reqwest::StatusCode;
 use serde::Deserialize;
 use serde::Serialize;
 use tokio::sync::mpsc;
 use tower::BoxError;
 
+use crate::configuration::PersistedQueries;
+use crate::json_ext::Object;
 use crate::uplink::persisted_queries_manifest_stream::MaybePersistedQueriesManifestChunks;
 use crate::uplink::persisted_queries_manifest_stream::PersistedQueriesManifestChunk;
 use crate::uplink::persisted_queries_manifest_stream::PersistedQueriesManifestQuery;
@@ -21,7 +29,26 @@ use crate::uplink::UplinkConfig;
 use crate::Configuration;
 
 /// An in memory cache of persisted queries.
-pub(crate) type PersistedQueryManifest = HashMap<String, String>;
+pub(crate) type PersistedQueryManifest =
+    HashMap<FullPersistedQueryOperationId, PersistedQueryOperation>;
+
+/// Identifies an operation in the manifest. Operations registered for a client are only
+/// available to that client, the others are available to every client.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct FullPersistedQueryOperationId {
+    pub(crate) operation_id: String,
+    pub(crate) client_name: Option<String>,
+}
+
+/// An operation of the manifest, with its optional metadata.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct PersistedQueryOperation {
+    pub(crate) body: String,
+    pub(crate) operation_name: Option<String>,
+    pub(crate) metadata: Option<Object>,
+}
+
+const MANIFEST_FETCH_TIMEOUT: Duration = Duration::from_secs(30);
 
 /// How the router should respond to requests that are not resolved as the IDs
 /// of an operation in the manifest. (For the most part this means "requests
@@ -45,12 +72,19 @@ pub(crate) enum FreeformGraphQLBehavior {
         safelist: FreeformGraphQLSafelist,
         apq_enabled: bool,
     },
+    /// Allows all operations, the safelist is only used to report unknown operations
+    ReportUnlessInSafelist {
+        safelist: FreeformGraphQLSafelist,
+        apq_enabled: bool,
+    },
 }
 
 /// Describes what the router should do for a given request: allow it, deny it
-/// with an error, or allow it but log the operation as unknown.
+/// with an error, or allow it but log the operation as unknown. Any action but
+/// `Allow` means that the operation is unknown.
 pub(crate) enum FreeformGraphQLAction {
     Allow,
+    AllowUnknown,
     Deny,
     AllowAndLog,
     DenyAndLog,
@@ -59,6 +93,7 @@ pub(crate) enum FreeformGraphQLAction {
 impl FreeformGraphQLBehavior {
     fn action_for_freeform_graphql(
         &self,
+        client_name: Option<&str>,
         ast: Result<&ast::Document, &str>,
     ) -> FreeformGraphQLAction {
         match self {
@@ -77,7 +112,7 @@ impl FreeformGraphQLBehavior {
                 log_unknown,
                 ..
             } => {
-                if safelist.is_allowed(ast) {
+                if safelist.is_allowed(client_name, ast) {
                     FreeformGraphQLAction::Allow
                 } else if *log_unknown {
                     FreeformGraphQLAction::DenyAndLog
@@ -86,17 +121,26 @@ impl FreeformGraphQLBehavior {
                 }
             }
             FreeformGraphQLBehavior::LogUnlessInSafelist { safelist, .. } => {
-                if safelist.is_allowed(ast) {
+                if safelist.is_allowed(client_name, ast) {
                     FreeformGraphQLAction::Allow
                 } else {
                     FreeformGraphQLAction::AllowAndLog
                 }
             }
+            FreeformGraphQLBehavior::ReportUnlessInSafelist { safelist, .. } => {
+                if safelist.is_allowed(client_name, ast) {
+                    FreeformGraphQLAction::Allow
+                } else {
+                    FreeformGraphQLAction::AllowUnknown
+                }
+            }
         }
     }
 }
 
-/// The normalized bodies of all operations in the PQ manifest.
+/// The normalized bodies of all operations in the PQ manifest, by client name. Like operation
+/// IDs, bodies registered for a client are only allowed for that client, the others are allowed
+/// for every client.
 ///
 /// Normalization currently consists of:
 /// - Sorting the top-level definitions (operation and fragment definitions)
@@ -116,40 +160,48 @@ impl FreeformGraphQLBehavior {
 /// formatting.
 #[derive(Debug)]
 pub(crate) struct FreeformGraphQLSafelist {
-    normalized_bodies: HashSet<String>,
+    normalized_bodies: HashMap<Option<String>, HashSet<String>>,
 }
 
 impl FreeformGraphQLSafelist {
     fn new(manifest: &PersistedQueryManifest) -> Self {
         let mut safelist = Self {
-            normalized_bodies: HashSet::new(),
+            normalized_bodies: HashMap::new(),
         };
 
-        for body in manifest.values() {
-            safelist.insert_from_manifest(body);
+        for (id, operation) in manifest {
+            safelist.insert_from_manifest(id.client_name.clone(), &operation.body);
         }
 
         safelist
     }
 
-    fn insert_from_manifest(&mut self, body_from_manifest: &str) {
-        self.normalized_bodies.insert(
-            self.normalize_body(
+    fn insert_from_manifest(&mut self, client_name: Option<String>, body_from_manifest: &str) {
+        self.normalized_bodies
+            .entry(client_name)
+            .or_default()
+            .insert(Self::normalize_body(
                 ast::Document::parse(body_from_manifest, "from_manifest")
                     .as_ref()
                     .map_err(|_| body_from_manifest),
-            ),
-        );
+            ));
     }
 
-    fn is_allowed(&self, ast: Result<&ast::Document, &str>) -> bool {
+    fn is_allowed(&self, client_name: Option<&str>, ast: Result<&ast::Document, &str>) -> bool {
         // Note: consider adding an LRU cache that caches this function's return
         // value based solely on body_from_request without needing to normalize
         // the body.
-        self.normalized_bodies.contains(&self.normalize_body(ast))
+        let normalized_body = Self::normalize_body(ast);
+        let is_registered = |client_name: Option<String>| {
+            self.normalized_bodies
+                .get(&client_name)
+                .is_some_and(|bodies| bodies.contains(&normalized_body))
+        };
+        client_name.is_some_and(|client_name| is_registered(Some(client_name.to_string())))
+            || is_registered(None)
     }
 
-    fn normalize_body(&self, ast: Result<&ast::Document, &str>) -> String {
+    pub(crate) fn normalize_body(ast: Result<&ast::Document, &str>) -> String {
         match ast {
             Err(body_from_request) => {
                 // If we can't parse the operation (whether from the PQ list or the
@@ -206,69 +258,90 @@ impl PersistedQueryManifestPoller {
     /// Create a new [`PersistedQueryManifestPoller`] from CLI options and YAML configuration.
     /// Starts polling immediately and this function only returns after all chunks have been fetched
     /// and the [`PersistedQueryManifest`] has been fully populated.
+    ///
+    /// Manifests are read from `persisted_queries.local_manifests` and
+    /// `persisted_queries.manifest_urls` if one of them is set, otherwise from uplink.
     pub(crate) async fn new(config: Configuration) -> Result<Self, BoxError> {
-        if let Some(uplink_config) = config.uplink.as_ref() {
-            // Note that the contents of this Arc<RwLock> will be overwritten by poll_uplink before
-            // we return from this `new` method, so the particular choice of freeform_graphql_behavior
-            // here does not matter. (Can we improve this? We could use an Option but then we'd just
-            // end up `unwrap`ping a lot later. Perhaps MaybeUninit, but that's even worse?)
-            let state = Arc::new(RwLock::new(PersistedQueryManifestPollerState {
-                persisted_query_manifest: PersistedQueryManifest::new(),
-                freeform_graphql_behavior: FreeformGraphQLBehavior::DenyAll { log_unknown: false },
-            }));
-
-            let http_client = Client::builder().timeout(uplink_config.timeout).gzip(true).build()
-            .map_err(|e| -> BoxError {
-                format!(
-                    "could not initialize HTTP client for fetching persisted queries manifest chunks: {}",
-                    e
-                ).into()
-            })?;
-
-            let (_drop_signal, drop_receiver) = mpsc::channel::<()>(1);
-            let (ready_sender, mut ready_receiver) =
-                mpsc::channel::<ManifestPollResultOnStartup>(1);
-
-            // start polling uplink for persisted query chunks
-            tokio::task::spawn(poll_uplink(
-                uplink_config.clone(),
-                state.clone(),
-                config,
-                ready_sender,
-                drop_receiver,
-                http_client,
-            ));
+        let manifest_stream = if config.persisted_queries.local_manifests.is_some()
+            || config.persisted_queries.manifest_urls.is_some()
+        {
+            local_manifest_stream(&config.persisted_queries)?
+        } else if let Some(uplink_config) = config.uplink.as_ref() {
+            uplink_manifest_stream(uplink_config.clone())?
+        } else {
+            return Err("persisted queries requires Apollo GraphOS. ensure that you have set APOLLO_KEY and APOLLO_GRAPH_REF environment variables, or set persisted_queries.local_manifests".into());
+        };
 
-            // wait for the uplink poller to report its first success and continue
-            // or report the error
-            match ready_receiver.recv().await {
-                Some(startup_result) => match startup_result {
-                    ManifestPollResultOnStartup::LoadedOperations => (),
-                    ManifestPollResultOnStartup::Err(error) => return Err(error),
-                },
-                None => {
-                    return Err("could not receive ready event for persisted query layer".into());
-                }
-            }
+        // Note that the contents of this Arc<RwLock> will be overwritten by poll_manifests before
+        // we return from this `new` method, so the particular choice of freeform_graphql_behavior
+        // here does not matter. (Can we improve this? We could use an Option but then we'd just
+        // end up `unwrap`ping a lot later. Perhaps MaybeUninit, but that's even worse?)
+        let state = Arc::new(RwLock::new(PersistedQueryManifestPollerState {
+            persisted_query_manifest: PersistedQueryManifest::new(),
+            freeform_graphql_behavior: FreeformGraphQLBehavior::DenyAll { log_unknown: false },
+        }));
 
-            Ok(Self {
-                state,
-                _drop_signal,
-            })
-        } else {
-            Err("persisted queries requires Apollo GraphOS. ensure that you have set APOLLO_KEY and APOLLO_GRAPH_REF environment variables".into())
+        let (_drop_signal, drop_receiver) = mpsc::channel::<()>(1);
+        let (ready_sender, mut ready_receiver) = mpsc::channel::<ManifestPollResultOnStartup>(1);
+
+        // start polling for persisted query manifests
+        tokio::task::spawn(poll_manifests(
+            manifest_stream,
+            state.clone(),
+            config,
+            ready_sender,
+            drop_receiver,
+        ));
+
+        // wait for the poller to report its first success and continue
+        // or report the error
+        match ready_receiver.recv().await {
+            Some(startup_result) => match startup_result {
+                ManifestPollResultOnStartup::LoadedOperations => (),
+                ManifestPollResultOnStartup::Err(error) => return Err(error),
+            },
+            None => {
+                return Err("could not receive ready event for persisted query layer".into());
+            }
         }
+
+        Ok(Self {
+            state,
+            _drop_signal,
+        })
     }
 
-    pub(crate) fn get_operation_body(&self, persisted_query_id: &str) -> Option<String> {
+    /// Finds an operation registered for the client, or for every client
+    pub(crate) fn get_operation(
+        &self,
+        persisted_query_id: &str,
+        client_name: Option<&str>,
+    ) -> Option<PersistedQueryOperation> {
         let state = self
             .state
             .read()
             .expect("could not acquire read lock on persisted query manifest state");
-        state
-            .persisted_query_manifest
-            .get(persisted_query_id)
-            .cloned()
+        let mut id = FullPersistedQueryOperationId {
+            operation_id: persisted_query_id.to_string(),
+            client_name: client_name.map(str::to_string),
+        };
+        if let Some(operation) = state.persisted_query_manifest.get(&id) {
+            return Some(operation.clone());
+        }
+        if id.client_name.is_none() {
+            return None;
+        }
+        id.client_name = None;
+        state.persisted_query_manifest.get(&id).cloned()
+    }
+
+    pub(crate) fn get_operation_body(
+        &self,
+        persisted_query_id: &str,
+        client_name: Option<&str>,
+    ) -> Option<String> {
+        self.get_operation(persisted_query_id, client_name)
+            .map(|operation| operation.body)
     }
 
     pub(crate) fn get_all_operations(&self) -> Vec<String> {
@@ -276,11 +349,16 @@ impl PersistedQueryManifestPoller {
             .state
             .read()
             .expect("could not acquire read lock on persisted query manifest state");
-        state.persisted_query_manifest.values().cloned().collect()
+        state
+            .persisted_query_manifest
+            .values()
+            .map(|operation| operation.body.clone())
+            .collect()
     }
 
     pub(crate) fn action_for_freeform_graphql(
         &self,
+        client_name: Option<&str>,
         ast: Result<&ast::Document, &str>,
     ) -> FreeformGraphQLAction {
         let state = self
@@ -289,7 +367,7 @@ impl PersistedQueryManifestPoller {
             .expect("could not acquire read lock on persisted query state");
         state
             .freeform_graphql_behavior
-            .action_for_freeform_graphql(ast)
+            .action_for_freeform_graphql(client_name, ast)
     }
 
     // Some(bool) means "never allows freeform GraphQL, bool is whether or not to log"
@@ -318,48 +396,239 @@ impl PersistedQueryManifestPoller {
             .expect("could not acquire read lock on persisted query state");
         match state.freeform_graphql_behavior {
             FreeformGraphQLBehavior::AllowAll { apq_enabled, .. }
-            | FreeformGraphQLBehavior::LogUnlessInSafelist { apq_enabled, .. } => apq_enabled,
+            | FreeformGraphQLBehavior::LogUnlessInSafelist { apq_enabled, .. }
+            | FreeformGraphQLBehavior::ReportUnlessInSafelist { apq_enabled, .. } => apq_enabled,
             _ => false,
         }
     }
 }
 
-async fn poll_uplink(
+/// Streams the persisted query manifests published in uplink.
+fn uplink_manifest_stream(
     uplink_config: UplinkConfig,
+) -> Result<BoxStream<'static, ManifestPollEvent>, BoxError> {
+    let http_client = Client::builder()
+        .timeout(uplink_config.timeout)
+        .gzip(true)
+        .build()
+        .map_err(|e| -> BoxError {
+            format!(
+                "could not initialize HTTP client for fetching persisted queries manifest chunks: {}",
+                e
+            )
+            .into()
+        })?;
+
+    Ok(stream_from_uplink_transforming_new_response::<
+        PersistedQueriesManifestQuery,
+        MaybePersistedQueriesManifestChunks,
+        Option<PersistedQueryManifest>,
+    >(uplink_config.clone(), move |response| {
+        let http_client = http_client.clone();
+        Box::new(Box::pin(async move {
+            match response {
+                Some(chunks) => manifest_from_chunks(chunks, http_client)
+                    .await
+                    .map(Some)
+                    .map_err(|err| {
+                        format!("could not download persisted query lists: {}", err).into()
+                    }),
+                None => Ok(None),
+            }
+        }))
+    })
+    .map(move |res| match res {
+        Ok(Some(new_manifest)) => ManifestPollEvent::NewManifest(new_manifest),
+        Ok(None) => ManifestPollEvent::NoPersistedQueryList {
+            graph_ref: uplink_config.apollo_graph_ref.clone(),
+        },
+        Err(e) => ManifestPollEvent::Err(e.into()),
+    })
+    .boxed())
+}
+
+/// Streams the persisted query manifests read from local files and URLs.
+///
+/// Files are watched and URLs are polled for changes. Every change produces a new manifest
+/// merging the latest version of each source, once all of them have been loaded.
+fn local_manifest_stream(
+    config: &PersistedQueries,
+) -> Result<BoxStream<'static, ManifestPollEvent>, BoxError> {
+    let http_client = Client::builder()
+        .timeout(MANIFEST_FETCH_TIMEOUT)
+        .gzip(true)
+        .build()
+        .map_err(|e| -> BoxError {
+            format!(
+                "could not initialize HTTP client for fetching persisted queries manifests: {}",
+                e
+            )
+            .into()
+        })?;
+
+    let mut sources: Vec<BoxStream<'static, Result<PersistedQueryManifest, BoxError>>> = Vec::new();
+    for path in config.local_manifests.iter().flatten() {
+        let path = path.clone();
+        sources.push(
+            stream::once(future::ready(()))
+                .chain(crate::files::watch(&path))
+                .then(move |()| {
+                    let path = path.clone();
+                    async move { read_manifest_file(&path).await }
+                })
+                .boxed(),
+        );
+    }
+    for url in config.manifest_urls.iter().flatten() {
+        sources.push(poll_manifest_url(
+            http_client.clone(),
+            url.clone(),
+            config.manifest_poll_interval,
+        ));
+    }
+
+    if sources.is_empty() {
+        return Err("persisted_queries.local_manifests and persisted_queries.manifest_urls do not contain any manifest".into());
+    }
+
+    let mut manifests: Vec<Option<PersistedQueryManifest>> = vec![None; sources.len()];
+    Ok(stream::select_all(
+        sources
+            .into_iter()
+            .enumerate()
+            .map(|(index, source)| source.map(move |res| (index, res)).boxed()),
+    )
+    .filter_map(move |(index, res)| {
+        future::ready(match res {
+            Ok(manifest) => {
+                manifests[index] = Some(manifest);
+                if manifests.iter().all(Option::is_some) {
+                    let new_manifest: PersistedQueryManifest = manifests
+                        .iter()
+                        .flatten()
+                        .flat_map(|manifest| {
+                            manifest.iter().map(|(id, body)| (id.clone(), body.clone()))
+                        })
+                        .collect();
+                    tracing::info!("Loaded {} persisted queries.", new_manifest.len());
+                    Some(ManifestPollEvent::NewManifest(new_manifest))
+                } else {
+                    None
+                }
+            }
+            Err(e) => Some(ManifestPollEvent::Err(e)),
+        })
+    })
+    .boxed())
+}
+
+async fn read_manifest_file(path: &Path) -> Result<PersistedQueryManifest, BoxError> {
+    let content = tokio::fs::read_to_string(path)
+        .await
+        .map_err(|e| -> BoxError {
+            format!(
+                "error reading persisted queries manifest {}: {}",
+                path.display(),
+                e
+            )
+            .into()
+        })?;
+    let chunk = serde_json::from_str::<SignedUrlChunk>(&content)
+        .map_err(|e| -> BoxError {
+            format!(
+                "error parsing persisted queries manifest {}: {}",
+                path.display(),
+                e
+            )
+            .into()
+        })?
+        .validate()?;
+
+    Ok(chunk.into_manifest())
+}
+
+/// Polls a manifest URL, only producing a manifest when it changed according to its ETag.
+fn poll_manifest_url(
+    http_client: Client,
+    url: String,
+    poll_interval: Duration,
+) -> BoxStream<'static, Result<PersistedQueryManifest, BoxError>> {
+    stream::unfold(
+        (None, true),
+        move |(etag, first): (Option<String>, bool)| {
+            let http_client = http_client.clone();
+            let url = url.clone();
+            async move {
+                if !first {
+                    tokio::time::sleep(poll_interval).await;
+                }
+                match fetch_manifest_url(&http_client, &url, etag.as_deref()).await {
+                    Ok(Some((manifest, new_etag))) => Some((Some(Ok(manifest)), (new_etag, false))),
+                    // not modified
+                    Ok(None) => Some((None, (etag, false))),
+                    Err(e) => Some((Some(Err(e)), (etag, false))),
+                }
+            }
+        },
+    )
+    .filter_map(future::ready)
+    .boxed()
+}
+
+async fn fetch_manifest_url(
+    http_client: &Client,
+    url: &str,
+    etag: Option<&str>,
+) -> Result<Option<(PersistedQueryManifest, Option<String>)>, BoxError> {
+    let mut request = http_client.get(url);
+    if let Some(etag) = etag {
+        request = request.header(IF_NONE_MATCH, etag);
+    }
+    let response = request
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| -> BoxError {
+            format!(
+                "error fetching persisted queries manifest from {}: {}",
+                url, e
+            )
+            .into()
+        })?;
+
+    if response.status() == StatusCode::NOT_MODIFIED {
+        return Ok(None);
+    }
+
+    let etag = response
+        .headers()
+        .get(ETAG)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let chunk = response
+        .json::<SignedUrlChunk>()
+        .await
+        .map_err(|e| -> BoxError {
+            format!(
+                "error reading body of persisted queries manifest from {}: {}",
+                url, e
+            )
+            .into()
+        })?
+        .validate()?;
+
+    Ok(Some((chunk.into_manifest(), etag)))
+}
+
+async fn poll_manifests(
+    manifest_stream: BoxStream<'static, ManifestPollEvent>,
     state: Arc<RwLock<PersistedQueryManifestPollerState>>,
     config: Configuration,
     ready_sender: mpsc::Sender<ManifestPollResultOnStartup>,
     mut drop_receiver: mpsc::Receiver<()>,
-    http_client: Client,
 ) {
-    let http_client = http_client.clone();
-    let mut uplink_executor = stream::select_all(vec![
-        stream_from_uplink_transforming_new_response::<
-            PersistedQueriesManifestQuery,
-            MaybePersistedQueriesManifestChunks,
-            Option<PersistedQueryManifest>,
-        >(uplink_config.clone(), move |response| {
-            let http_client = http_client.clone();
-            Box::new(Box::pin(async move {
-                match response {
-                    Some(chunks) => manifest_from_chunks(chunks, http_client)
-                        .await
-                        .map(Some)
-                        .map_err(|err| {
-                            format!("could not download persisted query lists: {}", err).into()
-                        }),
-                    None => Ok(None),
-                }
-            }))
-        })
-        .map(|res| match res {
-            Ok(Some(new_manifest)) => ManifestPollEvent::NewManifest(new_manifest),
-            Ok(None) => ManifestPollEvent::NoPersistedQueryList {
-                graph_ref: uplink_config.apollo_graph_ref.clone(),
-            },
-            Err(e) => ManifestPollEvent::Err(e.into()),
-        })
-        .boxed(),
+    let mut manifest_executor = stream::select_all(vec![
+        manifest_stream,
         drop_receiver
             .recv()
             .into_stream()
@@ -380,7 +649,7 @@ async fn poll_uplink(
 
     let mut ready_sender_once = Some(ready_sender);
 
-    while let Some(event) = uplink_executor.next().await {
+    while let Some(event) = manifest_executor.next().await {
         match event {
             ManifestPollEvent::NewManifest(new_manifest) => {
                 let freeform_graphql_behavior = if config.persisted_queries.safelist.enabled {
@@ -399,6 +668,11 @@ async fn poll_uplink(
                         safelist: FreeformGraphQLSafelist::new(&new_manifest),
                         apq_enabled: config.apq.enabled,
                     }
+                } else if config.persisted_queries.report_unknown.enabled {
+                    FreeformGraphQLBehavior::ReportUnlessInSafelist {
+                        safelist: FreeformGraphQLSafelist::new(&new_manifest),
+                        apq_enabled: config.apq.enabled,
+                    }
                 } else {
                     FreeformGraphQLBehavior::AllowAll {
                         apq_enabled: config.apq.enabled,
@@ -460,10 +734,7 @@ async fn poll_uplink(
             (None, ManifestPollResultOnStartup::Err(err)) => {
                 // We've already successfully started up, but we received some sort of error. This doesn't
                 // need to break our functional router, but we can log in case folks are interested.
-                tracing::error!(
-                    "error while polling uplink for persisted query manifests: {}",
-                    err
-                )
+                tracing::error!("error while polling for persisted query manifests: {}", err)
             }
             // Do nothing in the normal background "new manifest" case.
             (None, ManifestPollResultOnStartup::LoadedOperations) => {}
@@ -504,9 +775,7 @@ async fn add_chunk_to_operations(
     while let Some(chunk_url) = it.next() {
         match fetch_chunk(http_client.clone(), chunk_url).await {
             Ok(chunk) => {
-                for operation in chunk.operations {
-                    operations.insert(operation.id, operation.body);
-                }
+                operations.extend(chunk.into_manifest());
                 return Ok(());
             }
             Err(e) => {
@@ -554,15 +823,7 @@ async fn fetch_chunk(http_client: Client, chunk_url: &String) -> Result<SignedUr
             .into()
         })?;
 
-    if chunk.format != "apollo-persisted-query-manifest" {
-        return Err("chunk format is not 'apollo-persisted-query-manifest'".into());
-    }
-
-    if chunk.version != 1 {
-        return Err("persisted query manifest chunk version is not 1".into());
-    }
-
-    Ok(chunk)
+    chunk.validate()
 }
 
 /// Types of events produced by the manifest poller.
@@ -589,21 +850,98 @@ pub(crate) struct SignedUrlChunk {
     pub(crate) operations: Vec<Operation>,
 }
 
-/// A single operation containing an ID and a body,
+impl SignedUrlChunk {
+    fn validate(self) -> Result<Self, BoxError> {
+        if self.format != "apollo-persisted-query-manifest" {
+            return Err("chunk format is not 'apollo-persisted-query-manifest'".into());
+        }
+
+        if self.version != 1 {
+            return Err("persisted query manifest chunk version is not 1".into());
+        }
+
+        Ok(self)
+    }
+
+    fn into_manifest(self) -> PersistedQueryManifest {
+        self.operations
+            .into_iter()
+            .map(|operation| {
+                (
+                    FullPersistedQueryOperationId {
+                        operation_id: operation.id,
+                        client_name: operation.client_name,
+                    },
+                    PersistedQueryOperation {
+                        body: operation.body,
+                        operation_name: operation.operation_name,
+                        metadata: operation.metadata,
+                    },
+                )
+            })
+            .collect()
+    }
+}
+
+/// A single operation containing an ID and a body, optionally restricted to a client and
+/// annotated with metadata.
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub(crate) struct Operation {
     pub(crate) id: String,
     pub(crate) body: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) client_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) operation_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) metadata: Option<Object>,
 }
 
 #[cfg(test)]
 mod tests {
     use url::Url;
+    use wiremock::matchers::header;
+    use wiremock::matchers::method;
+    use wiremock::Mock;
+    use wiremock::MockServer;
+    use wiremock::ResponseTemplate;
 
     use super::*;
+    use crate::files::tests::create_temp_file;
+    use crate::files::tests::write_and_flush;
     use crate::test_harness::mocks::persisted_queries::*;
     use crate::uplink::Endpoints;
 
+    fn manifest_from_bodies<const N: usize>(
+        operations: [(&str, &str); N],
+    ) -> PersistedQueryManifest {
+        operations
+            .into_iter()
+            .map(|(id, body)| {
+                (
+                    FullPersistedQueryOperationId {
+                        operation_id: id.to_string(),
+                        client_name: None,
+                    },
+                    PersistedQueryOperation {
+                        body: body.to_string(),
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect()
+    }
+
+    fn manifest_json(id: &str, body: &str) -> String {
+        serde_json::json!({
+            "format": "apollo-persisted-query-manifest",
+            "version": 1,
+            "operations": [{ "id": id, "body": body }]
+        })
+        .to_string()
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn poller_can_get_operation_bodies() {
         let (id, body, manifest) = fake_manifest();
@@ -616,7 +954,7 @@ mod tests {
         )
         .await
         .unwrap();
-        assert_eq!(manifest_manager.get_operation_body(&id), Some(body))
+        assert_eq!(manifest_manager.get_operation_body(&id, None), Some(body))
     }
 
     #[tokio::test(flavor = "multi_thread")]
@@ -649,22 +987,120 @@ mod tests {
         )
         .await
         .unwrap();
-        assert_eq!(manifest_manager.get_operation_body(&id), Some(body))
+        assert_eq!(manifest_manager.get_operation_body(&id, None), Some(body))
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn poller_reloads_local_manifests() {
+        let (path, mut file) = create_temp_file();
+        write_and_flush(&mut file, &manifest_json("1234", "query { typename }")).await;
+
+        let manifest_manager = PersistedQueryManifestPoller::new(
+            Configuration::fake_builder()
+                .persisted_query(
+                    PersistedQueries::builder()
+                        .enabled(true)
+                        .local_manifests(vec![path])
+                        .build(),
+                )
+                .build()
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            manifest_manager.get_operation_body("1234", None),
+            Some("query { typename }".to_string())
+        );
+
+        write_and_flush(&mut file, &manifest_json("5678", "query { me }")).await;
+        assert_eq!(manifest_manager.get_operation_body("1234", None), None);
+        assert_eq!(
+            manifest_manager.get_operation_body("5678", None),
+            Some("query { me }".to_string())
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn poller_wont_start_with_invalid_local_manifest() {
+        let (path, mut file) = create_temp_file();
+        write_and_flush(
+            &mut file,
+            r#"{"format": "other", "version": 1, "operations": []}"#,
+        )
+        .await;
+
+        assert!(PersistedQueryManifestPoller::new(
+            Configuration::fake_builder()
+                .persisted_query(
+                    PersistedQueries::builder()
+                        .enabled(true)
+                        .local_manifests(vec![path])
+                        .build(),
+                )
+                .build()
+                .unwrap(),
+        )
+        .await
+        .is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn poller_polls_manifest_urls_with_etag() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(header("if-none-match", "\"v1\""))
+            .respond_with(ResponseTemplate::new(304))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("etag", "\"v1\"")
+                    .set_body_string(manifest_json("1234", "query { typename }")),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let manifest_manager = PersistedQueryManifestPoller::new(
+            Configuration::fake_builder()
+                .persisted_query(
+                    PersistedQueries::builder()
+                        .enabled(true)
+                        .manifest_urls(vec![format!("{}/manifest.json", mock_server.uri())])
+                        .manifest_poll_interval(Duration::from_millis(100))
+                        .build(),
+                )
+                .build()
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+        // the following polls get a 304 response and keep the manifest
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert_eq!(
+            manifest_manager.get_operation_body("1234", None),
+            Some("query { typename }".to_string())
+        );
     }
 
     #[test]
     fn safelist_body_normalization() {
-        let safelist = FreeformGraphQLSafelist::new(&PersistedQueryManifest::from([(
-            "valid-syntax".to_string(),
-            "fragment A on T { a }    query SomeOp { ...A ...B }    fragment,,, B on U{b c  } # yeah"
-                .to_string(),
+        let safelist = FreeformGraphQLSafelist::new(&manifest_from_bodies([(
+            "valid-syntax",
+            "fragment A on T { a }    query SomeOp { ...A ...B }    fragment,,, B on U{b c  } # yeah",
         ), (
-            "invalid-syntax".to_string(),
-            "}}}".to_string()),
+            "invalid-syntax",
+            "}}}"),
         ]));
 
         let is_allowed = |body: &str| -> bool {
-            safelist.is_allowed(ast::Document::parse(body, "").as_ref().map_err(|_| body))
+            safelist.is_allowed(
+                None,
+                ast::Document::parse(body, "").as_ref().map_err(|_| body),
+            )
         };
 
         // Precise string matches.
@@ -688,4 +1124,45 @@ mod tests {
         // ... unless they precisely match a safelisted document that also has invalid syntax.
         assert!(is_allowed("}}}"));
     }
+
+    #[test]
+    fn safelist_by_client_name() {
+        let operation = |id: &str, client_name: Option<&str>, body: &str| {
+            (
+                FullPersistedQueryOperationId {
+                    operation_id: id.to_string(),
+                    client_name: client_name.map(str::to_string),
+                },
+                PersistedQueryOperation {
+                    body: body.to_string(),
+                    ..Default::default()
+                },
+            )
+        };
+        let safelist = FreeformGraphQLSafelist::new(
+            &[
+                operation("a", Some("client-a"), "query A { a }"),
+                operation("shared", None, "query Shared { shared }"),
+            ]
+            .into_iter()
+            .collect(),
+        );
+
+        let is_allowed = |client_name: Option<&str>, body: &str| -> bool {
+            safelist.is_allowed(
+                client_name,
+                ast::Document::parse(body, "").as_ref().map_err(|_| body),
+            )
+        };
+
+        assert!(is_allowed(Some("client-a"), "query A { a }"));
+        // another client cannot run the operation registered for client A
+        assert!(!is_allowed(Some("client-b"), "query A { a }"));
+        assert!(!is_allowed(None, "query A { a }"));
+
+        // operations registered without a client are allowed for every client
+        assert!(is_allowed(Some("client-a"), "query Shared { shared }"));
+        assert!(is_allowed(Some("client-b"), "query Shared { shared }"));
+        assert!(is_allowed(None, "query Shared { shared }"));
+    }
 }
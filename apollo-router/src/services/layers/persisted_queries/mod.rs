@@ -1,24 +1,40 @@
 mod id_extractor;
 mod manifest_poller;
+mod unknown_operations;
 
 #[cfg(test)]
 use std::sync::Arc;
 
+use apollo_compiler::ast;
 use http::header::CACHE_CONTROL;
+use http::HeaderName;
 use http::HeaderValue;
 use id_extractor::PersistedQueryIdExtractor;
 pub(crate) use manifest_poller::PersistedQueryManifestPoller;
+use multimap::MultiMap;
 use tower::BoxError;
+use tower::ServiceExt;
 
 use self::manifest_poller::FreeformGraphQLAction;
+use self::manifest_poller::FreeformGraphQLSafelist;
+use self::unknown_operations::UnknownOperations;
+use self::unknown_operations::UnknownOperationsService;
 use super::query_analysis::ParsedDocument;
 use crate::graphql::Error as GraphQLError;
+use crate::plugins::telemetry::apollo::client_name_header_default;
+use crate::router_factory::Endpoint;
 use crate::services::SupergraphRequest;
 use crate::services::SupergraphResponse;
 use crate::Configuration;
+use crate::ListenAddr;
 
 const DONT_CACHE_RESPONSE_VALUE: &str = "private, no-cache, must-revalidate";
 
+const PERSISTED_QUERY_ID_KEY: &str = "apollo_persisted_queries::id";
+const PERSISTED_QUERY_CLIENT_NAME_KEY: &str = "apollo_persisted_queries::client_name";
+const PERSISTED_QUERY_OPERATION_NAME_KEY: &str = "apollo_persisted_queries::operation_name";
+const PERSISTED_QUERY_METADATA_KEY: &str = "apollo_persisted_queries::metadata";
+
 struct UsedQueryIdFromManifest;
 
 #[derive(Debug)]
@@ -27,6 +43,11 @@ pub(crate) struct PersistedQueryLayer {
     /// value of the manifest and projected safelist. None if the layer is disabled.
     pub(crate) manifest_poller: Option<PersistedQueryManifestPoller>,
     introspection_enabled: bool,
+    /// Header identifying the client, to find the operations registered for that client
+    client_name_header: HeaderName,
+    /// Statistics of the unknown freeform operations, with the address of their endpoint.
+    /// None if the report is disabled.
+    unknown_operations: Option<(Arc<UnknownOperations>, ListenAddr, String)>,
 }
 
 impl PersistedQueryLayer {
@@ -34,20 +55,61 @@ impl PersistedQueryLayer {
     /// and optionally, an existing persisted query manifest poller.
     pub(crate) async fn new(configuration: &Configuration) -> Result<Self, BoxError> {
         if configuration.persisted_queries.enabled {
+            let report_unknown = &configuration.persisted_queries.report_unknown;
             Ok(Self {
                 manifest_poller: Some(
                     PersistedQueryManifestPoller::new(configuration.clone()).await?,
                 ),
                 introspection_enabled: configuration.supergraph.introspection,
+                client_name_header: client_name_header(configuration),
+                unknown_operations: report_unknown.enabled.then(|| {
+                    (
+                        Arc::new(UnknownOperations::new(
+                            report_unknown.max_operations,
+                            report_unknown.max_client_names,
+                        )),
+                        report_unknown.listen.clone(),
+                        report_unknown.path.clone(),
+                    )
+                }),
             })
         } else {
             Ok(Self {
                 manifest_poller: None,
                 introspection_enabled: configuration.supergraph.introspection,
+                client_name_header: client_name_header(configuration),
+                unknown_operations: None,
             })
         }
     }
 
+    /// Keeps the unknown operations statistics of the layer used before a reload
+    pub(crate) fn carry_over_unknown_operations(&self, previous: &PersistedQueryLayer) {
+        if let (Some((unknown_operations, ..)), Some((previous, ..))) =
+            (&self.unknown_operations, &previous.unknown_operations)
+        {
+            unknown_operations.carry_over(previous);
+        }
+    }
+
+    /// The endpoint listing the most requested unknown operations, if enabled
+    pub(crate) fn web_endpoints(&self) -> MultiMap<ListenAddr, Endpoint> {
+        let mut endpoints = MultiMap::new();
+        if let Some((unknown_operations, listen, path)) = &self.unknown_operations {
+            endpoints.insert(
+                listen.clone(),
+                Endpoint::from_router_service(
+                    path.clone(),
+                    UnknownOperationsService {
+                        unknown_operations: unknown_operations.clone(),
+                    }
+                    .boxed(),
+                ),
+            );
+        }
+        endpoints
+    }
+
     /// Run a request through the layer.
     /// Takes care of:
     /// 1) resolving a persisted query ID to a query body
@@ -66,10 +128,17 @@ impl PersistedQueryLayer {
                 )
             } else if let Some(log_unknown) = manifest_poller.never_allows_freeform_graphql() {
                 // If we don't have an ID and we require an ID, return an error immediately,
-                if log_unknown {
-                    if let Some(operation_body) = request.supergraph_request.body().query.as_ref() {
+                if let Some(operation_body) = request.supergraph_request.body().query.as_ref() {
+                    if log_unknown {
                         log_unknown_operation(operation_body);
                     }
+                    if self.unknown_operations.is_some() {
+                        let ast = ast::Document::parse(operation_body, "operation");
+                        self.record_unknown_operation(
+                            &request,
+                            ast.as_ref().map_err(|_| operation_body.as_str()),
+                        );
+                    }
                 }
                 Err(supergraph_err_pq_id_required(request))
             } else {
@@ -109,12 +178,38 @@ impl PersistedQueryLayer {
         } else {
             // if there is no query, look up the persisted query in the manifest
             // and put the body on the `supergraph_request`
-            if let Some(persisted_query_body) =
-                manifest_poller.get_operation_body(persisted_query_id)
+            let client_name = self.client_name(&request);
+            if let Some(operation) =
+                manifest_poller.get_operation(persisted_query_id, client_name.as_deref())
             {
                 let body = request.supergraph_request.body_mut();
-                body.query = Some(persisted_query_body);
+                body.query = Some(operation.body);
                 body.extensions.remove("persistedQuery");
+                // expose the operation's registration to plugins and telemetry
+                let _ = request
+                    .context
+                    .insert(PERSISTED_QUERY_ID_KEY, persisted_query_id.to_string());
+                if let Some(client_name) = client_name {
+                    let _ = request
+                        .context
+                        .insert(PERSISTED_QUERY_CLIENT_NAME_KEY, client_name);
+                }
+                if let Some(operation_name) = operation.operation_name {
+                    let _ = request
+                        .context
+                        .insert(PERSISTED_QUERY_OPERATION_NAME_KEY, operation_name);
+                }
+                if let Some(metadata) = operation.metadata {
+                    for (key, value) in metadata.iter() {
+                        let _ = request.context.insert(
+                            format!("{PERSISTED_QUERY_METADATA_KEY}::{}", key.as_str()),
+                            value.clone(),
+                        );
+                    }
+                    let _ = request
+                        .context
+                        .insert(PERSISTED_QUERY_METADATA_KEY, metadata);
+                }
                 // Record that we actually used our ID, so we can skip the
                 // safelist check later.
                 request
@@ -208,16 +303,23 @@ impl PersistedQueryLayer {
         } else {
             Err(operation_body.as_str())
         };
-        match manifest_poller.action_for_freeform_graphql(ast_result) {
+        let client_name = self.client_name(&request);
+        match manifest_poller.action_for_freeform_graphql(client_name.as_deref(), ast_result) {
             FreeformGraphQLAction::Allow => {
                 tracing::info!(monotonic_counter.apollo.router.operations.persisted_queries = 1u64,);
                 Ok(request)
             }
+            FreeformGraphQLAction::AllowUnknown => {
+                tracing::info!(monotonic_counter.apollo.router.operations.persisted_queries = 1u64,);
+                self.record_unknown_operation(&request, ast_result);
+                Ok(request)
+            }
             FreeformGraphQLAction::Deny => {
                 tracing::info!(
                     monotonic_counter.apollo.router.operations.persisted_queries = 1u64,
                     persisted_queries.safelist.rejected.unknown = false,
                 );
+                self.record_unknown_operation(&request, ast_result);
                 Err(supergraph_err_operation_not_in_safelist(request))
             }
             // Note that this might even include complaining about an operation that came via APQs.
@@ -227,6 +329,7 @@ impl PersistedQueryLayer {
                     persisted_queries.logged = true
                 );
                 log_unknown_operation(operation_body);
+                self.record_unknown_operation(&request, ast_result);
                 Ok(request)
             }
             FreeformGraphQLAction::DenyAndLog => {
@@ -236,11 +339,43 @@ impl PersistedQueryLayer {
                     persisted_queries.logged = true
                 );
                 log_unknown_operation(operation_body);
+                self.record_unknown_operation(&request, ast_result);
                 Err(supergraph_err_operation_not_in_safelist(request))
             }
         }
     }
 
+    /// Counts a freeform operation that is not in the persisted query list, by normalized
+    /// operation and client name. Operations and client names that the report does not track are
+    /// counted as `__other__`
+    fn record_unknown_operation(
+        &self,
+        request: &SupergraphRequest,
+        ast: Result<&ast::Document, &str>,
+    ) {
+        if let Some((unknown_operations, ..)) = &self.unknown_operations {
+            let client_name = self.client_name(request).unwrap_or_default();
+            let (operation_hash, client_name) = unknown_operations
+                .record(FreeformGraphQLSafelist::normalize_body(ast), &client_name);
+            u64_counter!(
+                "apollo.router.operations.persisted_queries.unknown",
+                "Number of freeform GraphQL operations not found in the persisted query list",
+                1,
+                "operation.hash" = operation_hash,
+                "client.name" = client_name
+            );
+        }
+    }
+
+    fn client_name(&self, request: &SupergraphRequest) -> Option<String> {
+        request
+            .supergraph_request
+            .headers()
+            .get(&self.client_name_header)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+    }
+
     pub(crate) fn all_operations(&self) -> Option<Vec<String>> {
         self.manifest_poller
             .as_ref()
@@ -248,6 +383,19 @@ impl PersistedQueryLayer {
     }
 }
 
+/// The header configured in `telemetry.apollo.client_name_header`
+fn client_name_header(configuration: &Configuration) -> HeaderName {
+    configuration
+        .apollo_plugins
+        .plugins
+        .get("telemetry")
+        .and_then(|telemetry| telemetry.get("apollo"))
+        .and_then(|apollo| apollo.get("client_name_header"))
+        .and_then(|header| header.as_str())
+        .and_then(|header| HeaderName::try_from(header).ok())
+        .unwrap_or_else(client_name_header_default)
+}
+
 fn log_unknown_operation(operation_body: &str) {
     tracing::warn!(message = "unknown operation", operation_body);
 }
@@ -372,9 +520,14 @@ mod tests {
     use super::*;
     use crate::configuration::Apq;
     use crate::configuration::PersistedQueries;
+    use crate::configuration::PersistedQueriesReportUnknown;
     use crate::configuration::PersistedQueriesSafelist;
     use crate::configuration::Supergraph;
+    use crate::files::tests::create_temp_file;
+    use crate::files::tests::write_and_flush;
+    use crate::metrics::FutureMetricsExt;
     use crate::services::layers::persisted_queries::manifest_poller::FreeformGraphQLBehavior;
+    use crate::services::layers::persisted_queries::unknown_operations::OTHER;
     use crate::services::layers::query_analysis::QueryAnalysisLayer;
     use crate::spec::Schema;
     use crate::test_harness::mocks::persisted_queries::*;
@@ -457,6 +610,126 @@ mod tests {
         assert_eq!(request.supergraph_request.body().query, Some(body));
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn pq_layer_restricts_operations_to_their_client() {
+        let (path, mut file) = create_temp_file();
+        write_and_flush(
+            &mut file,
+            &json!({
+                "format": "apollo-persisted-query-manifest",
+                "version": 1,
+                "operations": [{
+                    "id": "1234",
+                    "body": "query Me { me { id } }",
+                    "clientName": "web",
+                    "operationName": "Me",
+                    "metadata": { "ttl": 60 }
+                }, {
+                    "id": "5678",
+                    "body": "query { typename }"
+                }]
+            })
+            .to_string(),
+        )
+        .await;
+
+        let pq_layer = PersistedQueryLayer::new(
+            &Configuration::fake_builder()
+                .persisted_query(
+                    PersistedQueries::builder()
+                        .enabled(true)
+                        .local_manifests(vec![path])
+                        .build(),
+                )
+                .apq(Apq::fake_builder().enabled(false).build())
+                .build()
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+        let request = pq_layer
+            .supergraph_request(
+                SupergraphRequest::fake_builder()
+                    .header("apollographql-client-name", "web")
+                    .extension(
+                        "persistedQuery",
+                        json!({"version": 1, "sha256Hash": "1234"}),
+                    )
+                    .build()
+                    .unwrap(),
+            )
+            .ok()
+            .expect("the operation is registered for this client");
+        assert_eq!(
+            request.supergraph_request.body().query.as_deref(),
+            Some("query Me { me { id } }")
+        );
+        let context = &request.context;
+        assert_eq!(
+            context.get::<_, String>(PERSISTED_QUERY_ID_KEY).unwrap(),
+            Some("1234".to_string())
+        );
+        assert_eq!(
+            context
+                .get::<_, String>(PERSISTED_QUERY_CLIENT_NAME_KEY)
+                .unwrap(),
+            Some("web".to_string())
+        );
+        assert_eq!(
+            context
+                .get::<_, String>(PERSISTED_QUERY_OPERATION_NAME_KEY)
+                .unwrap(),
+            Some("Me".to_string())
+        );
+        assert_eq!(
+            context
+                .get::<_, u64>("apollo_persisted_queries::metadata::ttl")
+                .unwrap(),
+            Some(60)
+        );
+
+        // another client cannot use that ID
+        let response = pq_layer
+            .supergraph_request(
+                SupergraphRequest::fake_builder()
+                    .header("apollographql-client-name", "ios")
+                    .extension(
+                        "persistedQuery",
+                        json!({"version": 1, "sha256Hash": "1234"}),
+                    )
+                    .build()
+                    .unwrap(),
+            )
+            .expect_err("the operation is registered for another client")
+            .next_response()
+            .await
+            .unwrap();
+        assert_eq!(
+            response.errors,
+            vec![graphql_err_operation_not_found("1234")]
+        );
+
+        // operations without a client name are available to all clients
+        let request = pq_layer
+            .supergraph_request(
+                SupergraphRequest::fake_builder()
+                    .header("apollographql-client-name", "ios")
+                    .extension(
+                        "persistedQuery",
+                        json!({"version": 1, "sha256Hash": "5678"}),
+                    )
+                    .build()
+                    .unwrap(),
+            )
+            .ok()
+            .expect("the operation is registered for all clients");
+        assert_eq!(
+            request.supergraph_request.body().query.as_deref(),
+            Some("query { typename }")
+        );
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn pq_layer_passes_on_to_apq_layer_when_id_not_found() {
         let (_id, _body, manifest) = fake_manifest();
@@ -759,6 +1032,109 @@ mod tests {
         ).await;
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn pq_layer_reports_unknown_operations() {
+        async {
+            let (_id, body, manifest) = fake_manifest();
+
+            let (_mock_guard, uplink_config) = mock_pq_uplink(&manifest).await;
+
+            let config = Configuration::fake_builder()
+                .persisted_query(
+                    PersistedQueries::builder()
+                        .enabled(true)
+                        .report_unknown(
+                            PersistedQueriesReportUnknown::builder()
+                                .enabled(true)
+                                .max_operations(1)
+                                .build(),
+                        )
+                        .build(),
+                )
+                .uplink(uplink_config)
+                .apq(Apq::fake_builder().enabled(false).build())
+                .build()
+                .unwrap();
+
+            let pq_layer = PersistedQueryLayer::new(&config).await.unwrap();
+            let schema = Arc::new(
+                Schema::parse_test(
+                    include_str!("../../../testdata/supergraph.graphql"),
+                    &config,
+                )
+                .unwrap(),
+            );
+            let query_analysis_layer = QueryAnalysisLayer::new(schema, Arc::new(config)).await;
+
+            for (query, client_name) in [
+                ("query SomeQuery { me { id } }", "web"),
+                ("query  SomeQuery {me{id}}", "ios"),
+                ("query OtherQuery { me { name } }", "web"),
+                (body.as_str(), "web"),
+            ] {
+                let incoming_request = SupergraphRequest::fake_builder()
+                    .query(query)
+                    .header("apollographql-client-name", client_name)
+                    .build()
+                    .unwrap();
+                let request = pq_layer
+                    .supergraph_request(incoming_request)
+                    .ok()
+                    .expect("pq layer returned error response instead of returning a request");
+                let request = query_analysis_layer
+                    .supergraph_request(request)
+                    .await
+                    .ok()
+                    .expect("QA layer returned error response instead of returning a request");
+                pq_layer
+                    .supergraph_request_with_analyzed_query(request)
+                    .await
+                    .ok()
+                    .expect("reporting unknown operations should not reject them");
+            }
+
+            let report = pq_layer.unknown_operations.as_ref().unwrap().0.report();
+            assert_eq!(report.untracked_requests, 1);
+            assert_eq!(report.operations.len(), 1);
+            let operation = &report.operations[0];
+            assert_eq!(
+                operation.body,
+                FreeformGraphQLSafelist::normalize_body(Ok(&ast::Document::parse(
+                    "query SomeQuery { me { id } }",
+                    "operation"
+                )
+                .unwrap()))
+            );
+            assert_eq!(operation.count, 2);
+            assert_eq!(operation.clients.get("web"), Some(&1));
+            assert_eq!(operation.clients.get("ios"), Some(&1));
+
+            assert_counter!(
+                "apollo.router.operations.persisted_queries.unknown",
+                1,
+                "operation.hash" = operation.hash.clone(),
+                "client.name" = "web"
+            );
+            assert_counter!(
+                "apollo.router.operations.persisted_queries.unknown",
+                1,
+                "operation.hash" = operation.hash.clone(),
+                "client.name" = "ios"
+            );
+            assert_counter!(
+                "apollo.router.operations.persisted_queries.unknown",
+                1,
+                "operation.hash" = OTHER,
+                "client.name" = OTHER
+            );
+
+            let endpoints = pq_layer.web_endpoints();
+            assert_eq!(endpoints.len(), 1);
+        }
+        .with_metrics()
+        .await;
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn pq_layer_rejects_invalid_ids_with_safelisting_enabled() {
         let (_id, _body, manifest) = fake_manifest();
@@ -0,0 +1,216 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::task::Context;
+use std::task::Poll;
+
+use futures::future::BoxFuture;
+use http::StatusCode;
+use serde::Serialize;
+use sha2::Digest;
+use sha2::Sha256;
+use tower::BoxError;
+use tower_service::Service;
+
+use crate::services::router;
+
+/// Reported in place of the operation hashes and client names that are not tracked, which keeps
+/// the number of distinct values of the metric attributes bounded
+pub(crate) const OTHER: &str = "__other__";
+
+/// Usage statistics of the freeform GraphQL operations that are not in the
+/// persisted query list, keyed by the hash of their normalized body.
+///
+/// The number of distinct operations is bounded: once `max_operations` are
+/// tracked, requests for new operations are only counted as untracked. Likewise, once
+/// `max_client_names` clients are counted for an operation, the requests of new clients are
+/// counted as `__other__`.
+#[derive(Debug)]
+pub(crate) struct UnknownOperations {
+    max_operations: usize,
+    max_client_names: usize,
+    inner: Mutex<UnknownOperationsInner>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct UnknownOperationsInner {
+    operations: HashMap<String, UnknownOperation>,
+    untracked_requests: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct UnknownOperation {
+    pub(crate) hash: String,
+    pub(crate) body: String,
+    pub(crate) count: u64,
+    /// Number of requests per client name
+    pub(crate) clients: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct UnknownOperationsReport {
+    /// Tracked operations, most requested first
+    pub(crate) operations: Vec<UnknownOperation>,
+    /// Requests for operations that were not tracked because the report was full
+    pub(crate) untracked_requests: u64,
+}
+
+impl UnknownOperations {
+    pub(crate) fn new(max_operations: usize, max_client_names: usize) -> Self {
+        Self {
+            max_operations,
+            max_client_names,
+            inner: Default::default(),
+        }
+    }
+
+    /// Keeps the statistics collected by a previous instance, so that they survive reloads
+    pub(crate) fn carry_over(&self, previous: &UnknownOperations) {
+        let previous = previous.inner.lock().expect("lock poisoned").clone();
+        *self.inner.lock().expect("lock poisoned") = previous;
+    }
+
+    /// Counts a request for an unknown operation, and returns the hash of its normalized body and
+    /// the client name it was counted under, `__other__` for what is not tracked
+    pub(crate) fn record(&self, normalized_body: String, client_name: &str) -> (String, String) {
+        let hash = hex::encode(Sha256::digest(normalized_body.as_bytes()));
+        let mut inner = self.inner.lock().expect("lock poisoned");
+        let inner = &mut *inner;
+        match inner.operations.get_mut(&hash) {
+            Some(operation) => {
+                let client_name = if operation.clients.contains_key(client_name)
+                    || operation.clients.len() < self.max_client_names
+                {
+                    client_name.to_string()
+                } else {
+                    OTHER.to_string()
+                };
+                operation.count += 1;
+                *operation.clients.entry(client_name.clone()).or_default() += 1;
+                (hash, client_name)
+            }
+            None if inner.operations.len() < self.max_operations => {
+                let client_name = if self.max_client_names > 0 {
+                    client_name.to_string()
+                } else {
+                    OTHER.to_string()
+                };
+                inner.operations.insert(
+                    hash.clone(),
+                    UnknownOperation {
+                        hash: hash.clone(),
+                        body: normalized_body,
+                        count: 1,
+                        clients: BTreeMap::from([(client_name.clone(), 1)]),
+                    },
+                );
+                (hash, client_name)
+            }
+            None => {
+                inner.untracked_requests += 1;
+                (OTHER.to_string(), OTHER.to_string())
+            }
+        }
+    }
+
+    pub(crate) fn report(&self) -> UnknownOperationsReport {
+        let inner = self.inner.lock().expect("lock poisoned");
+        let mut operations: Vec<UnknownOperation> = inner.operations.values().cloned().collect();
+        operations.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.hash.cmp(&b.hash)));
+        UnknownOperationsReport {
+            operations,
+            untracked_requests: inner.untracked_requests,
+        }
+    }
+}
+
+/// Serves the [`UnknownOperationsReport`] as JSON
+#[derive(Clone)]
+pub(crate) struct UnknownOperationsService {
+    pub(crate) unknown_operations: Arc<UnknownOperations>,
+}
+
+impl Service<router::Request> for UnknownOperationsService {
+    type Response = router::Response;
+    type Error = BoxError;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Ok(()).into()
+    }
+
+    fn call(&mut self, req: router::Request) -> Self::Future {
+        let report = self.unknown_operations.report();
+        Box::pin(async move {
+            Ok(router::Response {
+                response: http::Response::builder()
+                    .status(StatusCode::OK)
+                    .header(http::header::CONTENT_TYPE, "application/json")
+                    .body::<hyper::Body>(serde_json::to_vec(&report)?.into())
+                    .map_err(BoxError::from)?,
+                context: req.context,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_most_requested_operations_first() {
+        let unknown_operations = UnknownOperations::new(2, 10);
+        unknown_operations.record("{ a }".to_string(), "web");
+        let (hash, _) = unknown_operations.record("{ b }".to_string(), "web");
+        assert_eq!(
+            unknown_operations.record("{ b }".to_string(), "ios"),
+            (hash.clone(), "ios".to_string())
+        );
+        // the report is full
+        assert_eq!(
+            unknown_operations.record("{ c }".to_string(), "web"),
+            (OTHER.to_string(), OTHER.to_string())
+        );
+
+        let report = unknown_operations.report();
+        assert_eq!(report.untracked_requests, 1);
+        assert_eq!(report.operations.len(), 2);
+        assert_eq!(report.operations[0].hash, hash);
+        assert_eq!(report.operations[0].body, "{ b }");
+        assert_eq!(report.operations[0].count, 2);
+        assert_eq!(
+            report.operations[0].clients,
+            BTreeMap::from([("ios".to_string(), 1), ("web".to_string(), 1)])
+        );
+        assert_eq!(report.operations[1].body, "{ a }");
+        assert_eq!(report.operations[1].count, 1);
+    }
+
+    #[test]
+    fn bounds_the_client_names_of_an_operation() {
+        let unknown_operations = UnknownOperations::new(10, 2);
+        unknown_operations.record("{ a }".to_string(), "web");
+        unknown_operations.record("{ a }".to_string(), "ios");
+        let (_, client_name) = unknown_operations.record("{ a }".to_string(), "android");
+        assert_eq!(client_name, OTHER);
+        unknown_operations.record("{ a }".to_string(), "desktop");
+        // clients seen before are still counted under their name
+        let (_, client_name) = unknown_operations.record("{ a }".to_string(), "web");
+        assert_eq!(client_name, "web");
+
+        let report = unknown_operations.report();
+        assert_eq!(report.operations[0].count, 5);
+        assert_eq!(
+            report.operations[0].clients,
+            BTreeMap::from([
+                ("ios".to_string(), 1),
+                ("web".to_string(), 2),
+                (OTHER.to_string(), 2)
+            ])
+        );
+    }
+}
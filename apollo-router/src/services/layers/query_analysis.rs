@@ -29,6 +29,7 @@ pub(crate) struct QueryAnalysisLayer {
     configuration: Arc<Configuration>,
     cache: Arc<Mutex<LruCache<QueryAnalysisKey, (Context, ParsedDocument)>>>,
     enable_authorization_directives: bool,
+    enable_field_arguments: bool,
 }
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
@@ -41,6 +42,8 @@ impl QueryAnalysisLayer {
     pub(crate) async fn new(schema: Arc<Schema>, configuration: Arc<Configuration>) -> Self {
         let enable_authorization_directives =
             AuthorizationPlugin::enable_directives(&configuration, &schema).unwrap_or(false);
+        let enable_field_arguments = enable_authorization_directives
+            && AuthorizationPlugin::uses_field_arguments(&configuration);
         Self {
             schema,
             cache: Arc::new(Mutex::new(LruCache::new(
@@ -52,6 +55,7 @@ impl QueryAnalysisLayer {
                     .limit,
             ))),
             enable_authorization_directives,
+            enable_field_arguments,
             configuration,
         }
     }
@@ -145,6 +149,15 @@ impl QueryAnalysisLayer {
         };
 
         request.context.extend(&context);
+        if self.enable_field_arguments {
+            AuthorizationPlugin::field_arguments_analysis(
+                &doc,
+                &self.schema,
+                request.supergraph_request.body().operation_name.as_deref(),
+                &request.supergraph_request.body().variables,
+                &request.context,
+            );
+        }
         request
             .context
             .extensions()
@@ -28,6 +28,7 @@ pub(crate) mod external;
 pub(crate) mod http;
 pub(crate) mod layers;
 pub(crate) mod new_service;
+pub(crate) mod query_batching;
 pub(crate) mod query_planner;
 pub mod router;
 pub mod subgraph;
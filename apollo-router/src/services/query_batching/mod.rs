@@ -0,0 +1,494 @@
+//! Grouping of the subgraph fetches of a client batch in batched subgraph requests.
+//!
+//! The operations of a client batch are executed concurrently. When subgraph batching is enabled
+//! for a subgraph, a fetch to this subgraph does not send its request right away: it waits for the
+//! end of the execution wave, reached when every operation of the batch is either waiting on such
+//! a fetch or done executing. The waiting fetches are then grouped by subgraph and outgoing http
+//! request (URL and headers, so that the batched request is valid for each of them), and the first
+//! fetch of each group sends all the requests of the group at once, then hands the responses back
+//! to the other fetches. If the sending fetch is cancelled, the next fetch of the group takes over.
+
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+
+use tokio::sync::oneshot;
+use tower::BoxError;
+
+use crate::configuration::Batching;
+use crate::error::FetchError;
+use crate::query_planner::OperationKind;
+use crate::services::SubgraphRequest;
+use crate::services::SubgraphResponse;
+use crate::Context;
+
+/// What a fetch does at the end of the execution wave
+pub(crate) enum Assignment {
+    /// Sends the request of the fetch along with the requests of the other fetches of the group,
+    /// and hands their responses back to them
+    Send {
+        request: SubgraphRequest,
+        others: BatchedFetches,
+    },
+    /// Uses the response received by another fetch of the group
+    Received(Result<SubgraphResponse, BoxError>),
+}
+
+/// A fetch whose request is sent by another fetch of its group
+pub(crate) struct BatchedFetch {
+    pub(crate) request: SubgraphRequest,
+    sender: oneshot::Sender<Assignment>,
+}
+
+impl BatchedFetch {
+    pub(crate) fn respond(self, response: Result<SubgraphResponse, BoxError>) {
+        // the fetch might have been cancelled in the meantime
+        let _ = self.sender.send(Assignment::Received(response));
+    }
+}
+
+/// The other fetches of a group, handed over to the next one of them if the sending fetch is
+/// cancelled before responding
+pub(crate) struct BatchedFetches(Vec<BatchedFetch>);
+
+impl BatchedFetches {
+    pub(crate) fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &BatchedFetch> {
+        self.0.iter()
+    }
+
+    /// Takes the fetches to respond to them
+    pub(crate) fn take(&mut self) -> Vec<BatchedFetch> {
+        std::mem::take(&mut self.0)
+    }
+}
+
+impl Drop for BatchedFetches {
+    fn drop(&mut self) {
+        let mut fetches = self.take().into_iter();
+        if let Some(next) = fetches.next() {
+            // if the next fetch was cancelled too, the assignment is dropped, which hands the
+            // remaining fetches over to the fetch after it
+            let _ = next.sender.send(Assignment::Send {
+                request: next.request,
+                others: BatchedFetches(fetches.collect()),
+            });
+        }
+    }
+}
+
+struct WaitingFetch {
+    id: u64,
+    query: usize,
+    subgraph_name: String,
+    request: SubgraphRequest,
+    sender: oneshot::Sender<Assignment>,
+}
+
+impl WaitingFetch {
+    /// Whether both requests can be sent as a single http request: the batched request uses the
+    /// URL and headers of the first request of the group
+    fn can_be_batched_with(&self, other: &WaitingFetch) -> bool {
+        let request = &self.request.subgraph_request;
+        let other_request = &other.request.subgraph_request;
+        self.subgraph_name == other.subgraph_name
+            && request.method() == other_request.method()
+            && request.uri() == other_request.uri()
+            && request.headers() == other_request.headers()
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct QueryState {
+    /// Number of fetches of the query waiting for the end of the wave
+    waiting: usize,
+    finished: bool,
+}
+
+#[derive(Default)]
+struct State {
+    queries: Vec<QueryState>,
+    waiting: Vec<WaitingFetch>,
+    next_id: u64,
+}
+
+struct Batch {
+    config: Batching,
+    state: Mutex<State>,
+}
+
+impl Batch {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().expect("lock poisoned")
+    }
+
+    fn end_wave_if_ready(&self) {
+        let mut state = self.lock();
+        if state.waiting.is_empty()
+            || !state
+                .queries
+                .iter()
+                .all(|query| query.finished || query.waiting > 0)
+        {
+            return;
+        }
+        let waiting = std::mem::take(&mut state.waiting);
+        for query in state.queries.iter_mut() {
+            query.waiting = 0;
+        }
+        drop(state);
+
+        let mut groups: Vec<Vec<WaitingFetch>> = Vec::new();
+        for fetch in waiting {
+            match groups
+                .iter_mut()
+                .find(|group| group[0].can_be_batched_with(&fetch))
+            {
+                Some(group) => group.push(fetch),
+                None => groups.push(vec![fetch]),
+            }
+        }
+        for fetches in groups {
+            let mut fetches = fetches.into_iter();
+            let Some(first) = fetches.next() else {
+                continue;
+            };
+            let others = BatchedFetches(
+                fetches
+                    .map(|fetch| BatchedFetch {
+                        request: fetch.request,
+                        sender: fetch.sender,
+                    })
+                    .collect(),
+            );
+            // if the first fetch was cancelled, the assignment is dropped and the next fetch of
+            // the group sends the batch
+            let _ = first.sender.send(Assignment::Send {
+                request: first.request,
+                others,
+            });
+        }
+    }
+}
+
+/// An operation of a client batch, stored in the context extensions of the operation
+#[derive(Clone)]
+pub(crate) struct BatchQuery {
+    batch: Arc<Batch>,
+    index: usize,
+}
+
+impl BatchQuery {
+    /// Creates the operations of a client batch, if subgraph batching is enabled
+    pub(crate) fn new_batch(config: &Batching, size: usize) -> Option<Vec<BatchQuery>> {
+        if !config.batches_subgraphs() {
+            return None;
+        }
+        let batch = Arc::new(Batch {
+            config: config.clone(),
+            state: Mutex::new(State {
+                queries: vec![QueryState::default(); size],
+                ..Default::default()
+            }),
+        });
+        Some(
+            (0..size)
+                .map(|index| BatchQuery {
+                    batch: batch.clone(),
+                    index,
+                })
+                .collect(),
+        )
+    }
+
+    /// Returns the operation of the context, if its fetches to this subgraph are batched
+    ///
+    /// Only queries are batched, mutations and subscriptions are sent as usual.
+    pub(crate) fn for_subgraph(
+        context: &Context,
+        operation_kind: OperationKind,
+        subgraph_name: &str,
+    ) -> Option<BatchQuery> {
+        if operation_kind != OperationKind::Query {
+            return None;
+        }
+        context
+            .extensions()
+            .lock()
+            .get::<BatchQuery>()
+            .filter(|query| query.batch.config.batch_include(subgraph_name))
+            .cloned()
+    }
+
+    /// Waits for the end of the execution wave to know how to send the request
+    pub(crate) async fn wait(&self, subgraph_name: &str, request: SubgraphRequest) -> Assignment {
+        let (sender, receiver) = oneshot::channel();
+        let id = {
+            let mut state = self.batch.lock();
+            let id = state.next_id;
+            state.next_id += 1;
+            state.queries[self.index].waiting += 1;
+            state.waiting.push(WaitingFetch {
+                id,
+                query: self.index,
+                subgraph_name: subgraph_name.to_string(),
+                request,
+                sender,
+            });
+            id
+        };
+        let _guard = WaitGuard { query: self, id };
+
+        // lets the other branches of the query plan reach their fetches before checking whether
+        // the wave is over, so that parallel fetches end up in the same wave
+        tokio::task::yield_now().await;
+        self.batch.end_wave_if_ready();
+
+        receiver.await.unwrap_or_else(|_| {
+            Assignment::Received(Err(FetchError::SubrequestHttpError {
+                status_code: None,
+                service: subgraph_name.to_string(),
+                reason: "the batched request was cancelled".to_string(),
+            }
+            .into()))
+        })
+    }
+
+    /// Marks the operation as done executing, its fetches stop holding the waves back
+    pub(crate) fn finish(&self) {
+        self.batch.lock().queries[self.index].finished = true;
+        self.batch.end_wave_if_ready();
+    }
+}
+
+/// Removes a fetch cancelled before the end of the wave
+struct WaitGuard<'a> {
+    query: &'a BatchQuery,
+    id: u64,
+}
+
+impl Drop for WaitGuard<'_> {
+    fn drop(&mut self) {
+        let mut state = self.query.batch.lock();
+        if let Some(position) = state.waiting.iter().position(|fetch| fetch.id == self.id) {
+            let fetch = state.waiting.remove(position);
+            state.queries[fetch.query].waiting -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::configuration::subgraph::SubgraphConfiguration;
+    use crate::configuration::CommonBatchingConfig;
+
+    fn config() -> Batching {
+        Batching {
+            enabled: true,
+            mode: Default::default(),
+            subgraph: SubgraphConfiguration {
+                all: CommonBatchingConfig { enabled: false },
+                subgraphs: [(
+                    "products".to_string(),
+                    CommonBatchingConfig { enabled: true },
+                )]
+                .into_iter()
+                .collect(),
+            },
+        }
+    }
+
+    fn queries(size: usize) -> Vec<(BatchQuery, Context)> {
+        BatchQuery::new_batch(&config(), size)
+            .unwrap()
+            .into_iter()
+            .map(|query| {
+                let context = Context::new();
+                context.extensions().lock().insert(query.clone());
+                (query, context)
+            })
+            .collect()
+    }
+
+    fn request(context: &Context, query: &str) -> SubgraphRequest {
+        request_with_header(context, query, "web")
+    }
+
+    fn request_with_header(context: &Context, query: &str, client_name: &str) -> SubgraphRequest {
+        SubgraphRequest::fake_builder()
+            .context(context.clone())
+            .subgraph_request(
+                http::Request::builder()
+                    .header("client-name", client_name)
+                    .body(crate::graphql::Request::builder().query(query).build())
+                    .unwrap(),
+            )
+            .build()
+    }
+
+    #[test]
+    fn it_only_batches_queries_to_enabled_subgraphs() {
+        assert!(BatchQuery::new_batch(&Batching::default(), 2).is_none());
+
+        let (_, context) = queries(2).remove(0);
+        assert!(BatchQuery::for_subgraph(&context, OperationKind::Query, "products").is_some());
+        assert!(BatchQuery::for_subgraph(&context, OperationKind::Query, "reviews").is_none());
+        assert!(BatchQuery::for_subgraph(&context, OperationKind::Mutation, "products").is_none());
+    }
+
+    #[tokio::test]
+    async fn it_groups_the_fetches_of_a_wave() {
+        let mut queries = queries(2);
+        let (second, second_context) = queries.pop().unwrap();
+        let (first, first_context) = queries.pop().unwrap();
+
+        let second_fetch = tokio::spawn(async move {
+            second
+                .wait("products", request(&second_context, "{ b }"))
+                .await
+        });
+        let mut others = match first
+            .wait("products", request(&first_context, "{ a }"))
+            .await
+        {
+            Assignment::Send { request, others } => {
+                assert_eq!(
+                    request.subgraph_request.body().query.as_deref(),
+                    Some("{ a }")
+                );
+                others
+            }
+            Assignment::Received(_) => panic!("the first fetch should send the batch"),
+        };
+        assert_eq!(others.len(), 1);
+        let other = others.take().remove(0);
+        assert_eq!(
+            other.request.subgraph_request.body().query.as_deref(),
+            Some("{ b }")
+        );
+
+        other.respond(Ok(SubgraphResponse::fake_builder()
+            .data(serde_json_bytes::json!({ "b": 1 }))
+            .build()));
+        match second_fetch.await.unwrap() {
+            Assignment::Received(Ok(response)) => assert_eq!(
+                response.response.body().data,
+                Some(serde_json_bytes::json!({ "b": 1 }))
+            ),
+            _ => panic!("the second fetch should receive its response"),
+        }
+    }
+
+    #[tokio::test]
+    async fn it_only_groups_fetches_with_the_same_headers() {
+        let mut queries = queries(2);
+        let (second, second_context) = queries.pop().unwrap();
+        let (first, first_context) = queries.pop().unwrap();
+
+        let second_fetch = tokio::spawn(async move {
+            second
+                .wait(
+                    "products",
+                    request_with_header(&second_context, "{ b }", "ios"),
+                )
+                .await
+        });
+        match first
+            .wait("products", request(&first_context, "{ a }"))
+            .await
+        {
+            Assignment::Send { others, .. } => assert!(others.is_empty()),
+            Assignment::Received(_) => panic!("the first fetch should send its request"),
+        }
+        match second_fetch.await.unwrap() {
+            Assignment::Send { request, others } => {
+                assert_eq!(
+                    request.subgraph_request.body().query.as_deref(),
+                    Some("{ b }")
+                );
+                assert!(others.is_empty());
+            }
+            Assignment::Received(_) => panic!("the second fetch should send its request"),
+        }
+    }
+
+    #[tokio::test]
+    async fn it_hands_the_batch_over_when_the_sending_fetch_is_cancelled() {
+        let mut queries = queries(3);
+        let (third, third_context) = queries.pop().unwrap();
+        let (second, second_context) = queries.pop().unwrap();
+        let (first, first_context) = queries.pop().unwrap();
+
+        let second_fetch = tokio::spawn(async move {
+            second
+                .wait("products", request(&second_context, "{ b }"))
+                .await
+        });
+        let third_fetch = tokio::spawn(async move {
+            third
+                .wait("products", request(&third_context, "{ c }"))
+                .await
+        });
+        let others = match first
+            .wait("products", request(&first_context, "{ a }"))
+            .await
+        {
+            Assignment::Send { others, .. } => others,
+            Assignment::Received(_) => panic!("the first fetch should send the batch"),
+        };
+        assert_eq!(others.len(), 2);
+        // the sending fetch is cancelled before responding
+        drop(others);
+
+        match second_fetch.await.unwrap() {
+            Assignment::Send {
+                request,
+                mut others,
+            } => {
+                assert_eq!(
+                    request.subgraph_request.body().query.as_deref(),
+                    Some("{ b }")
+                );
+                assert_eq!(others.len(), 1);
+                others
+                    .take()
+                    .remove(0)
+                    .respond(Ok(SubgraphResponse::fake_builder().build()));
+            }
+            Assignment::Received(_) => panic!("the second fetch should take over the batch"),
+        }
+        assert!(matches!(
+            third_fetch.await.unwrap(),
+            Assignment::Received(Ok(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn it_waits_for_the_running_queries() {
+        let mut queries = queries(2);
+        let (second, _) = queries.pop().unwrap();
+        let (first, first_context) = queries.pop().unwrap();
+
+        let fetch = first.wait("products", request(&first_context, "{ a }"));
+        tokio::pin!(fetch);
+        assert!(tokio::time::timeout(Duration::from_millis(50), &mut fetch)
+            .await
+            .is_err());
+
+        second.finish();
+        match fetch.await {
+            Assignment::Send { others, .. } => assert!(others.is_empty()),
+            Assignment::Received(_) => panic!("the fetch should send its request"),
+        }
+    }
+}
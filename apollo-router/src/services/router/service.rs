@@ -53,6 +53,7 @@ use crate::services::layers::persisted_queries::PersistedQueryLayer;
 use crate::services::layers::query_analysis::QueryAnalysisLayer;
 use crate::services::layers::static_page::StaticPageLayer;
 use crate::services::new_service::ServiceFactory;
+use crate::services::query_batching::BatchQuery;
 use crate::services::router;
 #[cfg(test)]
 use crate::services::supergraph;
@@ -228,6 +229,15 @@ impl RouterService {
         &self,
         supergraph_request: SupergraphRequest,
     ) -> Result<router::Response, BoxError> {
+        // once executed, the operation stops holding the subgraph batches back, whatever the outcome
+        let batch_query = supergraph_request
+            .context
+            .extensions()
+            .lock()
+            .get::<BatchQuery>()
+            .cloned()
+            .map(FinishOnDrop);
+
         let mut request_res = self
             .persisted_query_layer
             .supergraph_request(supergraph_request);
@@ -250,6 +260,7 @@ impl RouterService {
                 },
             },
         };
+        drop(batch_query);
 
         let ClientRequestAccepts {
             wildcard: accepts_wildcard,
@@ -619,11 +630,17 @@ impl RouterService {
         let ok_results = graphql_requests?;
         let mut results = Vec::with_capacity(ok_results.len());
 
+        let mut batch_queries = None;
         if ok_results.len() > 1 {
             context
                 .extensions()
                 .lock()
                 .insert(self.experimental_batching.clone());
+            batch_queries = BatchQuery::new_batch(&self.experimental_batching, ok_results.len())
+                .map(Vec::into_iter);
+        }
+        if let Some(batch_query) = batch_queries.as_mut().and_then(Iterator::next) {
+            context.extensions().lock().insert(batch_query);
         }
 
         let mut ok_results_it = ok_results.into_iter();
@@ -668,6 +685,9 @@ impl RouterService {
                 .extensions()
                 .lock()
                 .insert(self.experimental_batching.clone());
+            if let Some(batch_query) = batch_queries.as_mut().and_then(Iterator::next) {
+                new_context.extensions().lock().insert(batch_query);
+            }
             results.push(SupergraphRequest {
                 supergraph_request: new,
                 // Build a new context. Cloning would cause issues.
@@ -685,6 +705,15 @@ impl RouterService {
     }
 }
 
+/// Marks a batched operation as done executing when dropped
+struct FinishOnDrop(BatchQuery);
+
+impl Drop for FinishOnDrop {
+    fn drop(&mut self) {
+        self.0.finish();
+    }
+}
+
 struct TranslateError<'a> {
     status: StatusCode,
     error: &'a str,
@@ -732,6 +761,7 @@ impl RouterFactory for RouterCreator {
             .plugins()
             .values()
             .for_each(|p| mm.extend(p.web_endpoints()));
+        mm.extend(self.persisted_query_layer.web_endpoints());
         mm
     }
 }
@@ -37,6 +37,9 @@ use uuid::Uuid;
 use super::http::HttpClientServiceFactory;
 use super::http::HttpRequest;
 use super::layers::content_negotiation::GRAPHQL_JSON_RESPONSE_HEADER_VALUE;
+use super::query_batching::Assignment;
+use super::query_batching::BatchQuery;
+use super::query_batching::BatchedFetches;
 use super::Plugins;
 use crate::configuration::TlsClientAuth;
 use crate::error::FetchError;
@@ -220,6 +223,9 @@ impl tower::Service<SubgraphRequest> for SubgraphService {
             String::new()
         };
 
+        let batch_query =
+            BatchQuery::for_subgraph(&request.context, request.operation_kind, &service_name);
+
         let SubgraphRequest {
             subgraph_request,
             context,
@@ -357,6 +363,17 @@ impl tower::Service<SubgraphRequest> for SubgraphService {
 
             let client = client_factory.create(&service_name);
 
+            // Batched fetches wait for the end of the execution wave, then one of them sends
+            // the requests of the whole group without APQ
+            if let Some(batch_query) = batch_query {
+                return match batch_query.wait(&service_name, request).await {
+                    Assignment::Received(response) => response,
+                    Assignment::Send { request, others } => {
+                        call_batched_http(request, others, context, client, &service_name).await
+                    }
+                };
+            }
+
             // If APQ is not enabled, simply make the graphql call
             // with the same request body.
             let apq_enabled = arc_apq_enabled.as_ref();
@@ -636,31 +653,7 @@ async fn call_http(
         .headers_mut()
         .append(ACCEPT, ACCEPT_GRAPHQL_JSON.clone());
 
-    let schema_uri = request.uri();
-    let host = schema_uri.host().unwrap_or_default();
-    let port = schema_uri.port_u16().unwrap_or_else(|| {
-        let scheme = schema_uri.scheme_str();
-        if scheme == Some("https") {
-            443
-        } else if scheme == Some("http") {
-            80
-        } else {
-            0
-        }
-    });
-
-    let path = schema_uri.path();
-
-    let subgraph_req_span = tracing::info_span!("subgraph_request",
-        "otel.kind" = "CLIENT",
-        "net.peer.name" = %host,
-        "net.peer.port" = %port,
-        "http.route" = %path,
-        "http.url" = %schema_uri,
-        "net.transport" = "ip_tcp",
-        "apollo.subgraph.name" = %service_name,
-        "graphql.operation.name" = %operation_name,
-    );
+    let subgraph_req_span = subgraph_request_span(&request, service_name, &operation_name);
 
     // The graphql spec is lax about what strategy to use for processing responses: https://github.com/graphql/graphql-over-http/blob/main/spec/GraphQLOverHTTP.md#processing-the-response
     //
@@ -766,6 +759,183 @@ async fn call_http(
     Ok(SubgraphResponse::new_from_response(resp, context))
 }
 
+/// call_batched_http sends the requests of a subgraph batch as a single http call with a JSON
+/// array body, then hands the responses back to the other fetches of the batch
+///
+/// The fetches of a batch share the same URL and headers. If this call is cancelled, the other
+/// fetches are handed over to one of them, which sends the batch instead.
+async fn call_batched_http(
+    request: SubgraphRequest,
+    mut others: BatchedFetches,
+    context: Context,
+    client: crate::services::http::BoxService,
+    service_name: &str,
+) -> Result<SubgraphResponse, BoxError> {
+    if others.is_empty() {
+        let body = request.subgraph_request.body().clone();
+        return call_http(request, body, context, client, service_name).await;
+    }
+
+    let batch_size = others.len() + 1;
+    tracing::info!(
+        histogram.apollo.router.operations.batching.subgraph.size = batch_size as f64,
+        subgraph.service.name = service_name,
+    );
+
+    let (parts, body) = request.subgraph_request.into_parts();
+    let operation_name = body.operation_name.clone().unwrap_or_default();
+    let bodies: Vec<&graphql::Request> = std::iter::once(&body)
+        .chain(
+            others
+                .iter()
+                .map(|other| other.request.subgraph_request.body()),
+        )
+        .collect();
+    let body = serde_json::to_string(&bodies).expect("JSON serialization should not fail");
+    let mut request = http::Request::from_parts(parts, Body::from(body));
+
+    request
+        .headers_mut()
+        .insert(CONTENT_TYPE, APPLICATION_JSON_HEADER_VALUE.clone());
+    request
+        .headers_mut()
+        .append(ACCEPT, ACCEPT_GRAPHQL_JSON.clone());
+
+    let subgraph_req_span = subgraph_request_span(&request, service_name, &operation_name);
+    let display_body = context.contains_key(LOGGING_DISPLAY_BODY);
+
+    let (parts, content_type, body) =
+        match do_fetch(client, &context, service_name, request, display_body)
+            .instrument(subgraph_req_span)
+            .await
+        {
+            Ok(response) => response,
+            Err(err) => {
+                for other in others.take() {
+                    other.respond(Err(err.clone().into()));
+                }
+                return Err(err.into());
+            }
+        };
+
+    let responses = match (content_type, body) {
+        (Err(err), _) | (Ok(_), Some(Err(err))) => Err(err),
+        (Ok(_), Some(Ok(body))) if parts.status.is_success() => {
+            batched_graphql_responses(service_name, body, batch_size)
+        }
+        (Ok(_), _) => Ok(Vec::new()),
+    };
+    let mut responses = match responses {
+        Ok(responses) if responses.len() == batch_size => responses,
+        Ok(_) => vec![graphql::Response::default(); batch_size],
+        Err(err) => vec![
+            graphql::Response::builder()
+                .error(err.to_graphql_error(None))
+                .build();
+            batch_size
+        ],
+    };
+
+    // Add an error for response codes that are not 2xx
+    if !parts.status.is_success() {
+        let status = parts.status;
+        let error = FetchError::SubrequestHttpError {
+            service: service_name.to_string(),
+            status_code: Some(status.as_u16()),
+            reason: format!(
+                "{}: {}",
+                status.as_str(),
+                status.canonical_reason().unwrap_or("Unknown")
+            ),
+        }
+        .to_graphql_error(None);
+        for response in responses.iter_mut() {
+            response.errors.insert(0, error.clone());
+        }
+    }
+
+    let mut responses = responses.into_iter();
+    let response = responses
+        .next()
+        .expect("there is one response per request of the batch");
+    for (other, other_response) in others.take().into_iter().zip(responses) {
+        let mut resp = http::Response::new(other_response);
+        *resp.status_mut() = parts.status;
+        *resp.version_mut() = parts.version;
+        *resp.headers_mut() = parts.headers.clone();
+        let context = other.request.context.clone();
+        other.respond(Ok(SubgraphResponse::new_from_response(resp, context)));
+    }
+
+    let resp = http::Response::from_parts(parts, response);
+    Ok(SubgraphResponse::new_from_response(resp, context))
+}
+
+/// Parses the JSON array of responses to a subgraph batch, in the order of the requests
+fn batched_graphql_responses(
+    service_name: &str,
+    body: Bytes,
+    batch_size: usize,
+) -> Result<Vec<graphql::Response>, FetchError> {
+    let malformed = |reason: String| FetchError::SubrequestMalformedResponse {
+        service: service_name.to_string(),
+        reason,
+    };
+    let values = match serde_json_bytes::Value::from_bytes(body) {
+        Ok(serde_json_bytes::Value::Array(values)) => values,
+        Ok(_) => return Err(malformed("expected a JSON array of responses".to_string())),
+        Err(err) => return Err(malformed(err.to_string())),
+    };
+    if values.len() != batch_size {
+        return Err(malformed(format!(
+            "expected {batch_size} responses to the batch, received {}",
+            values.len()
+        )));
+    }
+    Ok(values
+        .into_iter()
+        .map(|value| {
+            graphql::Response::from_value(service_name, value).unwrap_or_else(|error| {
+                graphql::Response::builder()
+                    .error(error.to_graphql_error(None))
+                    .build()
+            })
+        })
+        .collect())
+}
+
+fn subgraph_request_span(
+    request: &http::Request<Body>,
+    service_name: &str,
+    operation_name: &str,
+) -> tracing::Span {
+    let schema_uri = request.uri();
+    let host = schema_uri.host().unwrap_or_default();
+    let port = schema_uri.port_u16().unwrap_or_else(|| {
+        let scheme = schema_uri.scheme_str();
+        if scheme == Some("https") {
+            443
+        } else if scheme == Some("http") {
+            80
+        } else {
+            0
+        }
+    });
+
+    let path = schema_uri.path();
+
+    tracing::info_span!("subgraph_request",
+        "otel.kind" = "CLIENT",
+        "net.peer.name" = %host,
+        "net.peer.port" = %port,
+        "http.route" = %path,
+        "http.url" = %schema_uri,
+        "net.transport" = "ip_tcp",
+        "apollo.subgraph.name" = %service_name,
+        "graphql.operation.name" = %operation_name,
+    )
+}
+
 enum ContentType {
     ApplicationJson,
     ApplicationGraphqlResponseJson,
@@ -1043,6 +1213,7 @@ mod tests {
     use SubgraphRequest;
 
     use super::*;
+    use crate::configuration::Batching;
     use crate::graphql::Error;
     use crate::graphql::Request;
     use crate::graphql::Response;
@@ -1161,6 +1332,31 @@ mod tests {
         server.await.unwrap();
     }
 
+    // starts a local server emulating a subgraph answering batched requests
+    async fn emulate_subgraph_batched_response(listener: TcpListener) {
+        async fn handle(request: http::Request<Body>) -> Result<http::Response<Body>, Infallible> {
+            let body = hyper::body::to_bytes(request.into_body()).await.unwrap();
+            let requests: Vec<Request> = serde_json::from_slice(&body).unwrap();
+            let responses: Vec<_> = requests
+                .iter()
+                .map(|request| {
+                    serde_json::json!({
+                        "data": { "query": request.query, "batch_size": requests.len() }
+                    })
+                })
+                .collect();
+            Ok(http::Response::builder()
+                .header(CONTENT_TYPE, APPLICATION_JSON.essence_str())
+                .status(StatusCode::OK)
+                .body(serde_json::to_string(&responses).unwrap().into())
+                .unwrap())
+        }
+
+        let make_svc = make_service_fn(|_conn| async { Ok::<_, Infallible>(service_fn(handle)) });
+        let server = Server::from_tcp(listener).unwrap().serve(make_svc);
+        server.await.unwrap();
+    }
+
     // starts a local server emulating a subgraph returning bad response format
     async fn emulate_subgraph_application_graphql_response(listener: TcpListener) {
         async fn handle(_request: http::Request<Body>) -> Result<http::Response<Body>, Infallible> {
@@ -1784,6 +1980,60 @@ mod tests {
         assert!(response.response.body().errors.is_empty());
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_subgraph_service_batched_requests() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let socket_addr = listener.local_addr().unwrap();
+        tokio::task::spawn(emulate_subgraph_batched_response(listener));
+        let subgraph_service = SubgraphService::new(
+            "test",
+            true,
+            None,
+            Notify::default(),
+            HttpClientServiceFactory::from_config(
+                "test",
+                &Configuration::default(),
+                Http2Config::Enable,
+            ),
+        )
+        .expect("can create a SubgraphService");
+        let batching: Batching = serde_json::from_value(serde_json::json!({
+            "enabled": true,
+            "mode": "batch_http_link",
+            "subgraph": { "all": { "enabled": true } }
+        }))
+        .unwrap();
+
+        let url = Uri::from_str(&format!("http://{socket_addr}")).unwrap();
+        let queries = ["query { a }", "query { b }"];
+        let requests = BatchQuery::new_batch(&batching, queries.len())
+            .unwrap()
+            .into_iter()
+            .zip(queries)
+            .map(|(batch_query, query)| {
+                let context = Context::new();
+                context.extensions().lock().insert(batch_query);
+                SubgraphRequest::builder()
+                    .supergraph_request(supergraph_request(query))
+                    .subgraph_request(subgraph_http_request(url.clone(), query))
+                    .operation_kind(OperationKind::Query)
+                    .subgraph_name(String::from("test"))
+                    .context(context)
+                    .build()
+            });
+        let responses = futures::future::join_all(
+            requests.map(|request| subgraph_service.clone().oneshot(request)),
+        )
+        .await;
+
+        for (response, query) in responses.into_iter().zip(queries) {
+            assert_eq!(
+                response.unwrap().response.body().data,
+                Some(serde_json_bytes::json!({ "query": query, "batch_size": 2 }))
+            );
+        }
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn test_subgraph_service_invalid_response() {
         let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
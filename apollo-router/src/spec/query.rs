@@ -1130,7 +1130,7 @@ struct FormatParameters<'a> {
 pub(crate) struct Operation {
     pub(crate) name: Option<String>,
     kind: OperationKind,
-    type_name: String,
+    pub(crate) type_name: String,
     pub(crate) selection_set: Vec<Selection>,
     variables: HashMap<ByteString, Variable>,
 }